@@ -0,0 +1,190 @@
+//! Timed actions for choreographing several devices together
+use crate::devices::ThreadGuard;
+use crate::timing::{Clock, StoppableClock, TimingStrategy};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+type Action = Box<dyn FnMut() + Send>;
+
+/// Plays a script of timed actions from a single background thread, e.g. to turn on an LED
+/// at 0.5s, beep a buzzer at 1.0s and move a servo at 1.5s.
+///
+/// Each action is given the time it runs at, measured from the start of the show.
+/// Actions run one at a time in time order, so a slow action delays the ones after it.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+///
+/// let led = Arc::new(Mutex::new(LED::new(17)));
+/// let mut show = Choreographer::new();
+/// let on = Arc::clone(&led);
+/// show.at(Duration::from_millis(500), Box::new(move || on.lock().unwrap().on()));
+/// let off = Arc::clone(&led);
+/// show.at(Duration::from_millis(1000), Box::new(move || off.lock().unwrap().off()));
+/// show.run();
+/// show.wait();
+/// ```
+pub struct Choreographer {
+    actions: Arc<Mutex<Vec<(Duration, Action)>>>,
+    period: Option<Duration>,
+    running: Arc<AtomicBool>,
+    handle: ThreadGuard,
+}
+
+impl Choreographer {
+    /// Returns a Choreographer with no actions, which plays once
+    pub fn new() -> Choreographer {
+        let running = Arc::new(AtomicBool::new(false));
+        Choreographer {
+            actions: Arc::new(Mutex::new(Vec::new())),
+            period: None,
+            handle: ThreadGuard::new(Arc::clone(&running)),
+            running,
+        }
+    }
+
+    /// Add an action to the show. It takes effect from the next `run`, and can be added while
+    /// the show is playing.
+    /// * `time` - When to run the action, from the start of the show
+    /// * `action` - The function to call from the background thread
+    pub fn at(&mut self, time: Duration, action: Action) {
+        let mut actions = lock_actions(&self.actions);
+        actions.push((time, action));
+        // stable, so actions at the same time run in the order they were added
+        actions.sort_by_key(|(time, _)| *time);
+    }
+
+    /// Set whether the show repeats, which takes effect from the next `run`.
+    /// * `period` - How long each repetition lasts, or `None` to play the show once
+    pub fn set_loop(&mut self, period: Option<Duration>) {
+        if period == Some(Duration::from_secs(0)) {
+            println!("loop period must be greater than 0");
+        } else {
+            self.period = period;
+        }
+    }
+
+    /// Start playing the show in the background from the beginning,
+    /// stopping any show that is already playing.
+    pub fn run(&mut self) {
+        self.stop();
+
+        let actions = Arc::clone(&self.actions);
+        let running = Arc::clone(&self.running);
+        let period = self.period;
+        running.store(true, Ordering::SeqCst);
+        self.handle.spawn(move || {
+            // play from a snapshot of the script, so `at` doesn't wait for the show to finish
+            let mut script = std::mem::take(&mut *lock_actions(&actions));
+            if play(&mut script, period, &running) {
+                running.store(false, Ordering::SeqCst);
+            }
+            // put the script back, followed by any actions added while it was playing
+            let mut actions = lock_actions(&actions);
+            let added = std::mem::replace(&mut *actions, script);
+            actions.extend(added);
+            actions.sort_by_key(|(time, _)| *time);
+        });
+    }
+
+    /// Returns ``True`` if the show is playing.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Block until the show has finished playing.
+    /// Returns immediately if it isn't playing, and never returns for a looping show that isn't stopped.
+    pub fn wait(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+
+    /// Stop the show, waiting for the action being run, if any, to finish.
+    pub fn stop(&mut self) {
+        self.handle.stop();
+    }
+}
+
+// Lock the script, even if an action panicked while the show was playing
+fn lock_actions(
+    actions: &Mutex<Vec<(Duration, Action)>>,
+) -> MutexGuard<'_, Vec<(Duration, Action)>> {
+    actions.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Run the actions of `script` at their times until the show ends or `running` is cleared.
+// Returns ``False`` if the show was stopped.
+fn play(script: &mut [(Duration, Action)], period: Option<Duration>, running: &AtomicBool) -> bool {
+    let mut clock = StoppableClock::new(TimingStrategy::default(), running);
+    let mut start = Instant::now();
+    loop {
+        for (time, action) in script.iter_mut() {
+            clock.sleep((start + *time).saturating_duration_since(Instant::now()));
+            if !running.load(Ordering::SeqCst) {
+                return false;
+            }
+            action();
+        }
+        match period {
+            None => return true,
+            Some(period) => {
+                start += period;
+                clock.sleep(start.saturating_duration_since(Instant::now()));
+                if !running.load(Ordering::SeqCst) {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Choreographer {
+    fn default() -> Choreographer {
+        Choreographer::new()
+    }
+}
+
+impl Drop for Choreographer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn actions_can_be_added_while_a_looping_show_plays() {
+        let (sender, receiver) = mpsc::channel();
+        let mut show = Choreographer::new();
+        let first = sender.clone();
+        show.at(
+            Duration::from_millis(0),
+            Box::new(move || first.send(1).unwrap()),
+        );
+        show.set_loop(Some(Duration::from_millis(20)));
+        show.run();
+        assert_eq!(receiver.recv().unwrap(), 1);
+
+        // returns without waiting for the show, which never ends on its own
+        show.at(
+            Duration::from_millis(10),
+            Box::new(move || sender.send(2).unwrap()),
+        );
+        show.stop();
+        while receiver.try_recv().is_ok() {}
+
+        show.set_loop(None);
+        show.run();
+        show.wait();
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+}
@@ -1,6 +1,15 @@
 //! Describes generic devices such as `GPIODevice` and `CompositeDevice`
 
-use rppal::gpio::{Gpio, Level, Pin};
+use crate::error::GpioZeroError;
+use crate::pins::{pin_factory, InputPin, IoPin};
+use rppal::gpio::{self, Level, Mode, PullUpDown};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 /// Represents a single device of any type; GPIO-based, SPI-based, I2C-based,
 /// etc.  It defines the basic services applicable to all devices
@@ -10,6 +19,458 @@ pub trait Device {
 
     /// Returns ``True`` if the device is currently active and ``False`` otherwise.
     fn is_active(&self) -> bool;
+
+    /// Returns ``True`` if the device has been shut down and ``False`` otherwise.
+    /// `close` takes the device by value, so a device that can still be used is never closed
+    /// and this returns ``False``; it is provided so code written against the trait can check
+    /// it the same way as in gpiozero.
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+macro_rules! impl_device_trait {
+    ($($t:ty),*) => {
+        $(
+            impl Device for $t {
+                fn close(self) {
+                    <$t>::close(self)
+                }
+
+                fn is_active(&self) -> bool {
+                    <$t>::is_active(self)
+                }
+            }
+        )*
+    };
+}
+
+/// Represents a device whose state can be read and set as a number between 0.0 and 1.0,
+/// so that digital and PWM devices can be handled uniformly.
+/// Digital devices report either 0.0 or 1.0, and are turned on by any value above 0.0.
+pub trait ValueDevice {
+    /// Returns the device's value, between 0.0 (off) and 1.0 (fully on).
+    fn value(&self) -> f64;
+
+    /// Set the device's value, between 0.0 (off) and 1.0 (fully on).
+    fn set_value(&mut self, value: f64);
+}
+
+/// Represents any output device that can be turned on and off.
+/// The trait is object safe, so devices of different types can be stored together,
+/// e.g. in a `Vec<Box<dyn OutputDeviceTrait>>`, and shut down in a loop.
+pub trait OutputDeviceTrait: ValueDevice {
+    /// Turns the device on.
+    fn on(&mut self);
+
+    /// Turns the device off.
+    fn off(&mut self);
+
+    /// Shut down the device and release all associated resources.
+    fn close(self: Box<Self>) {
+        drop(self)
+    }
+}
+
+/// Number of times `with_retry` attempts an operation before giving up, unless a
+/// `DeviceFactory` sets another count with `set_retry_attempts`
+pub(crate) const RETRY_ATTEMPTS: u32 = 3;
+
+/// How long `with_retry` waits between attempts
+pub(crate) const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+thread_local! {
+    // The count set by `with_retry_attempts` on this thread, which takes precedence over `RETRY_ATTEMPTS`
+    static SCOPED_RETRY_ATTEMPTS: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Returns the number of attempts `with_retry` should make on the current thread
+pub(crate) fn retry_attempts() -> u32 {
+    SCOPED_RETRY_ATTEMPTS.with(|scoped| scoped.get().unwrap_or(RETRY_ATTEMPTS))
+}
+
+/// Run `f` with `retry_attempts` returning `attempts`, restoring the previous count afterwards
+pub(crate) fn with_retry_attempts<R, F: FnOnce() -> R>(attempts: u32, f: F) -> R {
+    // restores the previous count even if `f` panics
+    struct Restore(Option<u32>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0;
+            SCOPED_RETRY_ATTEMPTS.with(|scoped| scoped.set(previous));
+        }
+    }
+    let _restore = Restore(SCOPED_RETRY_ATTEMPTS.with(|scoped| scoped.replace(Some(attempts))));
+    f()
+}
+
+/// Errors which `with_retry` can tell apart, so that only failures which may clear up by
+/// themselves are retried
+pub(crate) trait Transient {
+    /// Returns `true` if trying the operation again might succeed
+    fn is_transient(&self) -> bool;
+}
+
+impl Transient for gpio::Error {
+    // A pin that doesn't exist or can't be accessed stays that way, but I/O can fail on a busy system
+    fn is_transient(&self) -> bool {
+        matches!(self, gpio::Error::Io(_))
+    }
+}
+
+impl Transient for GpioZeroError {
+    fn is_transient(&self) -> bool {
+        match self {
+            GpioZeroError::Io(_) | GpioZeroError::Pwm(_) => true,
+            GpioZeroError::Gpio(e) => e.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+/// Run `op` until it succeeds, up to `attempts` times with `delay` between attempts,
+/// returning the last error if every attempt fails.
+/// rppal calls such as acquiring a pin or setting the PWM can fail transiently on a busy system,
+/// so constructors and PWM writes go through this rather than failing on the first error.
+/// Errors that aren't transient, e.g. a pin that is in use or the wrong permissions, are
+/// returned straight away.
+pub(crate) fn with_retry<T, E, F>(attempts: u32, delay: Duration, mut op: F) -> Result<T, E>
+where
+    E: Transient,
+    F: FnMut() -> Result<T, E>,
+{
+    let mut result = op();
+    for _ in 1..attempts {
+        match &result {
+            Err(e) if e.is_transient() => {}
+            _ => break,
+        }
+        thread::sleep(delay);
+        result = op();
+    }
+    result
+}
+
+/// Owns a device's background thread together with the flag that keeps it running.
+/// Starting a thread with `spawn` first stops and joins the thread before it, and `stop`, which is
+/// also called when the guard is dropped, clears the flag and joins the thread. A device therefore
+/// never has two threads writing to its pin, and no thread outlives the device holding its pin.
+/// Joining waits for as long as the thread takes, so threads must check the flag at least every
+/// few milliseconds, e.g. by sleeping with a `StoppableClock`.
+#[derive(Debug)]
+pub(crate) struct ThreadGuard {
+    running: Arc<AtomicBool>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ThreadGuard {
+    /// Returns a guard with no thread, for the thread's running flag given
+    pub(crate) fn new(running: Arc<AtomicBool>) -> ThreadGuard {
+        ThreadGuard {
+            running,
+            handle: Mutex::new(None),
+        }
+    }
+
+    /// Returns the thread's running flag, e.g. for a thread to sleep on with a `StoppableClock`
+    pub(crate) fn running(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.running)
+    }
+
+    /// Stop the thread started before, if any, then set the running flag and run `f` on a new thread.
+    pub(crate) fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.stop();
+        self.running.store(true, Ordering::SeqCst);
+        *self.handle.lock().unwrap_or_else(PoisonError::into_inner) = Some(thread::spawn(f));
+    }
+
+    /// Takes the handle of the thread out of the guard, e.g. to wait for it to finish on its own.
+    pub(crate) fn take(&self) -> Option<JoinHandle<()>> {
+        self.handle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    /// Signal the thread to stop and wait for it to finish.
+    /// When called from the thread itself, e.g. by a callback it runs, the thread is only signalled.
+    pub(crate) fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.take() {
+            if handle.thread().id() != thread::current().id() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+impl Drop for ThreadGuard {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// How long before the end of `sleep_micros` to stop sleeping and start spinning, in microseconds
+const SPIN_MICROS: u64 = 200;
+
+/// Sleep for the given number of microseconds.
+/// `thread::sleep` can overshoot by tens of microseconds or more, so this sleeps for the bulk
+/// of the time and busy waits for the remainder to get sub-millisecond precision.
+pub(crate) fn sleep_micros(us: u64) {
+    let deadline = Instant::now() + Duration::from_micros(us);
+    if us > SPIN_MICROS {
+        thread::sleep(Duration::from_micros(us - SPIN_MICROS));
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+/// Physical pin numbers on the 40-pin header and the BCM GPIO number they are connected to.
+/// Pins not listed are power, ground or not connected.
+const BOARD_TO_BCM: [(u8, u8); 28] = [
+    (3, 2),
+    (5, 3),
+    (7, 4),
+    (8, 14),
+    (10, 15),
+    (11, 17),
+    (12, 18),
+    (13, 27),
+    (15, 22),
+    (16, 23),
+    (18, 24),
+    (19, 10),
+    (21, 9),
+    (22, 25),
+    (23, 11),
+    (24, 8),
+    (26, 7),
+    (27, 0),
+    (28, 1),
+    (29, 5),
+    (31, 6),
+    (32, 12),
+    (33, 13),
+    (35, 19),
+    (36, 16),
+    (37, 26),
+    (38, 20),
+    (40, 21),
+];
+
+/// Converts a physical pin number on the 40-pin header (1-40) to its BCM GPIO number.
+/// Returns `None` for pins that are not GPIO pins, such as power and ground.
+pub fn board_to_bcm(physical: u8) -> Option<u8> {
+    BOARD_TO_BCM
+        .iter()
+        .find(|(board, _)| *board == physical)
+        .map(|(_, bcm)| *bcm)
+}
+
+/// Converts a BCM GPIO number to its physical pin number on the 40-pin header (1-40).
+/// Returns `None` for GPIOs that are not on the header.
+pub fn bcm_to_board(bcm: u8) -> Option<u8> {
+    BOARD_TO_BCM
+        .iter()
+        .find(|(_, gpio)| *gpio == bcm)
+        .map(|(board, _)| *board)
+}
+
+/// Returns ``True`` if the BCM GPIO pin given can be driven by one of the hardware PWM channels.
+/// Software PWM works on any pin, but hardware PWM is only available on GPIO 12 and 18 (channel 0)
+/// and GPIO 13 and 19 (channel 1).
+pub fn supports_hardware_pwm(pin: u8) -> bool {
+    matches!(pin, 12 | 13 | 18 | 19)
+}
+
+/// Returns ``True`` if the BCM GPIO pin given can be used to construct a device right now.
+/// The pin is acquired and immediately released, without changing its mode or level.
+/// Returns ``False`` if the GPIO peripheral can't be accessed or the pin is already in use.
+///
+/// Pins are claimed through the current pin factory. With rppal, which only knows about pins held
+/// by this process, a pin used by another program through the memory-mapped registers may still be
+/// reported as available.
+pub fn pin_available(pin: u8) -> bool {
+    reserved_by(pin).is_none() && pin_factory().pin_available(pin)
+}
+
+// The pins reserved by the devices in this process, keyed by the id of the factory they came
+// from and the pin number, and the type of the device holding each one
+static RESERVATIONS: Mutex<BTreeMap<(String, u8), &'static str>> = Mutex::new(BTreeMap::new());
+
+fn reservations() -> MutexGuard<'static, BTreeMap<(String, u8), &'static str>> {
+    RESERVATIONS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the type of the device holding the BCM GPIO pin given on the current pin factory,
+/// e.g. `"LED"`, or `None` if no device in this process has reserved it.
+pub fn reserved_by(pin: u8) -> Option<&'static str> {
+    reservations().get(&(pin_factory().id(), pin)).copied()
+}
+
+/// A pin reserved for a single device, so that a second device on the same pin of the same
+/// factory fails with `GpioZeroError::PinInUse` naming the first. The pin is released when the
+/// reservation is dropped, which happens when the device holding it is dropped or closed.
+#[derive(Debug)]
+pub(crate) struct Reservation {
+    key: (String, u8),
+}
+
+impl Reservation {
+    /// Reserve `pin` of the factory with the id `factory` for a device of the type `owner`,
+    /// unless another device already holds it
+    pub(crate) fn new(
+        factory: String,
+        pin: u8,
+        owner: &'static str,
+    ) -> Result<Reservation, GpioZeroError> {
+        let key = (factory, pin);
+        let mut reservations = reservations();
+        if let Some(holder) = reservations.get(&key) {
+            return Err(GpioZeroError::PinInUse(pin, holder));
+        }
+        reservations.insert(key.clone(), owner);
+        Ok(Reservation { key })
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        reservations().remove(&self.key);
+    }
+}
+
+/// Reserve `pin` for a device of the type `owner` and acquire it from the pin factory as an input.
+/// The reservation should be kept alongside the pin for as long as the device exists.
+pub(crate) fn claim_input(
+    pin: u8,
+    owner: &'static str,
+    pull: PullUpDown,
+) -> Result<(Reservation, Box<dyn InputPin>), GpioZeroError> {
+    let factory = pin_factory();
+    let reservation = Reservation::new(factory.id(), pin, owner)?;
+    let pin = factory.input_pin(pin, pull)?;
+    Ok((reservation, pin))
+}
+
+/// Reserve `pin` for a device of the type `owner` and acquire it from the pin factory for
+/// reading and writing, starting in `mode`.
+pub(crate) fn claim_io(
+    pin: u8,
+    owner: &'static str,
+    mode: Mode,
+) -> Result<(Reservation, Box<dyn IoPin>), GpioZeroError> {
+    let factory = pin_factory();
+    let reservation = Reservation::new(factory.id(), pin, owner)?;
+    let pin = factory.io_pin(pin, mode)?;
+    Ok((reservation, pin))
+}
+
+/// Parses a pin label into its BCM GPIO number.
+/// Accepts a plain BCM number (`"17"`), a BCM number with a `GPIO` or `BCM` prefix (`"GPIO17"`, `"BCM17"`),
+/// or a physical pin number with a `BOARD` prefix (`"BOARD11"`). Prefixes are case insensitive.
+/// Returns `GpioZeroError::InvalidArgument` if the label isn't in one of these forms or
+/// doesn't name a GPIO pin on the 40-pin header.
+pub fn parse_pin_label(label: &str) -> Result<u8, GpioZeroError> {
+    let label = label.trim();
+    let upper = label.to_ascii_uppercase();
+    let (numbering, number) = if let Some(number) = upper.strip_prefix("GPIO") {
+        (PinNumbering::Bcm, number)
+    } else if let Some(number) = upper.strip_prefix("BCM") {
+        (PinNumbering::Bcm, number)
+    } else if let Some(number) = upper.strip_prefix("BOARD") {
+        (PinNumbering::Board, number)
+    } else {
+        (PinNumbering::Bcm, upper.as_str())
+    };
+    let invalid = || {
+        GpioZeroError::InvalidArgument(format!(
+            "{:?} is not a pin label, expected e.g. \"17\", \"GPIO17\", \"BCM17\" or \"BOARD11\"",
+            label
+        ))
+    };
+    let pin = number.trim().parse::<u8>().map_err(|_| invalid())?;
+    let bcm = numbering.to_bcm(pin).ok_or_else(invalid)?;
+    if bcm_to_board(bcm).is_none() {
+        return Err(invalid());
+    }
+    Ok(bcm)
+}
+
+/// The ways a pin can be numbered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinNumbering {
+    /// Broadcom GPIO numbering, as used by the rest of the library
+    Bcm,
+    /// Physical pin numbering on the 40-pin header
+    Board,
+}
+
+impl PinNumbering {
+    /// Converts a pin number in this numbering scheme to its BCM GPIO number.
+    /// Returns `None` if the pin is not a GPIO pin.
+    pub fn to_bcm(self, pin: u8) -> Option<u8> {
+        match self {
+            PinNumbering::Bcm => Some(pin),
+            PinNumbering::Board => board_to_bcm(pin),
+        }
+    }
+}
+
+// Takes the fallible constructor `from_label` builds the device with, `Self::try_new` by default
+macro_rules! impl_pin_numbering {
+    () => {
+        impl_pin_numbering!(Self::try_new);
+    };
+    ($try_new:expr) => {
+        /// Returns a device with the pin number given in the numbering scheme given
+        /// * `pin` - The pin which the device is attached to
+        /// * `numbering` - The numbering scheme `pin` is given in
+        pub fn new_with_numbering(pin: u8, numbering: PinNumbering) -> Self {
+            match numbering.to_bcm(pin) {
+                None => panic!("Pin {} is not a GPIO pin in {:?} numbering", pin, numbering),
+                Some(pin) => Self::new(pin),
+            }
+        }
+
+        /// Returns a device with the pin given as a label, e.g. `"17"`, `"GPIO17"`, `"BCM17"` or
+        /// `"BOARD11"`, or an error if the label isn't recognized or the pin can't be accessed.
+        /// See `parse_pin_label`.
+        /// * `label` - The pin which the device is attached to
+        pub fn from_label(label: &str) -> Result<Self, crate::error::GpioZeroError> {
+            crate::devices::parse_pin_label(label).and_then($try_new)
+        }
+    };
+}
+
+macro_rules! impl_tags {
+    () => {
+        /// Attach a piece of metadata to the device, e.g. its location or circuit, replacing
+        /// any value the key had. Tagged devices can be found with `registry::find_by_tag`.
+        /// * `key` - The name of the tag
+        /// * `value` - The value of the tag
+        pub fn set_tag(&mut self, key: &str, value: &str) {
+            self.tags.set(key, value)
+        }
+
+        /// Returns the value of the tag `key`, or `None` if it isn't set.
+        pub fn tag(&self, key: &str) -> Option<&str> {
+            self.tags.get(key)
+        }
+
+        /// Returns all of the device's tags.
+        pub fn tags(&self) -> &HashMap<String, String> {
+            self.tags.all()
+        }
+
+        /// Remove the tag `key` from the device, returning its value if it was set.
+        pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+            self.tags.remove(key)
+        }
+    };
 }
 
 #[macro_export]
@@ -29,9 +490,10 @@ macro_rules! impl_device {
 /// Represents a generic GPIO device and provides the services common to all single-pin GPIO devices
 #[derive(Debug)]
 pub struct GpioDevice {
-    pin: Pin,
+    pin: Box<dyn InputPin>,
     active_state: bool,
     inactive_state: bool,
+    _reservation: Reservation,
 }
 
 macro_rules! impl_gpio_device {
@@ -49,15 +511,13 @@ impl GpioDevice {
     ///
     /// * `pin` - The GPIO pin which the device is attached to
     pub fn new(pin: u8) -> GpioDevice {
-        match Gpio::new() {
+        match claim_input(pin, "GpioDevice", PullUpDown::Off) {
             Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => GpioDevice {
-                    pin,
-                    active_state: true,
-                    inactive_state: false,
-                },
+            Ok((reservation, pin)) => GpioDevice {
+                pin,
+                active_state: true,
+                inactive_state: false,
+                _reservation: reservation,
             },
         }
     }
@@ -77,3 +537,40 @@ impl GpioDevice {
     impl_device!();
     impl_gpio_device!();
 }
+
+impl_device_trait!(GpioDevice);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn with_retry_retries_transient_errors() {
+        let mut calls = 0;
+        let result: Result<(), GpioZeroError> = with_retry(3, Duration::from_millis(0), || {
+            calls += 1;
+            Err(io::Error::other("busy").into())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn with_retry_gives_up_on_errors_that_are_not_transient() {
+        let mut calls = 0;
+        let result: Result<(), GpioZeroError> = with_retry(3, Duration::from_millis(0), || {
+            calls += 1;
+            Err(GpioZeroError::PinInUse(4, "LED"))
+        });
+        assert!(matches!(result, Err(GpioZeroError::PinInUse(4, _))));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn with_retry_attempts_is_scoped() {
+        assert_eq!(retry_attempts(), RETRY_ATTEMPTS);
+        assert_eq!(with_retry_attempts(5, retry_attempts), 5);
+        assert_eq!(retry_attempts(), RETRY_ATTEMPTS);
+    }
+}
@@ -0,0 +1,112 @@
+//! Error type returned by fallible device operations
+use rppal::{gpio, pwm};
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+/// The error type of the crate, for use as `rust_gpiozero::Error`
+pub type Error = GpioZeroError;
+
+/// Errors that can occur when communicating with a device
+#[derive(Debug)]
+pub enum GpioZeroError {
+    /// The device did not respond within the expected time
+    Timeout,
+    /// The data read from the device did not match its checksum
+    ChecksumMismatch,
+    /// A value given to the device was out of range
+    InvalidArgument(String),
+    /// The pin with this BCM number doesn't exist or is already in use
+    PinUnavailable(u8),
+    /// The pin with this BCM number is already reserved by another device in this process,
+    /// whose type is given, e.g. `"LED"`
+    PinInUse(u8, &'static str),
+    /// The program doesn't have permission to access the GPIO pins, e.g. because the user
+    /// isn't in the `gpio` group
+    PermissionDenied(String),
+    /// A background thread used to drive the pins panicked
+    ThreadPanicked,
+    /// The operation isn't supported on this platform
+    Unsupported(String),
+    /// The GPIO pins could not be accessed for any other reason, e.g. an I/O error or an
+    /// unknown Raspberry Pi model
+    Gpio(gpio::Error),
+    /// The hardware PWM channel could not be accessed
+    Pwm(pwm::Error),
+    /// A GPIO character device could not be opened or controlled
+    Io(io::Error),
+}
+
+impl GpioZeroError {
+    /// Returns the underlying rppal error, if the error came from accessing the GPIO pins
+    /// and isn't one of the more specific variants.
+    pub fn as_rppal(&self) -> Option<&gpio::Error> {
+        match self {
+            GpioZeroError::Gpio(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GpioZeroError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpioZeroError::Timeout => write!(f, "timed out waiting for the device to respond"),
+            GpioZeroError::ChecksumMismatch => {
+                write!(
+                    f,
+                    "the data read from the device did not match its checksum"
+                )
+            }
+            GpioZeroError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            GpioZeroError::PinUnavailable(pin) => {
+                write!(f, "GPIO {} is not available or is already in use", pin)
+            }
+            GpioZeroError::PinInUse(pin, owner) => {
+                write!(f, "GPIO {} is already in use by {}", pin, owner)
+            }
+            GpioZeroError::PermissionDenied(path) => {
+                write!(f, "permission denied accessing {}", path)
+            }
+            GpioZeroError::ThreadPanicked => write!(f, "a background thread panicked"),
+            GpioZeroError::Unsupported(message) => write!(f, "not supported: {}", message),
+            GpioZeroError::Gpio(e) => write!(f, "GPIO error: {}", e),
+            GpioZeroError::Pwm(e) => write!(f, "PWM error: {}", e),
+            GpioZeroError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl StdError for GpioZeroError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            GpioZeroError::Gpio(e) => Some(e),
+            GpioZeroError::Pwm(e) => Some(e),
+            GpioZeroError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GpioZeroError {
+    fn from(e: io::Error) -> GpioZeroError {
+        GpioZeroError::Io(e)
+    }
+}
+
+impl From<pwm::Error> for GpioZeroError {
+    fn from(e: pwm::Error) -> GpioZeroError {
+        GpioZeroError::Pwm(e)
+    }
+}
+
+impl From<gpio::Error> for GpioZeroError {
+    fn from(e: gpio::Error) -> GpioZeroError {
+        match e {
+            gpio::Error::PinNotAvailable(pin) => GpioZeroError::PinUnavailable(pin),
+            gpio::Error::PermissionDenied(path) => GpioZeroError::PermissionDenied(path),
+            gpio::Error::ThreadPanic => GpioZeroError::ThreadPanicked,
+            e => GpioZeroError::Gpio(e),
+        }
+    }
+}
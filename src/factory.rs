@@ -0,0 +1,205 @@
+//! Building all of a program's devices in one step, from a table of pins and names
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut devices = DeviceFactory::build_all(&[
+//!     (PinKind::LED, 17, "status"),
+//!     (PinKind::Button, 2, "start"),
+//! ])
+//! .expect("could not set up the hardware");
+//!
+//! if let Some(AnyDevice::LED(led)) = devices.get_mut("status") {
+//!     led.on();
+//! }
+//! ```
+use crate::devices::{bcm_to_board, with_retry_attempts, Device, RETRY_ATTEMPTS};
+use crate::error::GpioZeroError;
+use crate::gpiod::GpiodPinFactory;
+use crate::input_devices::{Button, DigitalInputDevice};
+use crate::output_devices::{Buzzer, DigitalOutputDevice, PWMOutputDevice, LED, PWMLED};
+use crate::pins::with_pin_factory;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The kinds of device `DeviceFactory` can build, each with the defaults of its `new` constructor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinKind {
+    /// A `DigitalOutputDevice`
+    DigitalOutputDevice,
+    /// An `LED`
+    LED,
+    /// A `Buzzer`
+    Buzzer,
+    /// A `PWMOutputDevice`
+    PWMOutputDevice,
+    /// A `PWMLED`
+    PWMLED,
+    /// A `DigitalInputDevice`, with the pin pulled low
+    DigitalInputDevice,
+    /// A `Button`, with the pin pulled high
+    Button,
+}
+
+/// A device built by `DeviceFactory`, to be matched on for the device itself
+pub enum AnyDevice {
+    /// A `DigitalOutputDevice`
+    DigitalOutputDevice(DigitalOutputDevice),
+    /// An `LED`
+    LED(LED),
+    /// A `Buzzer`
+    Buzzer(Buzzer),
+    /// A `PWMOutputDevice`
+    PWMOutputDevice(PWMOutputDevice),
+    /// A `PWMLED`
+    PWMLED(PWMLED),
+    /// A `DigitalInputDevice`
+    DigitalInputDevice(DigitalInputDevice),
+    /// A `Button`
+    Button(Button),
+}
+
+impl AnyDevice {
+    // Build a device of `kind` on `pin`
+    fn build(kind: PinKind, pin: u8) -> Result<AnyDevice, GpioZeroError> {
+        Ok(match kind {
+            PinKind::DigitalOutputDevice => {
+                AnyDevice::DigitalOutputDevice(DigitalOutputDevice::try_new(pin)?)
+            }
+            PinKind::LED => AnyDevice::LED(LED::try_new(pin)?),
+            PinKind::Buzzer => AnyDevice::Buzzer(Buzzer::try_new(pin)?),
+            PinKind::PWMOutputDevice => AnyDevice::PWMOutputDevice(PWMOutputDevice::try_new(pin)?),
+            PinKind::PWMLED => AnyDevice::PWMLED(PWMLED::try_new(pin)?),
+            PinKind::DigitalInputDevice => {
+                AnyDevice::DigitalInputDevice(DigitalInputDevice::try_new(pin, false)?)
+            }
+            PinKind::Button => AnyDevice::Button(Button::try_new(pin, true, None)?),
+        })
+    }
+}
+
+impl Device for AnyDevice {
+    fn close(self) {
+        drop(self)
+    }
+
+    fn is_active(&self) -> bool {
+        match self {
+            AnyDevice::DigitalOutputDevice(device) => device.is_active(),
+            AnyDevice::LED(device) => device.is_active(),
+            AnyDevice::Buzzer(device) => device.is_active(),
+            AnyDevice::PWMOutputDevice(device) => device.is_active(),
+            AnyDevice::PWMLED(device) => device.is_active(),
+            AnyDevice::DigitalInputDevice(device) => device.is_active(),
+            AnyDevice::Button(device) => device.is_active(),
+        }
+    }
+}
+
+/// Builds a whole set of devices at once, for programs which declare all their hardware up front
+#[derive(Debug, Clone)]
+pub struct DeviceFactory {
+    retry_attempts: u32,
+    chip: Option<PathBuf>,
+}
+
+impl Default for DeviceFactory {
+    fn default() -> DeviceFactory {
+        DeviceFactory::new()
+    }
+}
+
+impl DeviceFactory {
+    /// Returns a factory which builds devices on the current pin factory, making 3 attempts at
+    /// acquiring each pin
+    pub fn new() -> DeviceFactory {
+        DeviceFactory {
+            retry_attempts: RETRY_ATTEMPTS,
+            chip: None,
+        }
+    }
+
+    /// Returns the number of attempts made at acquiring each pin
+    pub fn retry_attempts(&self) -> u32 {
+        self.retry_attempts
+    }
+
+    /// Set the number of attempts made at acquiring each pin before giving up, e.g. more on a
+    /// heavily loaded system or 1 to fail straight away. Only transient I/O errors are retried;
+    /// a pin that is in use or can't be accessed fails on the first attempt.
+    pub fn set_retry_attempts(&mut self, attempts: u32) {
+        self.retry_attempts = attempts.max(1);
+    }
+
+    /// Returns the path of the GPIO chip the devices are built on, if one has been set
+    pub fn chip(&self) -> Option<&Path> {
+        self.chip.as_deref()
+    }
+
+    /// Build the devices on the lines of the GPIO character device at `path` through a
+    /// `GpiodPinFactory`, e.g. `/dev/gpiochip1` for an expander, rather than the current pin
+    /// factory. The pin numbers in the table are then line numbers on the chip.
+    pub fn set_chip<P: AsRef<Path>>(&mut self, path: P) {
+        self.chip = Some(path.as_ref().to_path_buf());
+    }
+
+    /// Build a device for each `(kind, pin, name)` given with the default settings,
+    /// returning them keyed by name. See `build`.
+    /// * `devices` - The kind, BCM pin number and name of each device
+    pub fn build_all(
+        devices: &[(PinKind, u8, &str)],
+    ) -> Result<HashMap<String, AnyDevice>, GpioZeroError> {
+        DeviceFactory::new().build(devices)
+    }
+
+    /// Build a device for each `(kind, pin, name)` given, returning them keyed by name.
+    /// The table is checked before any pin is touched, and returns `GpioZeroError::InvalidArgument`
+    /// if a pin or name is used twice, or a pin isn't a GPIO pin on the header when no chip is set.
+    /// If building a device fails, the devices built so far are dropped, releasing their pins,
+    /// and the error is returned, as it is if the chip set with `set_chip` can't be opened.
+    /// * `devices` - The kind, BCM pin number and name of each device
+    pub fn build(
+        &self,
+        devices: &[(PinKind, u8, &str)],
+    ) -> Result<HashMap<String, AnyDevice>, GpioZeroError> {
+        let mut pins = HashSet::new();
+        let mut names = HashSet::new();
+        for (_, pin, name) in devices {
+            if self.chip.is_none() && bcm_to_board(*pin).is_none() {
+                return Err(GpioZeroError::InvalidArgument(format!(
+                    "{} is not a GPIO pin, for device {:?}",
+                    pin, name
+                )));
+            }
+            if !pins.insert(*pin) {
+                return Err(GpioZeroError::InvalidArgument(format!(
+                    "GPIO {} is used by more than one device",
+                    pin
+                )));
+            }
+            if !names.insert(*name) {
+                return Err(GpioZeroError::InvalidArgument(format!(
+                    "{:?} is the name of more than one device",
+                    name
+                )));
+            }
+        }
+
+        let build = || {
+            with_retry_attempts(self.retry_attempts, || {
+                let mut built = HashMap::new();
+                for (kind, pin, name) in devices {
+                    built.insert(name.to_string(), AnyDevice::build(*kind, *pin)?);
+                }
+                Ok(built)
+            })
+        };
+        match &self.chip {
+            Some(path) => with_pin_factory(Arc::new(GpiodPinFactory::open(path)?), build),
+            None => build(),
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! A pin backend using the Linux GPIO character device (`/dev/gpiochipN`)
+//!
+//! rppal drives the pins through the memory-mapped registers in `/dev/gpiomem`, which isn't
+//! available in many containers and sandboxes. The character device ABI only needs access to
+//! the chip's device node, and lines are released by the kernel when the process exits.
+//!
+//! On a Raspberry Pi the lines of `/dev/gpiochip0` are numbered the same as the BCM GPIO pins.
+//! The pins of other chips, e.g. an expander or a Compute Module's second bank, are used by
+//! opening the chip with `GpiodPinFactory::open`, or with `OutputDevice::new_on_chip` or
+//! `DeviceFactory::set_chip`.
+//! Lines have no software PWM or interrupts through this backend, so PWM devices return
+//! `GpioZeroError::Unsupported` and input devices poll the level.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::gpiod::GpiodPinFactory;
+//! use rust_gpiozero::pins::set_pin_factory;
+//! use rust_gpiozero::*;
+//!
+//! set_pin_factory(GpiodPinFactory::new().expect("could not open /dev/gpiochip0"));
+//! let mut led = LED::new(17);
+//! led.blink(1.0, 1.0);
+//! led.wait();
+//! ```
+use crate::error::GpioZeroError;
+use crate::pins::{InputPin, IoPin, PinFactory, LOCAL_PINS};
+use rppal::gpio::{Level, Mode, PullUpDown};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+// ioctl requests of the GPIO character device ABI (v1), from linux/gpio.h
+const GPIO_GET_CHIPINFO_IOCTL: c_ulong = 0x8044_B401;
+const GPIO_GET_LINEINFO_IOCTL: c_ulong = 0xC048_B402;
+const GPIO_GET_LINEHANDLE_IOCTL: c_ulong = 0xC16C_B403;
+const GPIOHANDLE_GET_LINE_VALUES_IOCTL: c_ulong = 0xC040_B408;
+const GPIOHANDLE_SET_LINE_VALUES_IOCTL: c_ulong = 0xC040_B409;
+const GPIOHANDLE_SET_CONFIG_IOCTL: c_ulong = 0xC054_B40A;
+
+const GPIOLINE_FLAG_KERNEL: u32 = 1;
+const GPIOHANDLE_REQUEST_INPUT: u32 = 1 << 0;
+const GPIOHANDLE_REQUEST_OUTPUT: u32 = 1 << 1;
+const GPIOHANDLE_REQUEST_BIAS_PULL_UP: u32 = 1 << 5;
+const GPIOHANDLE_REQUEST_BIAS_PULL_DOWN: u32 = 1 << 6;
+
+const GPIOHANDLES_MAX: usize = 64;
+// the errno returned when a line is already requested by another consumer
+const EBUSY: i32 = 16;
+const CONSUMER: &[u8] = b"rust_gpiozero";
+
+#[repr(C)]
+struct ChipInfo {
+    name: [u8; 32],
+    label: [u8; 32],
+    lines: u32,
+}
+
+#[repr(C)]
+struct LineInfo {
+    line_offset: u32,
+    flags: u32,
+    name: [u8; 32],
+    consumer: [u8; 32],
+}
+
+#[repr(C)]
+struct HandleRequest {
+    line_offsets: [u32; GPIOHANDLES_MAX],
+    flags: u32,
+    default_values: [u8; GPIOHANDLES_MAX],
+    consumer_label: [u8; 32],
+    lines: u32,
+    fd: c_int,
+}
+
+#[repr(C)]
+struct HandleConfig {
+    flags: u32,
+    default_values: [u8; GPIOHANDLES_MAX],
+    padding: [u32; 4],
+}
+
+#[repr(C)]
+struct HandleData {
+    values: [u8; GPIOHANDLES_MAX],
+}
+
+// Returns an error for the ioctl that has just failed
+fn check(result: c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+// The request flags for a line in `mode` with the pull given.
+// Modes other than `Mode::Output` are treated as inputs, since the line can't be muxed.
+fn flags(mode: Mode, pull: PullUpDown) -> u32 {
+    if mode == Mode::Output {
+        return GPIOHANDLE_REQUEST_OUTPUT;
+    }
+    GPIOHANDLE_REQUEST_INPUT
+        | match pull {
+            PullUpDown::PullUp => GPIOHANDLE_REQUEST_BIAS_PULL_UP,
+            PullUpDown::PullDown => GPIOHANDLE_REQUEST_BIAS_PULL_DOWN,
+            PullUpDown::Off => 0,
+        }
+}
+
+// The text of a NUL-terminated string from the kernel
+fn kernel_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// What a GPIO character device reports about itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioChipInfo {
+    /// The kernel's name for the chip, e.g. `"gpiochip0"`
+    pub name: String,
+    /// The label of the chip's driver, e.g. `"pinctrl-bcm2711"`
+    pub label: String,
+    /// The number of lines on the chip
+    pub lines: u32,
+}
+
+/// A `PinFactory` that requests lines from a GPIO character device
+#[derive(Debug, Clone)]
+pub struct GpiodPinFactory {
+    path: PathBuf,
+    chip: Arc<File>,
+}
+
+impl GpiodPinFactory {
+    /// Returns a factory for `/dev/gpiochip0`, which holds the header pins on a Raspberry Pi
+    pub fn new() -> Result<GpiodPinFactory, GpioZeroError> {
+        GpiodPinFactory::open("/dev/gpiochip0")
+    }
+
+    /// Returns a factory for the GPIO character device at `path`, or
+    /// `GpioZeroError::PermissionDenied` if the program isn't allowed to open it
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<GpiodPinFactory, GpioZeroError> {
+        let path = path.as_ref().to_path_buf();
+        match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(chip) => Ok(GpiodPinFactory {
+                path,
+                chip: Arc::new(chip),
+            }),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                Err(GpioZeroError::PermissionDenied(path.display().to_string()))
+            }
+            Err(e) => Err(GpioZeroError::Io(e)),
+        }
+    }
+
+    /// Returns the path of the character device the factory requests lines from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the name, label and number of lines of the chip
+    pub fn info(&self) -> Result<GpioChipInfo, GpioZeroError> {
+        let mut info = ChipInfo {
+            name: [0; 32],
+            label: [0; 32],
+            lines: 0,
+        };
+        // SAFETY: the info is a valid gpiochip_info that outlives the call
+        check(unsafe {
+            ioctl(
+                self.chip.as_raw_fd(),
+                GPIO_GET_CHIPINFO_IOCTL,
+                &mut info as *mut ChipInfo,
+            )
+        })?;
+        Ok(GpioChipInfo {
+            name: kernel_string(&info.name),
+            label: kernel_string(&info.label),
+            lines: info.lines,
+        })
+    }
+
+    // Request the line at `offset` on the chip for the device pin numbered `pin`
+    pub(crate) fn request(
+        &self,
+        pin: u8,
+        offset: u32,
+        mode: Mode,
+        pull: PullUpDown,
+    ) -> Result<GpiodLine, GpioZeroError> {
+        let mut request = HandleRequest {
+            line_offsets: [0; GPIOHANDLES_MAX],
+            flags: flags(mode, pull),
+            default_values: [0; GPIOHANDLES_MAX],
+            consumer_label: [0; 32],
+            lines: 1,
+            fd: -1,
+        };
+        request.line_offsets[0] = offset;
+        request.consumer_label[..CONSUMER.len()].copy_from_slice(CONSUMER);
+        // SAFETY: the request is a valid gpiohandle_request that outlives the call
+        let result = unsafe {
+            ioctl(
+                self.chip.as_raw_fd(),
+                GPIO_GET_LINEHANDLE_IOCTL,
+                &mut request as *mut HandleRequest,
+            )
+        };
+        match check(result) {
+            Ok(()) => Ok(GpiodLine {
+                pin,
+                // SAFETY: the kernel has just handed us ownership of this descriptor
+                handle: unsafe { File::from_raw_fd(request.fd) },
+                mode,
+                pull,
+                output: Level::Low,
+            }),
+            Err(e) if e.raw_os_error() == Some(EBUSY) => Err(GpioZeroError::PinUnavailable(pin)),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                Err(GpioZeroError::PinUnavailable(pin))
+            }
+            Err(e) => Err(GpioZeroError::Io(e)),
+        }
+    }
+
+    // Whether the line at `offset` exists and isn't held by the kernel or another program
+    pub(crate) fn line_available(&self, offset: u32) -> bool {
+        let mut info = LineInfo {
+            line_offset: offset,
+            flags: 0,
+            name: [0; 32],
+            consumer: [0; 32],
+        };
+        // SAFETY: the info is a valid gpioline_info that outlives the call
+        let result = unsafe {
+            ioctl(
+                self.chip.as_raw_fd(),
+                GPIO_GET_LINEINFO_IOCTL,
+                &mut info as *mut LineInfo,
+            )
+        };
+        check(result).is_ok() && info.flags & GPIOLINE_FLAG_KERNEL == 0
+    }
+}
+
+impl PinFactory for GpiodPinFactory {
+    fn input_pin(&self, pin: u8, pull: PullUpDown) -> Result<Box<dyn InputPin>, GpioZeroError> {
+        Ok(Box::new(self.request(
+            pin,
+            u32::from(pin),
+            Mode::Input,
+            pull,
+        )?))
+    }
+
+    fn io_pin(&self, pin: u8, mode: Mode) -> Result<Box<dyn IoPin>, GpioZeroError> {
+        Ok(Box::new(self.request(
+            pin,
+            u32::from(pin),
+            mode,
+            PullUpDown::Off,
+        )?))
+    }
+
+    fn pin_available(&self, pin: u8) -> bool {
+        self.line_available(u32::from(pin))
+    }
+
+    // the header pins are the lines of the first chip, whichever backend drives them
+    fn id(&self) -> String {
+        if self.path == Path::new("/dev/gpiochip0") {
+            LOCAL_PINS.to_string()
+        } else {
+            self.path.display().to_string()
+        }
+    }
+}
+
+// A line requested from the chip, which the kernel releases when the handle is closed
+#[derive(Debug)]
+pub(crate) struct GpiodLine {
+    pin: u8,
+    handle: File,
+    mode: Mode,
+    pull: PullUpDown,
+    // the level last written, which the line is driven to when it becomes an output
+    output: Level,
+}
+
+impl GpiodLine {
+    fn get_value(&self) -> io::Result<Level> {
+        let mut data = HandleData {
+            values: [0; GPIOHANDLES_MAX],
+        };
+        // SAFETY: the data is a valid gpiohandle_data that outlives the call
+        check(unsafe {
+            ioctl(
+                self.handle.as_raw_fd(),
+                GPIOHANDLE_GET_LINE_VALUES_IOCTL,
+                &mut data as *mut HandleData,
+            )
+        })?;
+        Ok(if data.values[0] == 0 {
+            Level::Low
+        } else {
+            Level::High
+        })
+    }
+
+    fn set_value(&self, level: Level) -> io::Result<()> {
+        let mut data = HandleData {
+            values: [0; GPIOHANDLES_MAX],
+        };
+        data.values[0] = (level == Level::High) as u8;
+        // SAFETY: the data is a valid gpiohandle_data that outlives the call
+        check(unsafe {
+            ioctl(
+                self.handle.as_raw_fd(),
+                GPIOHANDLE_SET_LINE_VALUES_IOCTL,
+                &mut data as *mut HandleData,
+            )
+        })
+    }
+
+    fn configure(&mut self, mode: Mode, initial: Level) -> io::Result<()> {
+        let mut config = HandleConfig {
+            flags: flags(mode, self.pull),
+            default_values: [0; GPIOHANDLES_MAX],
+            padding: [0; 4],
+        };
+        config.default_values[0] = (initial == Level::High) as u8;
+        // SAFETY: the config is a valid gpiohandle_config that outlives the call
+        check(unsafe {
+            ioctl(
+                self.handle.as_raw_fd(),
+                GPIOHANDLE_SET_CONFIG_IOCTL,
+                &mut config as *mut HandleConfig,
+            )
+        })?;
+        self.mode = mode;
+        Ok(())
+    }
+}
+
+impl InputPin for GpiodLine {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn read(&self) -> Level {
+        // a line that can't be read is reported low, as a floating input would most likely be
+        self.get_value().unwrap_or(Level::Low)
+    }
+}
+
+impl IoPin for GpiodLine {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        let mode = if mode == Mode::Output {
+            Mode::Output
+        } else {
+            Mode::Input
+        };
+        if mode != self.mode {
+            if let Err(e) = self.configure(mode, self.output) {
+                println!("Could not set the mode of GPIO {}: {}", self.pin, e);
+            }
+        }
+    }
+
+    fn read(&self) -> Level {
+        InputPin::read(self)
+    }
+
+    fn write(&mut self, level: Level) {
+        self.output = level;
+        if self.mode != Mode::Output {
+            return;
+        }
+        if let Err(e) = self.set_value(level) {
+            println!("Could not write to GPIO {}: {}", self.pin, e);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! `embedded-hal` traits for devices, enabled with the `hal` feature.
+//!
+//! Output devices implement `OutputPin` and `StatefulOutputPin`, input devices `InputPin` and
+//! `PWMOutputDevice` implements `SetDutyCycle`, so they can be handed to driver crates written
+//! against `embedded-hal` 1.0. High means active, as everywhere else in this library: a device
+//! with `active_high` set to ``False`` is driven low by `set_high`.
+//!
+//! Conversely, `from_hal_pin` builds an `LED`, `Buzzer`, `DigitalOutputDevice` or `OutputDevice`
+//! on any `embedded-hal` `OutputPin`, e.g. a pin of an I2C port expander, giving it the blinking,
+//! `active_high` and `toggle` behaviors of this library.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use embedded_hal::digital::OutputPin;
+//! use rust_gpiozero::*;
+//!
+//! // any driver taking an `OutputPin` can be given a device
+//! fn chip_select<P: OutputPin>(pin: &mut P) -> Result<(), P::Error> {
+//!     pin.set_low()
+//! }
+//!
+//! let mut cs = DigitalOutputDevice::new(8);
+//! chip_select(&mut cs).unwrap();
+//! ```
+//!
+//! ```
+//! use embedded_hal::digital::{ErrorType, OutputPin};
+//! use rust_gpiozero::*;
+//! use std::convert::Infallible;
+//!
+//! // stands in for a pin from any crate implementing `OutputPin`, e.g. a port expander driver
+//! struct ExpanderPin;
+//!
+//! impl ErrorType for ExpanderPin {
+//!     type Error = Infallible;
+//! }
+//!
+//! impl OutputPin for ExpanderPin {
+//!     fn set_low(&mut self) -> Result<(), Infallible> {
+//!         Ok(())
+//!     }
+//!
+//!     fn set_high(&mut self) -> Result<(), Infallible> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let led = LED::from_hal_pin(ExpanderPin);
+//! led.on();
+//! assert!(led.is_lit());
+//! ```
+use crate::error::GpioZeroError;
+use crate::input_devices::{Button, DigitalInputDevice, InputDevice};
+use crate::output_devices::{Buzzer, DigitalOutputDevice, OutputDevice, PWMOutputDevice, LED};
+use crate::pins::IoPin;
+use embedded_hal::digital::{self, InputPin, OutputPin, StatefulOutputPin};
+use embedded_hal::pwm::{self, SetDutyCycle};
+use rppal::gpio::{Level, Mode};
+use std::convert::Infallible;
+use std::fmt;
+
+/// The pin number reported by devices built with `from_hal_pin`, whose pins aren't GPIO pins of
+/// the Pi. Such pins aren't reserved, since the device owns the `embedded-hal` pin, and the
+/// `registry` only keeps the tags of one of them.
+pub const HAL_PIN: u8 = u8::MAX;
+
+impl pwm::Error for GpioZeroError {
+    fn kind(&self) -> pwm::ErrorKind {
+        pwm::ErrorKind::Other
+    }
+}
+
+// Writes to a digital device never fail: errors from the pin are printed, as for `on` and `off`
+macro_rules! impl_hal_output_pin {
+    ($device:ty) => {
+        impl digital::ErrorType for $device {
+            type Error = Infallible;
+        }
+
+        impl OutputPin for $device {
+            fn set_low(&mut self) -> Result<(), Infallible> {
+                self.off();
+                Ok(())
+            }
+
+            fn set_high(&mut self) -> Result<(), Infallible> {
+                self.on();
+                Ok(())
+            }
+        }
+
+        impl StatefulOutputPin for $device {
+            fn is_set_high(&mut self) -> Result<bool, Infallible> {
+                Ok(self.value())
+            }
+
+            fn is_set_low(&mut self) -> Result<bool, Infallible> {
+                Ok(!self.value())
+            }
+
+            fn toggle(&mut self) -> Result<(), Infallible> {
+                <$device>::toggle(self);
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_hal_output_pin!(OutputDevice);
+impl_hal_output_pin!(DigitalOutputDevice);
+
+macro_rules! impl_hal_input_pin {
+    ($device:ty) => {
+        impl digital::ErrorType for $device {
+            type Error = Infallible;
+        }
+
+        impl InputPin for $device {
+            fn is_high(&mut self) -> Result<bool, Infallible> {
+                Ok(self.value())
+            }
+
+            fn is_low(&mut self) -> Result<bool, Infallible> {
+                Ok(!self.value())
+            }
+        }
+    };
+}
+
+impl_hal_input_pin!(InputDevice);
+impl_hal_input_pin!(DigitalInputDevice);
+impl_hal_input_pin!(Button);
+
+impl pwm::ErrorType for PWMOutputDevice {
+    type Error = GpioZeroError;
+}
+
+/// The duty cycle is the `value` of the device scaled to `u16::MAX`, so the device's gamma
+/// correction and `active_high` setting still apply.
+impl SetDutyCycle for PWMOutputDevice {
+    fn max_duty_cycle(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), GpioZeroError> {
+        self.try_set_value(f64::from(duty) / f64::from(u16::MAX))
+    }
+}
+
+// An `embedded-hal` output pin used as the pin of a device
+struct HalOutputPin<P> {
+    pin: P,
+    // the level last written, since an `OutputPin` can't be read
+    level: Level,
+}
+
+impl<P> fmt::Debug for HalOutputPin<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HalOutputPin")
+            .field("level", &self.level)
+            .finish()
+    }
+}
+
+impl<P: OutputPin + Send> IoPin for HalOutputPin<P> {
+    fn pin(&self) -> u8 {
+        HAL_PIN
+    }
+
+    fn mode(&self) -> Mode {
+        Mode::Output
+    }
+
+    // the pin is always an output, so open-drain drives can't release it
+    fn set_mode(&mut self, _mode: Mode) {}
+
+    fn read(&self) -> Level {
+        self.level
+    }
+
+    fn write(&mut self, level: Level) {
+        let result = match level {
+            Level::Low => self.pin.set_low(),
+            Level::High => self.pin.set_high(),
+        };
+        match result {
+            Ok(()) => self.level = level,
+            Err(e) => println!("Could not write to the embedded-hal pin: {:?}", e),
+        }
+    }
+}
+
+// Returns an OutputDevice on `pin`, which is driven low to match the device being off
+fn hal_output_device<P: OutputPin + Send + 'static>(pin: P) -> OutputDevice {
+    let mut pin = HalOutputPin {
+        pin,
+        level: Level::High,
+    };
+    pin.write(Level::Low);
+    OutputDevice::with_pin(Box::new(pin), None)
+}
+
+impl OutputDevice {
+    /// Returns an OutputDevice driving an `embedded-hal` output pin. `pin` reports `HAL_PIN`.
+    /// * `pin` - The pin which the device is attached to
+    pub fn from_hal_pin<P: OutputPin + Send + 'static>(pin: P) -> OutputDevice {
+        hal_output_device(pin)
+    }
+}
+
+impl DigitalOutputDevice {
+    /// Returns a DigitalOutputDevice driving an `embedded-hal` output pin. `pin` reports `HAL_PIN`.
+    /// * `pin` - The pin which the device is attached to
+    pub fn from_hal_pin<P: OutputPin + Send + 'static>(pin: P) -> DigitalOutputDevice {
+        DigitalOutputDevice::from_device(hal_output_device(pin))
+    }
+}
+
+impl LED {
+    /// Returns an LED driving an `embedded-hal` output pin. `pin` reports `HAL_PIN`.
+    /// * `pin` - The pin which the LED is attached to
+    pub fn from_hal_pin<P: OutputPin + Send + 'static>(pin: P) -> LED {
+        LED::from_device(hal_output_device(pin))
+    }
+}
+
+impl Buzzer {
+    /// Returns a Buzzer driving an `embedded-hal` output pin. `pin` reports `HAL_PIN`.
+    /// * `pin` - The pin which the buzzer is attached to
+    pub fn from_hal_pin<P: OutputPin + Send + 'static>(pin: P) -> Buzzer {
+        Buzzer::from_device(hal_output_device(pin))
+    }
+}
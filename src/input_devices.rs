@@ -1,13 +1,25 @@
 //! Input device component interfaces for devices such as `Button`
-use rppal::gpio::{Gpio, InputPin, Level, Trigger};
-use std::time::Duration;
+use crate::devices::{claim_input, claim_io, Device, PinNumbering, Reservation, ThreadGuard};
+use crate::error::GpioZeroError;
+use crate::output_devices::OutputDevice;
+use crate::pins::{InputPin, IoPin};
+use crate::registry::Tags;
+use crate::timing::{Clock, StoppableClock, TimingStrategy};
+use rppal::gpio::{Level, Mode, PullUpDown, Trigger};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// Represents a generic GPIO input device.
 #[derive(Debug)]
 pub struct InputDevice {
-    pin: InputPin,
+    pin: Box<dyn InputPin>,
     active_state: bool,
     inactive_state: bool,
+    _reservation: Reservation,
 }
 
 impl InputDevice {
@@ -19,17 +31,7 @@ impl InputDevice {
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
     pub fn new(pin: u8) -> InputDevice {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => InputDevice {
-                    pin: pin.into_input_pulldown(),
-                    active_state: true,
-                    inactive_state: false,
-                },
-            },
-        }
+        InputDevice::try_new(pin, false).unwrap_or_else(|e| panic!("{:?}", e))
     }
     /// Returns an InputDevice with the pin number given with the pin pulled high with an internal resistor by default
     /// `is_active` property is adjusted accordingly so that
@@ -39,52 +41,440 @@ impl InputDevice {
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
     pub fn new_with_pullup(pin: u8) -> InputDevice {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => InputDevice {
-                    pin: pin.into_input_pullup(),
-                    active_state: false,
-                    inactive_state: true,
-                },
-            },
-        }
+        InputDevice::try_new(pin, true).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    // Returns an InputDevice with the pin number and pull given, or an error if the pin
+    // can't be accessed. It is active when the pin is pulled away from its resting level.
+    pub(crate) fn try_new(pin: u8, pull_up: bool) -> Result<InputDevice, GpioZeroError> {
+        let pull = if pull_up {
+            PullUpDown::PullUp
+        } else {
+            PullUpDown::PullDown
+        };
+        let (reservation, pin) = claim_input(pin, "InputDevice", pull)?;
+        Ok(InputDevice {
+            pin,
+            active_state: !pull_up,
+            inactive_state: pull_up,
+            _reservation: reservation,
+        })
     }
 
     impl_device!();
     impl_gpio_device!();
     impl_io_device!();
+    impl_pin_numbering!(|pin| Self::try_new(pin, false));
 }
 
 macro_rules! impl_events_mixin {
     () => {
         /// Pause the program until the device is activated, or the timeout is reached.
         fn wait_for(&mut self, timeout: Option<f32>, active: bool) {
+            let timeout = timeout.map(|n| Duration::from_millis((n * 1000.0) as u64));
+            // a blocking interrupt would replace the one dispatching to the handlers, so they'd
+            // miss edges while waiting; wait for the edge with another handler instead
+            if !self
+                .handlers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_empty()
+            {
+                self.wait_for_event(timeout, active);
+                return;
+            }
+
+            // the pin rises when the device becomes active only if it is active high
+            let trigger = if active == self.active_state {
+                Trigger::RisingEdge
+            } else {
+                Trigger::FallingEdge
+            };
+            if self.poller.interrupt_driven {
+                match self.pin.set_interrupt(trigger) {
+                    Ok(()) => {
+                        self.pin.poll_interrupt(true, timeout).unwrap();
+                        return;
+                    }
+                    Err(_) => self.poller.interrupt_driven = false,
+                }
+            }
+
+            // without interrupts, poll the level until it changes to the state waited for
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let mut last = self.value();
+            while !deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                thread::sleep(self.poller.interval());
+                let value = self.value();
+                if value == active && last != active {
+                    return;
+                }
+                last = value;
+            }
+        }
+
+        // Wait for an event changing the device to `active` with a temporary handler, alongside
+        // the handlers already registered. Returns ``True`` if the event arrived in time.
+        fn wait_for_event(&mut self, timeout: Option<Duration>, active: bool) -> bool {
+            let seen = Arc::new((Mutex::new(false), Condvar::new()));
+            let flag = Arc::clone(&seen);
+            let token = self.add_handler(move |event| {
+                if event.active == active {
+                    let (lock, cvar) = &*flag;
+                    *lock.lock().unwrap() = true;
+                    cvar.notify_all();
+                }
+            });
+
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let (lock, cvar) = &*seen;
+            let mut arrived = lock.lock().unwrap();
+            while !*arrived {
+                match deadline {
+                    None => arrived = cvar.wait(arrived).unwrap(),
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            break;
+                        }
+                        arrived = cvar.wait_timeout(arrived, deadline - now).unwrap().0;
+                    }
+                }
+            }
+            let reached = *arrived;
+            drop(arrived);
+            self.remove_handler(token);
+            reached
+        }
+    };
+}
+
+/// An edge event recorded by an input device, as returned by `poll_events`.
+#[derive(Debug, Clone, Copy)]
+pub struct InputEvent {
+    /// The time at which the edge was detected
+    pub timestamp: Instant,
+    /// ``True`` if the device became active and ``False`` if it became inactive
+    pub active: bool,
+}
+
+/// Identifies a handler registered with `add_handler`, so that it can be removed later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerToken(u64);
+
+type Handler = Box<dyn Fn(InputEvent) + Send>;
+
+// The handlers registered on an input device, all called from a single interrupt
+#[derive(Default)]
+struct Handlers {
+    next_token: u64,
+    handlers: Vec<(HandlerToken, Handler)>,
+}
+
+impl Handlers {
+    fn add(&mut self, handler: Handler) -> HandlerToken {
+        let token = HandlerToken(self.next_token);
+        self.next_token += 1;
+        self.handlers.push((token, handler));
+        token
+    }
+
+    fn remove(&mut self, token: HandlerToken) -> bool {
+        let len = self.handlers.len();
+        self.handlers.retain(|(t, _)| *t != token);
+        self.handlers.len() != len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    fn dispatch(&self, event: InputEvent) {
+        for (_, handler) in &self.handlers {
+            handler(event);
+        }
+    }
+}
+
+// A handler registered on an input device by another device, e.g. an LED following a button,
+// which can be removed without a reference to the input device. It doesn't keep the input
+// device's handlers alive, and removing it once the input device is gone does nothing.
+#[derive(Debug)]
+pub(crate) struct Subscription {
+    handlers: Weak<Mutex<Handlers>>,
+    token: HandlerToken,
+}
+
+impl Subscription {
+    // Remove the handler. The input device keeps listening for edges until its own handlers are
+    // changed or it is dropped, but has nothing to call for them.
+    pub(crate) fn cancel(self) {
+        if let Some(handlers) = self.handlers.upgrade() {
+            handlers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(self.token);
+        }
+    }
+}
+
+impl fmt::Debug for Handlers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handlers({})", self.handlers.len())
+    }
+}
+
+/// How often the level is read by default when interrupts aren't available
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// An input pin that can be read from a polling thread as well as the device
+#[derive(Debug)]
+struct SharedPin(Arc<Mutex<Box<dyn InputPin>>>);
+
+impl SharedPin {
+    fn new(pin: Box<dyn InputPin>) -> SharedPin {
+        SharedPin(Arc::new(Mutex::new(pin)))
+    }
+
+    fn shared(&self) -> Arc<Mutex<Box<dyn InputPin>>> {
+        Arc::clone(&self.0)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Box<dyn InputPin>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn pin(&self) -> u8 {
+        self.lock().pin()
+    }
+
+    fn read(&self) -> Level {
+        self.lock().read()
+    }
+
+    fn set_interrupt(&mut self, trigger: Trigger) -> Result<(), GpioZeroError> {
+        self.lock().set_interrupt(trigger)
+    }
+
+    fn poll_interrupt(
+        &mut self,
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Level>, GpioZeroError> {
+        self.lock().poll_interrupt(reset, timeout)
+    }
+
+    fn set_async_interrupt<C>(&mut self, trigger: Trigger, callback: C) -> Result<(), GpioZeroError>
+    where
+        C: FnMut(Level) + Send + 'static,
+    {
+        self.lock().set_async_interrupt(trigger, Box::new(callback))
+    }
+
+    fn clear_async_interrupt(&mut self) -> Result<(), GpioZeroError> {
+        self.lock().clear_async_interrupt()
+    }
+}
+
+// Detects edges by reading the level in a background thread, for when interrupts aren't available
+#[derive(Debug)]
+struct Poller {
+    interrupt_driven: bool,
+    interval: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    handle: ThreadGuard,
+}
+
+impl Poller {
+    fn new() -> Poller {
+        let running = Arc::new(AtomicBool::new(false));
+        Poller {
+            interrupt_driven: true,
+            interval: Arc::new(AtomicU64::new(DEFAULT_POLL_INTERVAL.as_micros() as u64)),
+            handle: ThreadGuard::new(Arc::clone(&running)),
+            running,
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_micros(self.interval.load(Ordering::SeqCst))
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.interval
+            .store(interval.as_micros() as u64, Ordering::SeqCst);
+    }
+
+    // Start calling `on_change` with the new level whenever the level of `pin` changes,
+    // unless the thread is already running
+    fn start<F>(&mut self, pin: Arc<Mutex<Box<dyn InputPin>>>, mut on_change: F)
+    where
+        F: FnMut(Level) + Send + 'static,
+    {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let running = Arc::clone(&self.running);
+        let interval = Arc::clone(&self.interval);
+        let read = move || pin.lock().unwrap_or_else(PoisonError::into_inner).read();
+        // read the starting level before returning, so an edge straight afterwards isn't missed
+        let mut last = read();
+        self.handle.spawn(move || {
+            let mut clock = StoppableClock::new(TimingStrategy::Sleep, &running);
+            while running.load(Ordering::SeqCst) {
+                clock.sleep(Duration::from_micros(interval.load(Ordering::SeqCst)));
+                let level = read();
+                if level != last {
+                    last = level;
+                    on_change(level);
+                }
+            }
+        });
+    }
+
+    fn stop(&mut self) {
+        self.handle.stop();
+    }
+}
+
+macro_rules! impl_handlers {
+    () => {
+        /// Register a function to be called from a background thread on every edge event.
+        /// Any number of handlers can be registered on the same device; they are called in the
+        /// order they were added. Returns a token which can be given to `remove_handler`.
+        /// Handlers must not add or remove handlers themselves.
+        /// * `f` - The function to call with each event
+        pub fn add_handler<F>(&mut self, f: F) -> HandlerToken
+        where
+            F: Fn(InputEvent) + Send + 'static,
+        {
+            let (token, first) = {
+                let mut handlers = self.handlers.lock().unwrap();
+                let token = handlers.add(Box::new(f));
+                (token, handlers.handlers.len() == 1)
+            };
+            if first {
+                self.install_handlers();
+            }
+            token
+        }
+
+        // Register a handler for another device, which removes it with `Subscription::cancel`
+        #[allow(dead_code)]
+        pub(crate) fn subscribe<F>(&mut self, f: F) -> Subscription
+        where
+            F: Fn(InputEvent) + Send + 'static,
+        {
+            Subscription {
+                token: self.add_handler(f),
+                handlers: Arc::downgrade(&self.handlers),
+            }
+        }
+
+        /// Remove a handler registered with `add_handler`.
+        /// Returns ``True`` if the handler was registered and ``False`` otherwise.
+        pub fn remove_handler(&mut self, token: HandlerToken) -> bool {
+            let (removed, empty) = {
+                let mut handlers = self.handlers.lock().unwrap_or_else(PoisonError::into_inner);
+                (handlers.remove(token), handlers.is_empty())
+            };
+            // the interrupt thread or poller takes the lock to dispatch, so it is released before
+            // waiting for them to stop
+            if removed && empty {
+                if self.poller.interrupt_driven {
+                    if let Err(e) = self.pin.clear_async_interrupt() {
+                        println!("Could not clear the interrupt: {}", e);
+                    }
+                } else {
+                    self.poller.stop();
+                }
+            }
+            removed
+        }
+
+        // Dispatch edge events to the handlers from an interrupt, or from a thread polling the
+        // level if interrupts aren't available
+        fn install_handlers(&mut self) {
+            let handlers = Arc::clone(&self.handlers);
+            let active_state = self.active_state;
+            let dispatch = move |level| {
+                handlers.lock().unwrap().dispatch(InputEvent {
+                    timestamp: Instant::now(),
+                    active: (level == Level::High) == active_state,
+                });
+            };
+            if self.poller.interrupt_driven {
+                match self
+                    .pin
+                    .set_async_interrupt(Trigger::Both, dispatch.clone())
+                {
+                    Ok(()) => return,
+                    Err(_) => self.poller.interrupt_driven = false,
+                }
+            }
+            self.poller.start(self.pin.shared(), dispatch);
+        }
+
+        /// Returns ``True`` if edges are detected with interrupts and ``False`` if the device
+        /// has fallen back to polling the level because interrupts aren't available, e.g. due to
+        /// the kernel or permissions. Polled edges are seen up to `poll_interval` late, and
+        /// edges shorter than it can be missed.
+        pub fn is_interrupt_driven(&self) -> bool {
+            self.poller.interrupt_driven
+        }
+
+        /// Set how often the level is read when the device falls back to polling.
+        /// * `interval` - The time between readings, 10ms by default
+        pub fn set_poll_interval(&mut self, interval: Duration) {
+            self.poller.set_interval(interval);
+        }
+
+        /// Returns how often the level is read when the device falls back to polling.
+        pub fn poll_interval(&self) -> Duration {
+            self.poller.interval()
+        }
+    };
+}
+
+macro_rules! impl_poll_events {
+    () => {
+        /// Returns all the edge events recorded since the last call, oldest first.
+        /// The first call starts recording events in the background.
+        /// If no events are pending, wait until one arrives or the timeout is reached.
+        /// * `timeout` - How long to wait for an event. If this is None, then wait indefinitely until an event arrives.
+        pub fn poll_events(&mut self, timeout: Option<Duration>) -> Vec<InputEvent> {
+            if self.events.is_none() {
+                let events = Arc::new((Mutex::new(Vec::new()), Condvar::new()));
+                let queue = Arc::clone(&events);
+                self.add_handler(move |event| {
+                    let (lock, cvar) = &*queue;
+                    lock.lock().unwrap().push(event);
+                    cvar.notify_all();
+                });
+                self.events = Some(events);
+            }
+
+            let events = Arc::clone(self.events.as_ref().unwrap());
+            let (lock, cvar) = &*events;
+            let mut pending = lock.lock().unwrap();
             match timeout {
                 None => {
-                    if active {
-                        self.pin.set_interrupt(Trigger::RisingEdge).unwrap();
-                        self.pin.poll_interrupt(true, None).unwrap();
-                    } else {
-                        self.pin.set_interrupt(Trigger::FallingEdge).unwrap();
-                        self.pin.poll_interrupt(true, None).unwrap();
+                    while pending.is_empty() {
+                        pending = cvar.wait(pending).unwrap();
                     }
                 }
-                Some(n) => {
-                    if active {
-                        self.pin.set_interrupt(Trigger::RisingEdge).unwrap();
-                        self.pin
-                            .poll_interrupt(true, Some(Duration::from_millis((n * 1000.0) as u64)))
-                            .unwrap();
-                    } else {
-                        self.pin.set_interrupt(Trigger::FallingEdge).unwrap();
-                        self.pin
-                            .poll_interrupt(true, Some(Duration::from_millis((n * 1000.0) as u64)))
-                            .unwrap();
+                Some(t) => {
+                    let deadline = Instant::now() + t;
+                    while pending.is_empty() {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            break;
+                        }
+                        pending = cvar.wait_timeout(pending, deadline - now).unwrap().0;
                     }
                 }
             }
+            pending.drain(..).collect()
         }
     };
 }
@@ -95,10 +485,15 @@ macro_rules! impl_events_mixin {
 /// states with (reasonably) clean transitions between the two.
 #[derive(Debug)]
 pub struct DigitalInputDevice {
-    pin: InputPin,
+    pin: SharedPin,
     active_state: bool,
     inactive_state: bool,
     bounce_time: Option<f32>,
+    events: Option<Arc<(Mutex<Vec<InputEvent>>, Condvar)>>,
+    handlers: Arc<Mutex<Handlers>>,
+    poller: Poller,
+    tags: Tags,
+    _reservation: Reservation,
 }
 
 impl DigitalInputDevice {
@@ -112,18 +507,7 @@ impl DigitalInputDevice {
     /// These pins are not suitable for use where no pullup resistor is required
     /// Source: https://pinout.xyz/pinout/pin5_gpio3
     pub fn new(pin: u8) -> DigitalInputDevice {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => DigitalInputDevice {
-                    pin: pin.into_input_pulldown(),
-                    active_state: true,
-                    inactive_state: false,
-                    bounce_time: None,
-                },
-            },
-        }
+        DigitalInputDevice::try_new(pin, false).unwrap_or_else(|e| panic!("{:?}", e))
     }
     /// Returns a DigitalInputDevice with the pin number given with the pin pulled high with an internal resistor by default
     /// `is_active` property is adjusted accordingly so that
@@ -133,24 +517,39 @@ impl DigitalInputDevice {
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
     pub fn new_with_pullup(pin: u8) -> DigitalInputDevice {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => DigitalInputDevice {
-                    pin: pin.into_input_pullup(),
-                    active_state: false,
-                    inactive_state: true,
-                    bounce_time: None,
-                },
-            },
-        }
+        DigitalInputDevice::try_new(pin, true).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    // Returns a DigitalInputDevice with the pin number and pull given, or an error if the pin
+    // can't be accessed. It is active when the pin is pulled away from its resting level.
+    pub(crate) fn try_new(pin: u8, pull_up: bool) -> Result<DigitalInputDevice, GpioZeroError> {
+        let pull = if pull_up {
+            PullUpDown::PullUp
+        } else {
+            PullUpDown::PullDown
+        };
+        let (reservation, pin) = claim_input(pin, "DigitalInputDevice", pull)?;
+        Ok(DigitalInputDevice {
+            tags: Tags::new(pin.pin()),
+            pin: SharedPin::new(pin),
+            active_state: !pull_up,
+            inactive_state: pull_up,
+            bounce_time: None,
+            events: None,
+            handlers: Arc::new(Mutex::new(Handlers::default())),
+            poller: Poller::new(),
+            _reservation: reservation,
+        })
     }
 
     impl_device!();
     impl_gpio_device!();
     impl_io_device!();
+    impl_pin_numbering!(|pin| Self::try_new(pin, false));
     impl_events_mixin!();
+    impl_handlers!();
+    impl_poll_events!();
+    impl_tags!();
 
     /// Pause the program until the device is deactivated, or the timeout is reached.
     pub fn wait_for_inactive(&mut self, timeout: Option<f32>) {
@@ -168,62 +567,1092 @@ impl DigitalInputDevice {
 /// Alternatively, connect one side of the button to the 3V3 pin, and the other to any GPIO pin,
 /// and then create a Button instance with Button::new_with_pulldown
 pub struct Button {
-    pin: InputPin,
+    pin: SharedPin,
     active_state: bool,
     inactive_state: bool,
     // FIXME: Implement debouncing
     #[allow(dead_code)]
     bounce_time: Option<f32>,
+    events: Option<Arc<(Mutex<Vec<InputEvent>>, Condvar)>>,
+    handlers: Arc<Mutex<Handlers>>,
+    press_timer: Arc<Mutex<PressTimer>>,
+    poller: Poller,
+    tags: Tags,
+    _reservation: Reservation,
+}
+
+// Keeps track of when a button was pressed to work out how long presses last
+#[derive(Debug, Default)]
+struct PressTimer {
+    pressed_at: Option<Instant>,
+    last_press_duration: Option<Duration>,
+}
+
+impl PressTimer {
+    fn press(&mut self, at: Instant) {
+        self.pressed_at = Some(at);
+    }
+
+    fn release(&mut self, at: Instant) -> Option<Duration> {
+        let duration = self.pressed_at.take().map(|pressed_at| at - pressed_at);
+        if duration.is_some() {
+            self.last_press_duration = duration;
+        }
+        duration
+    }
 }
 
 impl Button {
     /// Returns a Button with the pin number given and the pin pulled high with an internal resistor by default
     /// * `pin` - The GPIO pin which the device is attached to
     pub fn new(pin: u8) -> Button {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => Button {
-                    pin: pin.into_input_pullup(),
-                    active_state: false,
-                    inactive_state: true,
-                    bounce_time: None,
-                },
-            },
-        }
+        Button::new_with_pull(pin, true, None)
     }
     /// Returns a Button with the pin number given and the pin pulled down with an internal resistor by default
     /// * `pin` - The GPIO pin which the device is attached to
     pub fn new_with_pulldown(pin: u8) -> Button {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => Button {
-                    pin: pin.into_input_pulldown(),
-                    active_state: true,
-                    inactive_state: false,
-                    bounce_time: None,
-                },
-            },
-        }
+        Button::new_with_pull(pin, false, None)
+    }
+
+    /// Returns a Button with the pin number, pull resistor and active state given.
+    ///
+    /// | `pull_up` | `active_state` | pressed when the pin is |
+    /// |-----------|----------------|-------------------------|
+    /// | `true`    | `None`         | low                     |
+    /// | `false`   | `None`         | high                    |
+    /// | any       | `Some(true)`   | high                    |
+    /// | any       | `Some(false)`  | low                     |
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    /// * `pull_up` - ``True`` to pull the pin high with an internal resistor, ``False`` to pull it low
+    /// * `active_state` - Whether the button is pressed when the pin is high, or `None` to
+    ///   take it from the pull, i.e. a button wired to the opposite rail from the pull
+    pub fn new_with_pull(pin: u8, pull_up: bool, active_state: Option<bool>) -> Button {
+        Button::try_new(pin, pull_up, active_state).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    // Returns a Button like `new_with_pull`, or an error if the pin can't be accessed
+    pub(crate) fn try_new(
+        pin: u8,
+        pull_up: bool,
+        active_state: Option<bool>,
+    ) -> Result<Button, GpioZeroError> {
+        let pull = if pull_up {
+            PullUpDown::PullUp
+        } else {
+            PullUpDown::PullDown
+        };
+        let (reservation, pin) = claim_input(pin, "Button", pull)?;
+        let active_state = active_state.unwrap_or(!pull_up);
+        let mut button = Button {
+            tags: Tags::new(pin.pin()),
+            pin: SharedPin::new(pin),
+            active_state,
+            inactive_state: !active_state,
+            bounce_time: None,
+            events: None,
+            handlers: Arc::new(Mutex::new(Handlers::default())),
+            poller: Poller::new(),
+            press_timer: Arc::new(Mutex::new(PressTimer::default())),
+            _reservation: reservation,
+        };
+        // time every press from the edges, whether or not the program is waiting for them
+        let press_timer = Arc::clone(&button.press_timer);
+        button.add_handler(move |event| {
+            let mut press_timer = press_timer.lock().unwrap_or_else(PoisonError::into_inner);
+            if event.active {
+                press_timer.press(event.timestamp);
+            } else {
+                press_timer.release(event.timestamp);
+            }
+        });
+        Ok(button)
     }
 
     impl_device!();
     impl_gpio_device!();
     impl_io_device!();
+    impl_pin_numbering!(|pin| Self::try_new(pin, true, None));
     impl_events_mixin!();
+    impl_handlers!();
+    impl_poll_events!();
+    impl_tags!();
 
     //// Pause the program until the device is deactivated, or the timeout is reached.
     /// * `timeout` - Number of seconds to wait before proceeding. If this is None, then wait indefinitely until the device is inactive.
     pub fn wait_for_release(&mut self, timeout: Option<f32>) {
-        self.wait_for(timeout, false)
+        self.wait_for(timeout, false);
     }
 
     /// Pause the program until the device is activated, or the timeout is reached.
     /// * `timeout` - Number of seconds to wait before proceeding. If this is None, then wait indefinitely until the device is active.
     pub fn wait_for_press(&mut self, timeout: Option<f32>) {
-        self.wait_for(timeout, true)
+        self.wait_for(timeout, true);
+    }
+
+    /// Pause the program until the button has been pressed `n` times, or the timeout is reached.
+    /// Only presses made during the call are counted, so each call starts again from zero.
+    /// Returns ``True`` if the button was pressed `n` times in time.
+    /// * `n` - The number of presses to wait for
+    /// * `timeout` - How long to wait for. If this is None, then wait indefinitely until the button has been pressed `n` times.
+    pub fn wait_for_count(&mut self, n: u32, timeout: Option<Duration>) -> bool {
+        let presses = Arc::new((Mutex::new(0u32), Condvar::new()));
+        let counter = Arc::clone(&presses);
+        let token = self.add_handler(move |event| {
+            if event.active {
+                let (lock, cvar) = &*counter;
+                *lock.lock().unwrap() += 1;
+                cvar.notify_all();
+            }
+        });
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let (lock, cvar) = &*presses;
+        let mut count = lock.lock().unwrap();
+        let reached = loop {
+            if *count >= n {
+                break true;
+            }
+            match deadline {
+                None => count = cvar.wait(count).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break false;
+                    }
+                    count = cvar.wait_timeout(count, deadline - now).unwrap().0;
+                }
+            }
+        };
+        drop(count);
+        self.remove_handler(token);
+        reached
+    }
+
+    /// Returns ``True`` if the button is currently pressed and ``False`` otherwise.
+    pub fn is_pressed(&self) -> bool {
+        self.is_active()
+    }
+
+    /// Returns how long the last complete press lasted, or `None` if the button hasn't been
+    /// pressed and released yet. Presses are timed in the background from the time of each edge,
+    /// so this is up to date as soon as the button is released.
+    pub fn last_press_duration(&self) -> Option<Duration> {
+        self.press_timer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .last_press_duration
+    }
+
+    /// Invokes the function given, with the duration of the press, each time the button is released.
+    /// The function is registered with `add_handler`, so it runs on a background thread
+    /// and can be removed with the returned token.
+    /// * `f` - The function to call on release
+    pub fn when_released_with_duration<F>(&mut self, f: F) -> HandlerToken
+    where
+        F: Fn(Duration) + Send + 'static,
+    {
+        // the button's own timer is updated by another handler, so presses are timed again here
+        let press_timer = Mutex::new(PressTimer::default());
+        self.add_handler(move |event| {
+            let mut press_timer = press_timer.lock().unwrap();
+            if event.active {
+                press_timer.press(event.timestamp);
+            } else if let Some(duration) = press_timer.release(event.timestamp) {
+                drop(press_timer);
+                f(duration);
+            }
+        })
+    }
+}
+
+/// How far apart the presses of a chord can be by default for it to count as pressed together
+const DEFAULT_CHORD_WINDOW: Duration = Duration::from_millis(150);
+
+/// How long after an edge on a button further edges are ignored as contact bounce when detecting chords
+const CHORD_BOUNCE_TIME: Duration = Duration::from_millis(20);
+
+type ChordAction = Arc<Mutex<Box<dyn Fn() + Send>>>;
+
+// A function registered with `when_chord`, which fires once each time its buttons are held together
+struct Chord {
+    buttons: Vec<usize>,
+    action: ChordAction,
+    fired: bool,
+}
+
+// Which buttons of a board are held and since when, shared by the handlers of all its buttons
+struct ChordState {
+    held: Vec<Option<Instant>>,
+    last_edge: Vec<Option<Instant>>,
+    chords: Vec<Chord>,
+    window: Duration,
+    exact: bool,
+}
+
+impl ChordState {
+    // Record an edge on button `index`, returning the chords it completes
+    fn update(&mut self, index: usize, event: InputEvent) -> Vec<ChordAction> {
+        if let Some(last) = self.last_edge[index] {
+            if event.timestamp.saturating_duration_since(last) < CHORD_BOUNCE_TIME {
+                return Vec::new();
+            }
+        }
+        self.last_edge[index] = Some(event.timestamp);
+
+        if !event.active {
+            self.held[index] = None;
+            for chord in &mut self.chords {
+                if chord.buttons.contains(&index) {
+                    chord.fired = false;
+                }
+            }
+            return Vec::new();
+        }
+        if self.held[index].is_some() {
+            return Vec::new();
+        }
+        self.held[index] = Some(event.timestamp);
+
+        let held = &self.held;
+        let held_count = held.iter().filter(|held| held.is_some()).count();
+        let mut completed = Vec::new();
+        for chord in &mut self.chords {
+            if chord.fired || (self.exact && held_count != chord.buttons.len()) {
+                continue;
+            }
+            let times: Option<Vec<Instant>> = chord.buttons.iter().map(|b| held[*b]).collect();
+            if let Some(times) = times {
+                let first = times.iter().min().unwrap();
+                let last = times.iter().max().unwrap();
+                if *last - *first <= self.window {
+                    chord.fired = true;
+                    completed.push(Arc::clone(&chord.action));
+                }
+            }
+        }
+        completed
+    }
+}
+
+/// Represents a group of buttons, e.g. a keypad of separate buttons or the buttons of a
+/// game controller, which can detect chords of buttons pressed together.
+pub struct ButtonBoard {
+    buttons: Vec<Button>,
+    chords: Arc<Mutex<ChordState>>,
+    handlers: Vec<HandlerToken>,
+}
+
+impl ButtonBoard {
+    /// Returns a ButtonBoard with a Button attached to each of the pin numbers given,
+    /// with the pins pulled high with an internal resistor
+    /// * `pins` - The GPIO pins which the buttons are attached to
+    pub fn new(pins: &[u8]) -> ButtonBoard {
+        ButtonBoard::from_buttons(pins.iter().map(|pin| Button::new(*pin)).collect())
+    }
+
+    /// Returns a ButtonBoard made of the buttons given
+    /// * `buttons` - The buttons on the board, with their own configuration
+    pub fn from_buttons(buttons: Vec<Button>) -> ButtonBoard {
+        let len = buttons.len();
+        ButtonBoard {
+            buttons,
+            chords: Arc::new(Mutex::new(ChordState {
+                held: vec![None; len],
+                last_edge: vec![None; len],
+                chords: Vec::new(),
+                window: DEFAULT_CHORD_WINDOW,
+                exact: true,
+            })),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Returns ``True`` if any of the buttons is currently pressed and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.buttons.iter().any(|button| button.is_active())
+    }
+
+    /// Returns whether each button is currently pressed, in order.
+    pub fn value(&self) -> Vec<bool> {
+        self.buttons
+            .iter()
+            .map(|button| button.is_active())
+            .collect()
+    }
+
+    /// Returns the buttons on the board.
+    pub fn buttons(&mut self) -> &mut [Button] {
+        &mut self.buttons
+    }
+
+    /// Invokes the function given when the buttons at the indexes in `combo` are held together.
+    /// The buttons must all be pressed within the chord window of each other, so pressing them
+    /// one after the other doesn't trigger the chord, and the function is called once per chord,
+    /// however long it is held. Presses are debounced for 20ms. The function runs on a background thread.
+    /// * `combo` - The indexes of the buttons in the chord, in any order
+    /// * `f` - The function to call when the chord is pressed
+    pub fn when_chord<F>(&mut self, combo: &[usize], f: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        if combo.is_empty() || combo.iter().any(|index| *index >= self.buttons.len()) {
+            println!("combo must be a non-empty list of button indexes");
+            return;
+        }
+        let mut buttons = combo.to_vec();
+        buttons.sort_unstable();
+        buttons.dedup();
+        self.chords.lock().unwrap().chords.push(Chord {
+            buttons,
+            action: Arc::new(Mutex::new(Box::new(f))),
+            fired: false,
+        });
+        if self.handlers.is_empty() {
+            for (index, button) in self.buttons.iter_mut().enumerate() {
+                let chords = Arc::clone(&self.chords);
+                self.handlers.push(button.add_handler(move |event| {
+                    let completed = chords.lock().unwrap().update(index, event);
+                    for action in completed {
+                        (action.lock().unwrap())();
+                    }
+                }));
+            }
+        }
+    }
+
+    /// Remove all the functions registered with `when_chord`.
+    pub fn clear_chords(&mut self) {
+        for (button, token) in self.buttons.iter_mut().zip(self.handlers.drain(..)) {
+            button.remove_handler(token);
+        }
+        let mut chords = self.chords.lock().unwrap();
+        chords.chords.clear();
+        chords.held.iter_mut().for_each(|held| *held = None);
+        chords.last_edge.iter_mut().for_each(|edge| *edge = None);
+    }
+
+    /// Set how far apart the presses of a chord can be for the buttons to count as pressed
+    /// together, 150ms by default.
+    pub fn set_chord_window(&mut self, window: Duration) {
+        self.chords.lock().unwrap().window = window;
+    }
+
+    /// Returns how far apart the presses of a chord can be for the buttons to count as pressed together.
+    pub fn chord_window(&self) -> Duration {
+        self.chords.lock().unwrap().window
+    }
+
+    /// Set whether a chord fires only when exactly its buttons are held (``True``, the default)
+    /// or whenever at least its buttons are held, even if others are held too (``False``).
+    pub fn set_exact_chords(&mut self, exact: bool) {
+        self.chords.lock().unwrap().exact = exact;
+    }
+
+    /// Shut down the board and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+/// Represents a generic input device which takes its value from the average of a queue of
+/// historical readings, sampled in a background thread. This is useful for noisy sensors such
+/// as PIR motion sensors and LDR light sensors.
+///
+/// The device is active when the average of the queue is above `threshold`.
+/// A hysteresis band can be set with `set_hysteresis` to stop the device flipping back and forth
+/// while the value hovers around the threshold. A longer queue or a lower sample rate rejects more noise but responds more slowly.
+pub struct SmoothedInputDevice {
+    pin: u8,
+    threshold: f64,
+    hysteresis: f64,
+    active: AtomicBool,
+    state: Arc<Mutex<SmoothedState>>,
+    handle: ThreadGuard,
+    _reservation: Reservation,
+}
+
+// The readings of a `SmoothedInputDevice` and how they are taken, shared with the sampling thread
+#[derive(Debug)]
+struct SmoothedState {
+    queue: VecDeque<bool>,
+    queue_len: usize,
+    sample_rate: f64,
+}
+
+impl SmoothedInputDevice {
+    /// Returns a SmoothedInputDevice with the pin number given with the pin pulled to low by default,
+    /// a queue of 5 readings sampled at 100 Hz and a threshold of 0.5
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new(pin: u8) -> SmoothedInputDevice {
+        SmoothedInputDevice::try_new(pin, false).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a SmoothedInputDevice with the pin number given with the pin pulled high with an internal resistor by default
+    /// * `pin` - The GPIO pin which the device is attached to
+    pub fn new_with_pullup(pin: u8) -> SmoothedInputDevice {
+        SmoothedInputDevice::try_new(pin, true).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    // Returns a SmoothedInputDevice with the pin number and pull given, or an error if the pin
+    // can't be accessed
+    pub(crate) fn try_new(pin: u8, pull_up: bool) -> Result<SmoothedInputDevice, GpioZeroError> {
+        let pull = if pull_up {
+            PullUpDown::PullUp
+        } else {
+            PullUpDown::PullDown
+        };
+        let (reservation, pin) = claim_input(pin, "SmoothedInputDevice", pull)?;
+        Ok(SmoothedInputDevice::from_pin(pin, !pull_up, reservation))
+    }
+
+    impl_pin_numbering!(|pin| Self::try_new(pin, false));
+
+    fn from_pin(
+        pin: Box<dyn InputPin>,
+        active_state: bool,
+        reservation: Reservation,
+    ) -> SmoothedInputDevice {
+        let number = pin.pin();
+        let state = Arc::new(Mutex::new(SmoothedState {
+            queue: VecDeque::new(),
+            queue_len: 5,
+            sample_rate: 100.0,
+        }));
+        let running = Arc::new(AtomicBool::new(false));
+
+        let sampler_state = Arc::clone(&state);
+        let sampler_running = Arc::clone(&running);
+        let handle = ThreadGuard::new(running);
+        handle.spawn(move || {
+            let mut clock = StoppableClock::new(TimingStrategy::Sleep, &sampler_running);
+            while sampler_running.load(Ordering::SeqCst) {
+                let active = (pin.read() == Level::High) == active_state;
+                let delay = {
+                    let mut state = sampler_state.lock().unwrap();
+                    state.queue.push_back(active);
+                    while state.queue.len() > state.queue_len {
+                        state.queue.pop_front();
+                    }
+                    Duration::from_micros((1_000_000.0 / state.sample_rate) as u64)
+                };
+                clock.sleep(delay);
+            }
+        });
+
+        SmoothedInputDevice {
+            pin: number,
+            threshold: 0.5,
+            hysteresis: 0.0,
+            active: AtomicBool::new(false),
+            state,
+            handle,
+            _reservation: reservation,
+        }
+    }
+
+    /// Returns the average of the queued readings, between 0.0 (always inactive) and 1.0 (always active).
+    pub fn value(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        if state.queue.is_empty() {
+            return 0.0;
+        }
+        state.queue.iter().filter(|active| **active).count() as f64 / state.queue.len() as f64
+    }
+
+    /// Returns ``True`` if the value is above the threshold and ``False`` otherwise.
+    /// With a hysteresis band, the device only becomes active above `threshold + band / 2`
+    /// and only becomes inactive again below `threshold - band / 2`.
+    pub fn is_active(&self) -> bool {
+        let value = self.value();
+        let half_band = self.hysteresis / 2.0;
+        let active = if self.active.load(Ordering::SeqCst) {
+            value >= self.threshold - half_band
+        } else {
+            value > self.threshold + half_band
+        };
+        self.active.store(active, Ordering::SeqCst);
+        active
+    }
+
+    /// Set the value above which the device is considered active
+    pub fn set_threshold(&mut self, value: f64) {
+        if !(0.0..=1.0).contains(&value) {
+            println!("threshold must be between 0.0 and 1.0");
+        } else {
+            self.threshold = value;
+        }
+    }
+
+    /// Get the value above which the device is considered active
+    pub fn get_threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Set the width of the band around the threshold in which the device keeps its previous state
+    /// * `band` - The width of the band, 0.0 (the default) disables hysteresis
+    pub fn set_hysteresis(&mut self, band: f64) {
+        if !(0.0..=1.0).contains(&band) {
+            println!("hysteresis must be between 0.0 and 1.0");
+        } else {
+            self.hysteresis = band;
+        }
+    }
+
+    /// Get the width of the band around the threshold in which the device keeps its previous state
+    pub fn get_hysteresis(&self) -> f64 {
+        self.hysteresis
+    }
+
+    /// Set the number of readings averaged to get the value.
+    /// When the queue is shortened the oldest readings are dropped.
+    pub fn set_queue_len(&mut self, value: usize) {
+        if value == 0 {
+            println!("queue_len must be at least 1");
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.queue_len = value;
+        while state.queue.len() > value {
+            state.queue.pop_front();
+        }
+    }
+
+    /// Get the number of readings averaged to get the value
+    pub fn get_queue_len(&self) -> usize {
+        self.state.lock().unwrap().queue_len
+    }
+
+    /// Set how many readings are taken per second
+    pub fn set_sample_rate(&mut self, hz: f64) {
+        if hz <= 0.0 {
+            println!("sample_rate must be greater than 0");
+        } else {
+            self.state.lock().unwrap().sample_rate = hz;
+        }
+    }
+
+    /// Get how many readings are taken per second
+    pub fn get_sample_rate(&self) -> f64 {
+        self.state.lock().unwrap().sample_rate
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for SmoothedInputDevice {
+    fn drop(&mut self) {
+        self.handle.stop();
+    }
+}
+
+impl_device_trait!(
+    InputDevice,
+    DigitalInputDevice,
+    Button,
+    ButtonBoard,
+    SmoothedInputDevice
+);
+
+/// Represents an HC-SR04 ultrasonic distance sensor, as found in the CamJam #3 EduKit.
+///
+/// The distance is calculated from the time taken for an ultrasonic pulse to echo back.
+/// The defaults assume an HC-SR04 style module: a 10μs trigger pulse, sound travelling
+/// at 343 m/s (dry air at roughly 20°C) and at least 60ms between measurements so that
+/// stray echoes of the previous pulse have died down.
+///
+/// Connect the echo pin through a voltage divider, since the sensor outputs 5V.
+pub struct DistanceSensor {
+    echo: Box<dyn InputPin>,
+    trigger: Box<dyn IoPin>,
+    max_distance: f64,
+    trigger_pulse_width: Duration,
+    speed_of_sound: f64,
+    sample_interval: Duration,
+    last_sample: Option<Instant>,
+    _reservations: (Reservation, Reservation),
+}
+
+impl DistanceSensor {
+    /// Returns a DistanceSensor with the echo and trigger pin numbers given
+    /// * `echo` - The GPIO pin which the ECHO pin is attached to
+    /// * `trigger` - The GPIO pin which the TRIG pin is attached to
+    pub fn new(echo: u8, trigger: u8) -> DistanceSensor {
+        let (echo_reservation, echo) = claim_input(echo, "DistanceSensor", PullUpDown::Off)
+            .unwrap_or_else(|e| panic!("{:?}", e));
+        let (trigger_reservation, mut trigger) =
+            claim_io(trigger, "DistanceSensor", Mode::Output).unwrap_or_else(|e| panic!("{:?}", e));
+        trigger.set_low();
+        DistanceSensor {
+            echo,
+            trigger,
+            max_distance: 1.0,
+            trigger_pulse_width: Duration::from_micros(10),
+            speed_of_sound: 343.26,
+            sample_interval: Duration::from_millis(60),
+            last_sample: None,
+            _reservations: (echo_reservation, trigger_reservation),
+        }
+    }
+
+    /// Returns the current distance measured by the sensor in meters.
+    /// This value is between 0 and `max_distance`; if no echo is received in time,
+    /// `max_distance` is returned.
+    pub fn distance(&mut self) -> f64 {
+        if let Some(last_sample) = self.last_sample {
+            let elapsed = last_sample.elapsed();
+            if elapsed < self.sample_interval {
+                thread::sleep(self.sample_interval - elapsed);
+            }
+        }
+        self.last_sample = Some(Instant::now());
+
+        // send the trigger pulse, busy waiting since the pulse is only a few microseconds long
+        self.trigger.set_high();
+        let start = Instant::now();
+        while start.elapsed() < self.trigger_pulse_width {}
+        self.trigger.set_low();
+
+        // allow for the round trip to `max_distance` plus the time the module takes to respond
+        let timeout = Duration::from_micros(
+            (self.max_distance * 2.0 / self.speed_of_sound * 1_000_000.0) as u64,
+        ) + Duration::from_millis(10);
+
+        let start = Instant::now();
+        while self.echo.read() == Level::Low {
+            if start.elapsed() > timeout {
+                return self.max_distance;
+            }
+        }
+        let pulse_start = Instant::now();
+        while self.echo.read() == Level::High {
+            if pulse_start.elapsed() > timeout {
+                return self.max_distance;
+            }
+        }
+        let pulse = pulse_start.elapsed();
+        let seconds = pulse.as_secs() as f64 + f64::from(pulse.subsec_nanos()) / 1_000_000_000.0;
+        (seconds * self.speed_of_sound / 2.0).min(self.max_distance)
+    }
+
+    /// Set the maximum distance, in meters, that the sensor will measure
+    pub fn set_max_distance(&mut self, value: f64) {
+        if value <= 0.0 {
+            println!("max_distance must be greater than 0");
+        } else {
+            self.max_distance = value;
+        }
+    }
+
+    /// Set the duration of the pulse sent on the trigger pin to start a measurement
+    pub fn set_trigger_pulse_width(&mut self, value: Duration) {
+        self.trigger_pulse_width = value;
+    }
+
+    /// Set the speed of sound, in meters per second, used to convert echo time to distance.
+    /// The speed of sound in air is roughly `331.3 + 0.606 * temperature` (in °C),
+    /// so adjusting this gives more accurate readings in hot or cold environments.
+    pub fn set_speed_of_sound(&mut self, m_per_s: f64) {
+        if m_per_s <= 0.0 {
+            println!("speed_of_sound must be greater than 0");
+        } else {
+            self.speed_of_sound = m_per_s;
+        }
+    }
+
+    /// Set the minimum time between two consecutive measurements
+    pub fn set_sample_interval(&mut self, value: Duration) {
+        self.sample_interval = value;
+    }
+
+    /// Get the maximum distance, in meters, that the sensor will measure
+    pub fn get_max_distance(&self) -> f64 {
+        self.max_distance
+    }
+
+    /// Get the duration of the pulse sent on the trigger pin to start a measurement
+    pub fn get_trigger_pulse_width(&self) -> Duration {
+        self.trigger_pulse_width
+    }
+
+    /// Get the speed of sound, in meters per second, used to convert echo time to distance
+    pub fn get_speed_of_sound(&self) -> f64 {
+        self.speed_of_sound
+    }
+
+    /// Get the minimum time between two consecutive measurements
+    pub fn get_sample_interval(&self) -> Duration {
+        self.sample_interval
+    }
+}
+
+/// The models of DHT temperature and humidity sensor supported by `DHTxx`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DhtModel {
+    /// DHT11, with 1°C and 1% resolution
+    Dht11,
+    /// DHT22 (also sold as AM2302), with 0.1°C and 0.1% resolution
+    Dht22,
+}
+
+impl DhtModel {
+    // Check the checksum of the five bytes sent by the sensor and convert them to a reading
+    fn decode(self, data: [u8; 5]) -> Result<Reading, GpioZeroError> {
+        let sum = data[..4].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if sum != data[4] {
+            return Err(GpioZeroError::ChecksumMismatch);
+        }
+
+        Ok(match self {
+            DhtModel::Dht11 => Reading {
+                humidity: f64::from(data[0]) + f64::from(data[1]) / 10.0,
+                temperature_c: f64::from(data[2]) + f64::from(data[3] & 0x7f) / 10.0,
+            },
+            DhtModel::Dht22 => {
+                let humidity = (u16::from(data[0]) << 8) | u16::from(data[1]);
+                let temperature = (u16::from(data[2] & 0x7f) << 8) | u16::from(data[3]);
+                let sign = if data[2] & 0x80 != 0 { -1.0 } else { 1.0 };
+                Reading {
+                    humidity: f64::from(humidity) / 10.0,
+                    temperature_c: sign * f64::from(temperature) / 10.0,
+                }
+            }
+        })
+    }
+}
+
+/// A temperature and humidity reading from a `DHTxx` sensor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    /// The temperature in degrees Celsius
+    pub temperature_c: f64,
+    /// The relative humidity in percent
+    pub humidity: f64,
+}
+
+/// Represents a DHT11 or DHT22 temperature and humidity sensor.
+/// Connect the data pin of the sensor to any GPIO pin, with a 10 kohm pull-up resistor to 3.3v.
+///
+/// The sensor uses a single-wire protocol with microsecond timings, which is read by
+/// busy waiting on the pin. On a non-realtime OS the process can be descheduled in the
+/// middle of a read, so reads are best-effort: expect the occasional
+/// `GpioZeroError::Timeout` or `GpioZeroError::ChecksumMismatch` and use
+/// `read_with_retries` to retry them.
+pub struct DHTxx {
+    pin: Box<dyn IoPin>,
+    model: DhtModel,
+    _reservation: Reservation,
+}
+
+impl DHTxx {
+    /// Returns a DHTxx with the pin number and sensor model given
+    /// * `pin` - The GPIO pin which the data pin of the sensor is attached to
+    /// * `model` - The model of the sensor
+    pub fn new(pin: u8, model: DhtModel) -> DHTxx {
+        match claim_io(pin, "DHTxx", Mode::Input) {
+            Err(e) => panic!("{:?}", e),
+            Ok((reservation, pin)) => DHTxx {
+                pin,
+                model,
+                _reservation: reservation,
+            },
+        }
+    }
+
+    /// Read the temperature and humidity from the sensor.
+    /// The sensor should not be read more than once every 2 seconds.
+    pub fn read(&mut self) -> Result<Reading, GpioZeroError> {
+        let start_signal = match self.model {
+            DhtModel::Dht11 => Duration::from_millis(18),
+            DhtModel::Dht22 => Duration::from_micros(1100),
+        };
+
+        // hold the line low to ask the sensor for a reading, then release it
+        self.pin.set_mode(Mode::Output);
+        self.pin.set_low();
+        let start = Instant::now();
+        while start.elapsed() < start_signal {}
+        self.pin.set_high();
+        self.pin.set_mode(Mode::Input);
+
+        // the sensor answers with ~80μs low and ~80μs high before sending data
+        self.wait_for_level(Level::Low)?;
+        self.wait_for_level(Level::High)?;
+        self.wait_for_level(Level::Low)?;
+
+        // each bit is ~50μs low followed by ~27μs high for a 0 or ~70μs high for a 1
+        let mut data = [0u8; 5];
+        for i in 0..40 {
+            self.wait_for_level(Level::High)?;
+            let high = self.wait_for_level(Level::Low)?;
+            if high > Duration::from_micros(50) {
+                data[i / 8] |= 1 << (7 - (i % 8));
+            }
+        }
+
+        self.model.decode(data)
+    }
+
+    /// Read the temperature and humidity from the sensor, retrying failed reads.
+    /// Waits 2 seconds between attempts, as required by the sensor.
+    /// * `retries` - Number of times to retry after the first failed read
+    pub fn read_with_retries(&mut self, retries: u32) -> Result<Reading, GpioZeroError> {
+        let mut result = self.read();
+        for _ in 0..retries {
+            if result.is_ok() {
+                break;
+            }
+            thread::sleep(Duration::from_secs(2));
+            result = self.read();
+        }
+        result
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        self.pin.pin()
+    }
+
+    // Wait for the line to reach `level`, returning how long it took
+    fn wait_for_level(&self, level: Level) -> Result<Duration, GpioZeroError> {
+        let start = Instant::now();
+        while self.pin.read() != level {
+            if start.elapsed() > Duration::from_micros(200) {
+                return Err(GpioZeroError::Timeout);
+            }
+        }
+        Ok(start.elapsed())
+    }
+}
+
+/// How often a `Keypad` scans its matrix in the background
+const KEYPAD_SCAN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Number of consecutive scans a key must be seen in before it counts as pressed
+const KEYPAD_DEBOUNCE_SCANS: u32 = 3;
+
+/// Represents a matrix keypad, such as a 4x4 membrane keypad, with a switch at each crossing of a row and a column.
+///
+/// The keypad is scanned by driving one row high at a time and reading which columns follow it.
+/// Only one key is reported at a time; when several keys are held the first one found is returned.
+pub struct Keypad {
+    matrix: Arc<Mutex<KeypadMatrix>>,
+    running: Arc<AtomicBool>,
+    handle: ThreadGuard,
+}
+
+// The pins and layout of a `Keypad`, shared with the scanning thread
+#[derive(Debug)]
+struct KeypadMatrix {
+    rows: Vec<OutputDevice>,
+    cols: Vec<InputDevice>,
+    layout: Vec<Vec<char>>,
+}
+
+impl KeypadMatrix {
+    fn scan(&mut self) -> Option<char> {
+        for (r, row) in self.rows.iter_mut().enumerate() {
+            row.on();
+            let col = self.cols.iter().position(|col| col.is_active());
+            row.off();
+            if let Some(c) = col {
+                return Some(self.layout[r][c]);
+            }
+        }
+        None
+    }
+}
+
+impl Keypad {
+    /// Returns a Keypad on the pins given. The columns are pulled low by default.
+    ///
+    /// * `rows` - The GPIO pins the rows are attached to
+    /// * `cols` - The GPIO pins the columns are attached to
+    /// * `layout` - The character of each key, one slice per row with one character per column
+    ///
+    pub fn new(rows: &[u8], cols: &[u8], layout: &[&[char]]) -> Keypad {
+        if layout.len() != rows.len() || layout.iter().any(|row| row.len() != cols.len()) {
+            panic!("layout must have one row per row pin and one key per column pin");
+        }
+        let rows = rows
+            .iter()
+            .map(|pin| {
+                let mut row = OutputDevice::new(*pin);
+                row.off();
+                row
+            })
+            .collect();
+        let running = Arc::new(AtomicBool::new(false));
+        Keypad {
+            matrix: Arc::new(Mutex::new(KeypadMatrix {
+                rows,
+                cols: cols.iter().map(|pin| InputDevice::new(*pin)).collect(),
+                layout: layout.iter().map(|row| row.to_vec()).collect(),
+            })),
+            handle: ThreadGuard::new(Arc::clone(&running)),
+            running,
+        }
+    }
+
+    /// Scan the keypad once, returning the key that is held down or `None` if no key is pressed.
+    pub fn read_key(&self) -> Option<char> {
+        self.matrix.lock().unwrap().scan()
+    }
+
+    /// Invokes the passed closure with the key each time a key is pressed.
+    /// The keypad is scanned every 10ms in the background, and a key must be held for 3 scans
+    /// in a row to count as pressed. It replaces any closure registered before.
+    /// * `action` - The function to call from the background thread
+    pub fn when_key_pressed<C>(&mut self, action: C)
+    where
+        C: Fn(char) + Send + 'static,
+    {
+        self.stop();
+
+        let matrix = Arc::clone(&self.matrix);
+        let running = Arc::clone(&self.running);
+        running.store(true, Ordering::SeqCst);
+        self.handle.spawn(move || {
+            let mut candidate = None;
+            let mut count = 0;
+            let mut pressed = None;
+            while running.load(Ordering::SeqCst) {
+                let key = matrix.lock().unwrap().scan();
+                if key == candidate {
+                    count += 1;
+                } else {
+                    candidate = key;
+                    count = 1;
+                }
+                if count >= KEYPAD_DEBOUNCE_SCANS && candidate != pressed {
+                    pressed = candidate;
+                    if let Some(key) = pressed {
+                        action(key);
+                    }
+                }
+                thread::sleep(KEYPAD_SCAN_INTERVAL);
+            }
+        });
+    }
+
+    /// Stop the background scanning started by `when_key_pressed`.
+    pub fn stop(&mut self) {
+        self.handle.stop();
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for Keypad {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dht11_readings_are_whole_numbers_with_a_tenth_byte() {
+        let reading = DhtModel::Dht11.decode([45, 0, 23, 5, 73]).unwrap();
+        assert_eq!(reading.humidity, 45.0);
+        assert_eq!(reading.temperature_c, 23.5);
+    }
+
+    #[test]
+    fn dht22_readings_are_tenths_with_a_sign_bit() {
+        // 65.2% and -10.1°C
+        let reading = DhtModel::Dht22
+            .decode([0x02, 0x8c, 0x80, 0x65, 0x73])
+            .unwrap();
+        assert_eq!(reading.humidity, 65.2);
+        assert_eq!(reading.temperature_c, -10.1);
+    }
+
+    #[test]
+    fn dht_readings_with_a_bad_checksum_are_rejected() {
+        assert!(matches!(
+            DhtModel::Dht22.decode([0x02, 0x8c, 0x00, 0x65, 0x00]),
+            Err(GpioZeroError::ChecksumMismatch)
+        ));
+    }
+
+    // A board of `buttons` buttons with a single chord of `chord`
+    fn chords(buttons: usize, chord: &[usize], exact: bool) -> ChordState {
+        ChordState {
+            held: vec![None; buttons],
+            last_edge: vec![None; buttons],
+            chords: vec![Chord {
+                buttons: chord.to_vec(),
+                action: Arc::new(Mutex::new(Box::new(|| {}))),
+                fired: false,
+            }],
+            window: DEFAULT_CHORD_WINDOW,
+            exact,
+        }
+    }
+
+    fn edge(start: Instant, millis: u64, active: bool) -> InputEvent {
+        InputEvent {
+            timestamp: start + Duration::from_millis(millis),
+            active,
+        }
+    }
+
+    #[test]
+    fn chords_fire_once_when_pressed_within_the_window() {
+        let start = Instant::now();
+        let mut state = chords(2, &[0, 1], false);
+        assert!(state.update(0, edge(start, 0, true)).is_empty());
+        assert_eq!(state.update(1, edge(start, 100, true)).len(), 1);
+
+        // the chord fires again only once its buttons have been released and pressed again
+        assert!(state.update(1, edge(start, 200, false)).is_empty());
+        assert!(state.update(0, edge(start, 200, false)).is_empty());
+        assert!(state.update(0, edge(start, 300, true)).is_empty());
+        assert_eq!(state.update(1, edge(start, 350, true)).len(), 1);
+    }
+
+    #[test]
+    fn chords_pressed_too_far_apart_do_not_fire() {
+        let start = Instant::now();
+        let mut state = chords(2, &[0, 1], false);
+        state.update(0, edge(start, 0, true));
+        assert!(state.update(1, edge(start, 151, true)).is_empty());
+    }
+
+    #[test]
+    fn chord_edges_within_the_bounce_time_are_ignored() {
+        let start = Instant::now();
+        let mut state = chords(2, &[0, 1], false);
+        state.update(0, edge(start, 0, true));
+        // a bounce releasing button 0 is ignored, so it is still held for the chord
+        assert!(state.update(0, edge(start, 10, false)).is_empty());
+        assert_eq!(state.update(1, edge(start, 50, true)).len(), 1);
+    }
+
+    #[test]
+    fn exact_chords_do_not_fire_with_other_buttons_held() {
+        let start = Instant::now();
+        let mut state = chords(3, &[0, 1], true);
+        state.update(2, edge(start, 0, true));
+        state.update(0, edge(start, 10, true));
+        assert!(state.update(1, edge(start, 20, true)).is_empty());
+
+        let mut state = chords(3, &[0, 1], true);
+        state.update(0, edge(start, 0, true));
+        assert_eq!(state.update(1, edge(start, 20, true)).len(), 1);
     }
 }
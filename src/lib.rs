@@ -6,7 +6,7 @@
 //! This library is based on [GPIOZero](https://gpiozero.readthedocs.io/en/stable/index.html)
 //! library.
 //!
-//! _Note: This is a work in progress. The library will eventually support `embedded-hal` based drivers_
+//! _Note: This is a work in progress. Devices implement the `embedded-hal` traits with the `hal` feature, see the `hal` module_
 //!
 //!
 //! The idea is to get started with physical computing using Rust with little coding
@@ -16,7 +16,7 @@
 //!
 //! # Example : Blinking an LED
 //!
-//! ```
+//! ```no_run
 //! use rust_gpiozero::*;
 //!
 //! // Create a new LED attached to Pin 17
@@ -27,13 +27,40 @@
 //! led.blink(2.0,3.0);
 //! ```
 
+pub use self::choreographer::*;
 pub use self::devices::*;
+pub use self::error::*;
+pub use self::factory::*;
 pub use self::input_devices::*;
 pub use self::output_devices::*;
+pub use self::pattern::*;
+#[cfg(feature = "signal")]
+pub use self::signal::*;
+pub use self::spi::*;
+pub use self::timing::TimingStrategy;
 
+pub mod choreographer;
 #[macro_use]
 pub mod devices;
+pub mod error;
+pub mod factory;
+pub mod gpiod;
+#[cfg(feature = "hal")]
+pub mod hal;
 #[macro_use]
 pub mod output_devices;
 #[macro_use]
 pub mod input_devices;
+pub mod mock;
+pub mod pattern;
+pub mod pins;
+pub mod registry;
+pub mod remote;
+pub mod rtttl;
+pub mod sbc;
+#[cfg(feature = "signal")]
+pub mod signal;
+pub mod spi;
+pub mod tick;
+pub mod timing;
+pub mod tools;
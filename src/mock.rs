@@ -0,0 +1,332 @@
+//! A pin backend that simulates the pins in memory, for testing device logic without a Raspberry Pi
+//!
+//! Every level a mock pin takes is recorded with the time it changed, and the level seen by an
+//! input can be driven from the test. Mock pins have no interrupts, so input devices built on them
+//! poll the level, and events are seen up to the device's `poll_interval` late.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_gpiozero::mock::MockPinFactory;
+//! use rust_gpiozero::pins::set_pin_factory;
+//! use rust_gpiozero::*;
+//!
+//! let factory = MockPinFactory::new();
+//! set_pin_factory(factory.clone());
+//!
+//! let mut led = LED::new(17);
+//! led.on();
+//! led.off();
+//! assert_eq!(factory.pin(17).levels(), vec![false, true, false]);
+//!
+//! let button = Button::new(2);
+//! assert!(!button.is_active());
+//! factory.pin(2).drive_low();
+//! assert!(button.is_active());
+//! ```
+use crate::error::GpioZeroError;
+use crate::pins::{InputPin, IoPin, PinFactory};
+use rppal::gpio::{Level, Mode, PullUpDown};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+// The simulated state of a single pin, shared by the factory and the pin handed to a device
+#[derive(Debug)]
+struct MockState {
+    mode: Mode,
+    pull: PullUpDown,
+    // the level driven by the pin while it is an output
+    output: Level,
+    // the level driven onto the pin from outside by the test, if any
+    external: Option<Level>,
+    pwm: Option<(f64, f64)>,
+    history: Vec<(Instant, Level)>,
+    acquired: bool,
+    // set by the test to make software PWM on the pin fail
+    pwm_fails: bool,
+}
+
+impl MockState {
+    fn new() -> MockState {
+        MockState {
+            mode: Mode::Input,
+            pull: PullUpDown::Off,
+            output: Level::Low,
+            external: None,
+            pwm: None,
+            history: vec![(Instant::now(), Level::Low)],
+            acquired: false,
+            pwm_fails: false,
+        }
+    }
+
+    // The level of the line: driven by the pin if it is an output, and otherwise by the test,
+    // or by the pull if nothing is driving it
+    fn level(&self) -> Level {
+        match (self.mode, self.external) {
+            (Mode::Output, _) => self.output,
+            (_, Some(level)) => level,
+            _ => match self.pull {
+                PullUpDown::PullUp => Level::High,
+                _ => Level::Low,
+            },
+        }
+    }
+
+    // Apply a change to the pin, recording the new level if the change moved the line
+    fn update<F: FnOnce(&mut MockState)>(&mut self, change: F) {
+        let before = self.level();
+        change(self);
+        let after = self.level();
+        if after != before {
+            self.history.push((Instant::now(), after));
+        }
+    }
+}
+
+type SharedState = Arc<Mutex<MockState>>;
+
+fn lock(state: &Mutex<MockState>) -> MutexGuard<'_, MockState> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A `PinFactory` whose pins only exist in memory.
+/// Clones share the same pins, so a test can keep one to inspect and drive the pins after
+/// passing another to `set_pin_factory`.
+#[derive(Debug, Clone, Default)]
+pub struct MockPinFactory {
+    pins: Arc<Mutex<HashMap<u8, SharedState>>>,
+}
+
+impl MockPinFactory {
+    /// Returns a factory with every pin low, unused and not pulled either way
+    pub fn new() -> MockPinFactory {
+        MockPinFactory::default()
+    }
+
+    fn state(&self, pin: u8) -> SharedState {
+        let mut pins = self.pins.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(
+            pins.entry(pin)
+                .or_insert_with(|| Arc::new(Mutex::new(MockState::new()))),
+        )
+    }
+
+    // Mark `pin` as in use, unless a device already holds it
+    fn acquire(&self, pin: u8) -> Result<SharedState, GpioZeroError> {
+        let state = self.state(pin);
+        {
+            let mut locked = lock(&state);
+            if locked.acquired {
+                return Err(GpioZeroError::PinUnavailable(pin));
+            }
+            locked.acquired = true;
+        }
+        Ok(state)
+    }
+
+    /// Returns a handle to the mock pin with the BCM number given, for inspecting and driving it
+    /// from a test. The pin doesn't need to be in use by a device.
+    pub fn pin(&self, pin: u8) -> MockPin {
+        MockPin {
+            pin,
+            state: self.state(pin),
+        }
+    }
+}
+
+impl PinFactory for MockPinFactory {
+    fn input_pin(&self, pin: u8, pull: PullUpDown) -> Result<Box<dyn InputPin>, GpioZeroError> {
+        let state = self.acquire(pin)?;
+        lock(&state).update(|state| {
+            state.mode = Mode::Input;
+            state.pull = pull;
+        });
+        Ok(Box::new(MockDevicePin { pin, state }))
+    }
+
+    fn io_pin(&self, pin: u8, mode: Mode) -> Result<Box<dyn IoPin>, GpioZeroError> {
+        let state = self.acquire(pin)?;
+        lock(&state).update(|state| state.mode = mode);
+        Ok(Box::new(MockDevicePin { pin, state }))
+    }
+
+    fn pin_available(&self, pin: u8) -> bool {
+        !lock(&self.state(pin)).acquired
+    }
+}
+
+/// A test's handle to a mock pin, from `MockPinFactory::pin`
+#[derive(Debug, Clone)]
+pub struct MockPin {
+    pin: u8,
+    state: SharedState,
+}
+
+impl MockPin {
+    /// Returns the BCM GPIO number of the pin
+    pub fn number(&self) -> u8 {
+        self.pin
+    }
+
+    /// Returns the current level of the line
+    pub fn level(&self) -> Level {
+        lock(&self.state).level()
+    }
+
+    /// Returns ``True`` if the line is currently high
+    pub fn is_high(&self) -> bool {
+        self.level() == Level::High
+    }
+
+    /// Returns the current mode of the pin
+    pub fn mode(&self) -> Mode {
+        lock(&self.state).mode
+    }
+
+    /// Returns ``True`` if a device currently holds the pin
+    pub fn in_use(&self) -> bool {
+        lock(&self.state).acquired
+    }
+
+    /// Returns the frequency in Hz and duty cycle of the software PWM on the pin, or `None` if there is none
+    pub fn pwm(&self) -> Option<(f64, f64)> {
+        lock(&self.state).pwm
+    }
+
+    /// Make software PWM on the pin fail with a `GpioZeroError::Io` until set back to ``False``,
+    /// to check how a device copes with errors from the backend
+    pub fn fail_pwm(&self, fail: bool) {
+        lock(&self.state).pwm_fails = fail;
+    }
+
+    /// Returns every level the line has taken, starting with its level when the pin was first
+    /// used, together with the time it changed to that level.
+    pub fn history(&self) -> Vec<(Instant, Level)> {
+        lock(&self.state).history.clone()
+    }
+
+    /// Returns the levels in `history` as ``True`` for high, without the times
+    pub fn levels(&self) -> Vec<bool> {
+        lock(&self.state)
+            .history
+            .iter()
+            .map(|(_, level)| *level == Level::High)
+            .collect()
+    }
+
+    /// Returns how long the line stayed at each level in `history`, apart from the current one
+    pub fn durations(&self) -> Vec<(Level, Duration)> {
+        lock(&self.state)
+            .history
+            .windows(2)
+            .map(|pair| (pair[0].1, pair[1].0 - pair[0].0))
+            .collect()
+    }
+
+    /// Forget the history, keeping only the current level
+    pub fn clear_history(&self) {
+        let mut state = lock(&self.state);
+        let level = state.level();
+        state.history = vec![(Instant::now(), level)];
+    }
+
+    /// Drive the line to `level` from outside, as a button or sensor would.
+    /// This is what an input reads; a pin in output mode keeps reading the level it drives.
+    pub fn drive(&self, level: Level) {
+        lock(&self.state).update(|state| state.external = Some(level));
+    }
+
+    /// Drive the line high from outside
+    pub fn drive_high(&self) {
+        self.drive(Level::High)
+    }
+
+    /// Drive the line low from outside
+    pub fn drive_low(&self) {
+        self.drive(Level::Low)
+    }
+
+    /// Stop driving the line from outside, leaving it at the level of its pull
+    pub fn release(&self) {
+        lock(&self.state).update(|state| state.external = None);
+    }
+}
+
+// The pin handed to a device by a MockPinFactory, which is released when the device drops it
+#[derive(Debug)]
+struct MockDevicePin {
+    pin: u8,
+    state: SharedState,
+}
+
+impl Drop for MockDevicePin {
+    fn drop(&mut self) {
+        lock(&self.state).update(|state| {
+            state.acquired = false;
+            state.mode = Mode::Input;
+            state.pull = PullUpDown::Off;
+            state.pwm = None;
+        });
+    }
+}
+
+impl InputPin for MockDevicePin {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn read(&self) -> Level {
+        lock(&self.state).level()
+    }
+}
+
+impl IoPin for MockDevicePin {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn mode(&self) -> Mode {
+        lock(&self.state).mode
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        lock(&self.state).update(|state| state.mode = mode);
+    }
+
+    fn read(&self) -> Level {
+        lock(&self.state).level()
+    }
+
+    fn write(&mut self, level: Level) {
+        lock(&self.state).update(|state| state.output = level);
+    }
+
+    fn set_pwm(&mut self, period: Duration, pulse_width: Duration) -> Result<(), GpioZeroError> {
+        let period = period.as_secs_f64();
+        if period <= 0.0 {
+            return Err(GpioZeroError::InvalidArgument(
+                "the PWM period must be greater than 0".to_string(),
+            ));
+        }
+        self.set_pwm_frequency(1.0 / period, pulse_width.as_secs_f64() / period)
+    }
+
+    fn set_pwm_frequency(&mut self, frequency: f64, duty_cycle: f64) -> Result<(), GpioZeroError> {
+        let mut state = lock(&self.state);
+        if state.pwm_fails {
+            return Err(GpioZeroError::Io(io::Error::other(
+                "software PWM on the mock pin was set to fail",
+            )));
+        }
+        state.pwm = Some((frequency, duty_cycle));
+        Ok(())
+    }
+
+    fn clear_pwm(&mut self) -> Result<(), GpioZeroError> {
+        lock(&self.state).pwm = None;
+        Ok(())
+    }
+}
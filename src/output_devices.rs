@@ -1,19 +1,121 @@
 //! Output device component interfaces for devices such as `LED`, `PWMLED`, etc
-use rppal::gpio::{Gpio, IoPin, Level, Mode};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::sync::Mutex;
+use crate::devices::{
+    claim_io, retry_attempts, sleep_micros, supports_hardware_pwm, with_retry, Device,
+    OutputDeviceTrait, PinNumbering, Reservation, ThreadGuard, ValueDevice, RETRY_DELAY,
+};
+use crate::error::GpioZeroError;
+use crate::gpiod::GpiodPinFactory;
+use crate::input_devices::{Button, Subscription};
+use crate::pattern::{BlinkPreset, Pattern};
+use crate::pins::{with_pin_factory, IoPin, LOCAL_PINS};
+use crate::registry::Tags;
+use crate::remote::RemotePinFactory;
+use crate::tick::TickDriver;
+use crate::timing::{
+    check_intervals, play, Clock, LimitedClock, Sink, StoppableClock, TimingStrategy,
+};
+use rppal::gpio::{Level, Mode};
+use rppal::pwm::{Channel, Polarity, Pwm};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::DerefMut;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Weak};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use std::thread;
-use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Represents a generic GPIO output device.
 #[derive(Debug)]
 pub struct OutputDevice {
-    pin: IoPin,
-    active_state: bool,
-    inactive_state: bool,
+    pin: Box<dyn IoPin>,
+    active_drive: PinDrive,
+    inactive_drive: PinDrive,
+    value: AtomicBool,
+    active_since: Option<Instant>,
+    active_duration: Duration,
+    // the value the pin is driven to, which lags `value` while a write is deferred
+    driven: bool,
+    pending: Option<bool>,
+    min_toggle_interval: Duration,
+    last_change: Option<Instant>,
+    // `None` for a pin that isn't one of the Pi's GPIO pins, e.g. an embedded-hal pin
+    _reservation: Option<Reservation>,
+}
+
+/// How an output device drives its pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveMode {
+    /// The pin is actively driven both high and low
+    PushPull,
+    /// The pin is only driven low, and is left floating (high impedance) instead of being driven high.
+    /// An external or internal pull-up resistor is needed for the line to read high.
+    OpenDrain,
+}
+
+/// What an output device does with its pin to represent a logical state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinDrive {
+    /// The pin is driven high
+    High,
+    /// The pin is driven low
+    Low,
+    /// The pin is released (switched to an input) and left floating, so the level is set
+    /// by a pull resistor or whatever else is connected to the line
+    Float,
+}
+
+/// How much current a GPIO pad can source or sink while holding its level.
+/// A lower strength slows the edges, reducing ringing and EMI on long wires, while a higher one
+/// drives heavier loads more cleanly. On the Raspberry Pi it is set for a whole bank of pins at
+/// once (GPIO 0 to 27 share one setting), and the default is 8mA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveStrength {
+    /// 2mA
+    Ma2,
+    /// 4mA
+    Ma4,
+    /// 6mA
+    Ma6,
+    /// 8mA
+    Ma8,
+    /// 10mA
+    Ma10,
+    /// 12mA
+    Ma12,
+    /// 14mA
+    Ma14,
+    /// 16mA
+    Ma16,
+}
+
+impl DriveStrength {
+    /// Returns the drive strength in milliamps
+    pub fn milliamps(self) -> u8 {
+        match self {
+            DriveStrength::Ma2 => 2,
+            DriveStrength::Ma4 => 4,
+            DriveStrength::Ma6 => 6,
+            DriveStrength::Ma8 => 8,
+            DriveStrength::Ma10 => 10,
+            DriveStrength::Ma12 => 12,
+            DriveStrength::Ma14 => 14,
+            DriveStrength::Ma16 => 16,
+        }
+    }
+}
+
+impl PinDrive {
+    // The level the line is expected to be at, assuming a floating line is pulled up
+    fn level(self) -> Level {
+        match self {
+            PinDrive::Low => Level::Low,
+            PinDrive::High | PinDrive::Float => Level::High,
+        }
+    }
 }
+
 #[macro_export]
 macro_rules! impl_io_device {
     () => {
@@ -42,14 +144,38 @@ macro_rules! impl_io_device {
 
 macro_rules! impl_output_device {
     () => {
-        /// Set the state for active_high
-        pub fn set_active_high(&mut self, value: bool) {
+        fn value_to_drive(&self, value: bool) -> PinDrive {
             if value {
-                self.active_state = true;
-                self.inactive_state = false;
+                self.active_drive
             } else {
-                self.active_state = false;
-                self.inactive_state = true;
+                self.inactive_drive
+            }
+        }
+
+        /// Returns ``True`` if the device was last turned on and ``False`` otherwise.
+        /// This is the commanded state, so it doesn't read the pin.
+        pub fn value(&self) -> bool {
+            self.value.load(Ordering::SeqCst)
+        }
+
+        /// Read the level of the pin and return ``True`` if it is at the active level.
+        /// Unlike `value`, this reflects the physical state of the line, e.g. an open-drain
+        /// output that is released but held low by another device reads ``False``.
+        pub fn read_pin(&self) -> bool {
+            self.pin.read() == self.active_drive.level()
+        }
+
+        /// Set the state for active_high.
+        /// This swaps the active and inactive drives when the polarity changes, so an open-drain
+        /// device stays open-drain.
+        pub fn set_active_high(&mut self, value: bool) {
+            if value != self.active_high() {
+                // the pin is unchanged, so its logical value is inverted
+                let active = !self.value.fetch_xor(true, Ordering::SeqCst);
+                self.track_active(active);
+                self.driven = !self.driven;
+                self.pending = self.pending.map(|pending| !pending);
+                std::mem::swap(&mut self.active_drive, &mut self.inactive_drive);
             }
         }
         /// When ``True``, the `value` property is ``True`` when the device's
@@ -58,7 +184,7 @@ macro_rules! impl_output_device {
         /// Be warned that changing it will invert `value` (i.e. changing this property doesn't change
         /// the device's pin state - it just changes how that state is interpreted).
         pub fn active_high(&self) -> bool {
-            self.active_state
+            self.active_drive != PinDrive::Low
         }
 
         /// Turns the device on.
@@ -70,21 +196,156 @@ macro_rules! impl_output_device {
         pub fn off(&mut self) {
             self.write_state(false)
         }
+
+        /// Write `value` once as if the device's active_high setting were `active_high`, without
+        /// changing the setting, e.g. to drive a single inverted pulse from a test routine.
+        /// `value` afterwards reports the state of the pin under the device's own setting.
+        /// * `value` - ``True`` to drive the pin to the active level for `active_high`
+        /// * `active_high` - The polarity to use for this write only
+        pub fn write_state_with(&mut self, value: bool, active_high: bool) {
+            self.write_state(value == (active_high == self.active_high()))
+        }
         /// Reverse the state of the device. If it's on, turn it off; if it's off, turn it on.
+        /// The new state is taken from the cached value rather than the pin, so toggles made
+        /// through a shared device (e.g. from several threads holding a `DigitalOutputDevice`'s lock
+        /// in turn) never both read the same state and cancel out.
         pub fn toggle(&mut self) {
-            if self.is_active() {
-                self.off()
-            } else {
-                self.on()
-            }
+            let value = !self.value.fetch_xor(true, Ordering::SeqCst);
+            self.write_state(value)
         }
         fn write_state(&mut self, value: bool) {
-            if self.value_to_state(value) {
-                self.pin.set_high()
+            self.value.store(value, Ordering::SeqCst);
+            self.track_active(value);
+            self.pending = Some(value);
+            self.flush_pending();
+        }
+
+        // Drive the pin to the last value written, unless that would change it sooner than
+        // `min_toggle_interval` after the last change. Returns how long until a deferred write
+        // can be applied, or `None` if there is nothing left to apply.
+        fn flush_pending(&mut self) -> Option<Duration> {
+            let value = self.pending?;
+            if value != self.driven {
+                if let Some(last_change) = self.last_change {
+                    let elapsed = last_change.elapsed();
+                    if elapsed < self.min_toggle_interval {
+                        return Some(self.min_toggle_interval - elapsed);
+                    }
+                }
+                self.last_change = Some(Instant::now());
+            }
+            self.pending = None;
+            self.driven = value;
+            self.drive(self.value_to_drive(value));
+            None
+        }
+
+        // Set the minimum time between changes of the pin, applying any deferred write
+        // straight away if the interval is now zero
+        fn set_min_toggle_interval(&mut self, interval: Duration) {
+            self.min_toggle_interval = interval;
+            self.flush_pending();
+        }
+
+        fn drive(&mut self, drive: PinDrive) {
+            match drive {
+                PinDrive::Float => self.pin.set_mode(Mode::Input),
+                PinDrive::High | PinDrive::Low => {
+                    // set the level before switching to an output so a released pin doesn't glitch
+                    self.pin.write(drive.level());
+                    if self.pin.mode() != Mode::Output {
+                        self.pin.set_mode(Mode::Output)
+                    }
+                }
+            }
+        }
+
+        /// Set what the device does with its pin when it is active and when it is inactive.
+        /// The pin is updated straight away to match the current value.
+        /// This generalizes `set_active_high` and `set_drive_mode`, e.g. an open-drain
+        /// active-low output is `set_drives(PinDrive::Low, PinDrive::Float)`.
+        /// * `active` - The drive used while the device is on
+        /// * `inactive` - The drive used while the device is off
+        pub fn set_drives(&mut self, active: PinDrive, inactive: PinDrive) {
+            self.active_drive = active;
+            self.inactive_drive = inactive;
+            self.drive(self.value_to_drive(self.driven));
+        }
+
+        /// Returns the drives used while the device is active and inactive, in that order.
+        pub fn drives(&self) -> (PinDrive, PinDrive) {
+            (self.active_drive, self.inactive_drive)
+        }
+
+        /// Set how the device drives its pin. With `DriveMode::OpenDrain` the pin is released
+        /// (switched to an input) instead of being driven high.
+        pub fn set_drive_mode(&mut self, mode: DriveMode) {
+            let (from, to) = match mode {
+                DriveMode::PushPull => (PinDrive::Float, PinDrive::High),
+                DriveMode::OpenDrain => (PinDrive::High, PinDrive::Float),
+            };
+            let swap = |drive: PinDrive| if drive == from { to } else { drive };
+            self.set_drives(swap(self.active_drive), swap(self.inactive_drive));
+        }
+
+        /// Returns how the device drives its pin.
+        /// This is `DriveMode::OpenDrain` if either state leaves the pin floating.
+        pub fn drive_mode(&self) -> DriveMode {
+            if self.active_drive == PinDrive::Float || self.inactive_drive == PinDrive::Float {
+                DriveMode::OpenDrain
             } else {
-                self.pin.set_low()
+                DriveMode::PushPull
+            }
+        }
+
+        /// Set the drive strength of the device's pin, where the platform supports it.
+        /// The Raspberry Pi sets drive strength with its pad control registers, which rppal
+        /// doesn't give access to, so this currently returns `GpioZeroError::Unsupported` and
+        /// leaves the pin at the default of 8mA. A series resistor on the line is the usual
+        /// alternative for taming ringing on long wires.
+        pub fn set_drive_strength(&mut self, strength: DriveStrength) -> Result<(), GpioZeroError> {
+            Err(GpioZeroError::Unsupported(format!(
+                "setting the drive strength of GPIO {} to {}mA: pad control isn't available through rppal",
+                self.pin.pin(),
+                strength.milliamps()
+            )))
+        }
+
+        // Accumulate the time spent active whenever the device turns off
+        fn track_active(&mut self, active: bool) {
+            match (active, self.active_since) {
+                (true, None) => self.active_since = Some(Instant::now()),
+                (false, Some(since)) => {
+                    self.active_duration += since.elapsed();
+                    self.active_since = None;
+                }
+                _ => {}
             }
         }
+
+        /// Returns the total time the device has been active for, including the current
+        /// activation if it is on now.
+        pub fn active_duration(&self) -> Duration {
+            self.active_duration
+                + self
+                    .active_since
+                    .map_or(Duration::from_secs(0), |since| since.elapsed())
+        }
+
+        /// Reset the total time the device has been active for to zero.
+        pub fn reset_active_duration(&mut self) {
+            self.active_duration = Duration::from_secs(0);
+            if self.active_since.is_some() {
+                self.active_since = Some(Instant::now());
+            }
+        }
+
+        /// Returns the current mode of the underlying pin.
+        /// With `DriveMode::OpenDrain` this is `Mode::Input` while the pin is released and
+        /// `Mode::Output` while it is driven low.
+        pub fn mode(&self) -> Mode {
+            self.pin.mode()
+        }
     };
 }
 
@@ -95,23 +356,123 @@ impl OutputDevice {
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
     pub fn new(pin: u8) -> OutputDevice {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => OutputDevice {
-                    pin: pin.into_io(Mode::Output),
-                    active_state: true,
-                    inactive_state: false,
-                },
-            },
+        OutputDevice::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an OutputDevice with the pin number given, or a `GpioZeroError` if the pin can't be
+    /// accessed, e.g. because it is already in use or the program lacks permission
+    pub fn try_new(pin: u8) -> Result<OutputDevice, GpioZeroError> {
+        OutputDevice::claim(pin, "OutputDevice")
+    }
+
+    /// Returns an OutputDevice with the line number given on the GPIO character device at
+    /// `chip_path`, e.g. `/dev/gpiochip1` for the pins of an expander or a Compute Module's
+    /// second bank. Use `pins::with_pin_factory` with a `GpiodPinFactory` to put other devices
+    /// on the chip.
+    /// * `chip_path` - The path of the GPIO chip
+    /// * `pin` - The line on the chip which the device is attached to
+    pub fn new_on_chip(chip_path: &str, pin: u8) -> OutputDevice {
+        OutputDevice::try_new_on_chip(chip_path, pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an OutputDevice like `new_on_chip`, or a `GpioZeroError` if the chip can't be
+    /// opened or the line can't be requested
+    pub fn try_new_on_chip(chip_path: &str, pin: u8) -> Result<OutputDevice, GpioZeroError> {
+        let factory = GpiodPinFactory::open(chip_path)?;
+        with_pin_factory(Arc::new(factory), || OutputDevice::try_new(pin))
+    }
+
+    // Returns an OutputDevice like `try_new`, reserving the pin for a device of the type `owner`
+    // so that a clash names the device the user created rather than the OutputDevice inside it
+    pub(crate) fn claim(pin: u8, owner: &'static str) -> Result<OutputDevice, GpioZeroError> {
+        let (reservation, pin) = claim_io(pin, owner, Mode::Output)?;
+        Ok(OutputDevice::with_pin(pin, Some(reservation)))
+    }
+
+    // Returns an OutputDevice driving `pin`, which is expected to be an output driven low
+    pub(crate) fn with_pin(pin: Box<dyn IoPin>, reservation: Option<Reservation>) -> OutputDevice {
+        OutputDevice {
+            pin,
+            active_drive: PinDrive::High,
+            inactive_drive: PinDrive::Low,
+            value: AtomicBool::new(false),
+            active_since: None,
+            active_duration: Duration::from_secs(0),
+            driven: false,
+            pending: None,
+            min_toggle_interval: Duration::from_secs(0),
+            last_change: None,
+            _reservation: reservation,
         }
     }
 
+    impl_pin_numbering!();
+
     impl_device!();
     impl_gpio_device!();
-    impl_io_device!();
     impl_output_device!();
+
+    /// Drive the pin through each level for exactly the given duration, in order.
+    /// The levels are physical levels, so they are not affected by `active_high`.
+    /// This busy waits between transitions to get sub-millisecond precision, which makes
+    /// it suitable for bit-banging simple single-wire protocols. The pin is left at the last level.
+    /// The pin is driven as set with `set_drives`, so e.g. an open-drain pin is released for
+    /// a high level rather than driven high.
+    /// * `sequence` - The levels to drive the pin to and how long to hold each one
+    pub fn write_timed_sequence(&mut self, sequence: &[(Level, Duration)]) {
+        let mut deadline = Instant::now();
+        for (level, duration) in sequence {
+            let drive = [self.active_drive, self.inactive_drive]
+                .iter()
+                .copied()
+                .find(|drive| drive.level() == *level)
+                .unwrap_or(match level {
+                    Level::High => PinDrive::High,
+                    Level::Low => PinDrive::Low,
+                });
+            self.drive(drive);
+            deadline += *duration;
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+        if let Some((level, _)) = sequence.last() {
+            let active = *level == self.active_drive.level();
+            self.value.store(active, Ordering::SeqCst);
+            self.track_active(active);
+            self.driven = active;
+            self.pending = None;
+        }
+    }
+
+    // Stop any PWM on the pin and leave it at 0% duty, i.e. low if `active_high` and high otherwise
+    fn release_pwm(&mut self, active_high: bool) {
+        if self.pin.clear_pwm().is_err() {
+            println!("Could not clear pwm for pin");
+        };
+        if active_high {
+            self.pin.set_low();
+        } else {
+            self.pin.set_high();
+        }
+    }
+
+    /// Stop any PWM on the pin and drive it to its inactive state.
+    #[cfg(feature = "signal")]
+    pub(crate) fn shutdown(&mut self) {
+        let _ = self.pin.clear_pwm();
+        self.off();
+    }
+}
+
+impl Drop for OutputDevice {
+    // Leave the pin inactive, dropping any deferred write, before the pin itself is released
+    fn drop(&mut self) {
+        let _ = self.pin.clear_pwm();
+        self.pending = None;
+        let inactive = self.inactive_drive;
+        self.drive(inactive);
+    }
 }
 
 /// Represents a generic output device with typical on/off behaviour.
@@ -121,49 +482,561 @@ impl OutputDevice {
 pub struct DigitalOutputDevice {
     device: Arc<Mutex<OutputDevice>>,
     blinking: Arc<AtomicBool>,
-    handle: Option<JoinHandle<()>>,
+    handle: ThreadGuard,
     blink_count: Option<i32>,
+    remaining_blinks: Arc<AtomicI32>,
+    timing: TimingStrategy,
+    sync: Option<Arc<Mutex<Receiver<()>>>>,
+    sync_fallback: bool,
+    on_cycle: Option<CycleCallback>,
+    next_blink: NextBlink,
+    overridden: Arc<Mutex<Override>>,
+    tick_generation: Arc<AtomicU64>,
+    // applies the writes deferred by a minimum toggle interval while it is non-zero
+    deferred_writes: ThreadGuard,
+    tags: Tags,
+}
+
+type CycleFn = Box<dyn Fn(u32) + Send>;
+
+// A function called with the index of each cycle of a digital blink
+#[derive(Clone)]
+struct CycleCallback(Arc<Mutex<CycleFn>>);
+
+impl fmt::Debug for CycleCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CycleCallback")
+    }
+}
+
+/// How long a blink synced with `sync_to` waits for a pulse at a time before checking if it has been stopped
+const SYNC_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+// A `Clock` which ends each step of a blink when a pulse is received, for blinks synced with
+// `sync_to`. With `fallback`, a step also ends once its own duration has passed without a pulse.
+// If the sender is dropped, steps go back to being timed by `clock`.
+struct SyncClock<'a, C: Clock> {
+    clock: C,
+    pulses: &'a Mutex<Receiver<()>>,
+    fallback: bool,
+    running: &'a AtomicBool,
+}
+
+impl<'a, C: Clock> Clock for SyncClock<'a, C> {
+    fn sleep(&mut self, duration: Duration) {
+        let pulses = self.pulses.lock().unwrap_or_else(PoisonError::into_inner);
+        let deadline = Instant::now() + duration;
+        while self.running.load(Ordering::SeqCst) {
+            let wait = if self.fallback {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining == Duration::from_secs(0) {
+                    return;
+                }
+                remaining.min(SYNC_CHECK_INTERVAL)
+            } else {
+                SYNC_CHECK_INTERVAL
+            };
+            match pulses.recv_timeout(wait) {
+                Ok(()) => return,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.clock
+                        .sleep(deadline.saturating_duration_since(Instant::now()));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// The state a digital device is forced into with `force_on` or `force_off`, and the state
+// to go back to when the override is released
+#[derive(Debug, Default)]
+struct Override {
+    forced: Option<bool>,
+    resume: bool,
+}
+
+// Writes blink steps to a digital device, turning it on for any value above 0.0,
+// and calls the `on_cycle` function at the start of each pass through the sequence.
+// While the device is overridden the steps are only recorded, so the blink keeps time.
+struct DigitalSink {
+    device: Arc<Mutex<OutputDevice>>,
+    overridden: Arc<Mutex<Override>>,
+    on_cycle: Option<CycleCallback>,
+    len: usize,
+    step: usize,
+    cycle: u32,
+}
+
+impl DigitalSink {
+    fn new(
+        device: &Arc<Mutex<OutputDevice>>,
+        overridden: &Arc<Mutex<Override>>,
+        on_cycle: Option<CycleCallback>,
+        len: usize,
+    ) -> Self {
+        DigitalSink {
+            device: Arc::clone(device),
+            overridden: Arc::clone(overridden),
+            on_cycle,
+            len,
+            step: 0,
+            cycle: 0,
+        }
+    }
+
+    // Start counting steps again for a new sequence of `len` steps, carrying on the cycle count
+    fn restart(&mut self, len: usize) {
+        self.len = len;
+        self.step = 0;
+    }
+}
+
+impl Sink for DigitalSink {
+    fn write(&mut self, value: f64) {
+        {
+            let mut overridden = self.overridden.lock().unwrap();
+            overridden.resume = value > 0.0;
+            if overridden.forced.is_none() {
+                if value > 0.0 {
+                    self.device.lock().unwrap().on()
+                } else {
+                    self.device.lock().unwrap().off()
+                }
+            }
+        }
+        if self.step == 0 {
+            if let Some(on_cycle) = &self.on_cycle {
+                (on_cycle.0.lock().unwrap())(self.cycle);
+            }
+            self.cycle += 1;
+        }
+        self.step = (self.step + 1) % self.len;
+    }
+}
+
+// Writes blink steps to a PWM device as its duty cycle
+//
+// The background thread writes through a sink holding the device's lock for the whole blink, rather
+// than locking the device for every step, so that many devices fading at once don't contend on their
+// locks 25 times a second. The thread sleeps with a `StoppableClock`, so it notices `stop` within 10ms
+// and releases the lock. The trade-off is that a `set_value` (or anything else that needs the pin)
+// made during a fade waits up to 10ms, and that `on_step` and `when_crosses` functions must not
+// control the same device.
+struct PwmSink<D> {
+    device: D,
+    on_step: Option<StepCallback>,
+    crossings: Vec<Crossing>,
+    gamma: f64,
+    active_high: bool,
+    frequency: f64,
+    running: Arc<AtomicBool>,
+    last: Option<f64>,
+    // where the last value written is published, for a device to report as its value
+    position: Option<Arc<AtomicU64>>,
+}
+
+impl PwmSink<Arc<Mutex<OutputDevice>>> {
+    // Take the device's lock, returning a sink which can be written to until it is dropped,
+    // carrying on from the last value written
+    fn lock(&self) -> PwmSink<MutexGuard<'_, OutputDevice>> {
+        PwmSink {
+            device: lock_device(&self.device),
+            on_step: self.on_step.clone(),
+            crossings: self.crossings.clone(),
+            gamma: self.gamma,
+            active_high: self.active_high,
+            frequency: self.frequency,
+            running: Arc::clone(&self.running),
+            last: self.last,
+            position: self.position.clone(),
+        }
+    }
+}
+
+// Written to by a TickDriver, locking the device for each step
+struct TickedPwmSink(PwmSink<Arc<Mutex<OutputDevice>>>);
+
+impl Sink for TickedPwmSink {
+    fn write(&mut self, value: f64) {
+        let last = {
+            let mut sink = self.0.lock();
+            sink.write(value);
+            sink.last
+        };
+        self.0.last = last;
+    }
+}
+
+impl<D: DerefMut<Target = OutputDevice>> Sink for PwmSink<D> {
+    fn write(&mut self, value: f64) {
+        let duty = value.powf(self.gamma);
+        let duty = if self.active_high { duty } else { 1.0 - duty };
+        // give up on the blink rather than panic, so the device isn't left stuck part way through a fade
+        if let Err(e) = with_retry(retry_attempts(), RETRY_DELAY, || {
+            self.device.pin.set_pwm_frequency(self.frequency, duty)
+        }) {
+            println!("Could not set pwm for pin, stopping: {}", e);
+            self.running.store(false, Ordering::SeqCst);
+            self.device.release_pwm(self.active_high);
+            return;
+        }
+        if let Some(position) = &self.position {
+            position.store(value.to_bits(), Ordering::SeqCst);
+        }
+        if let Some(on_step) = &self.on_step {
+            (on_step.lock().unwrap())(value);
+        }
+        if let Some(last) = self.last {
+            for crossing in &self.crossings {
+                let crossed = if crossing.rising {
+                    last < crossing.level && value >= crossing.level
+                } else {
+                    last > crossing.level && value <= crossing.level
+                };
+                if crossed {
+                    (crossing.action.lock().unwrap())();
+                }
+            }
+        }
+        self.last = Some(value);
+    }
+}
+
+// A function registered with `when_crosses`, called when the duty cycle passes `level`
+#[derive(Clone)]
+struct Crossing {
+    level: f64,
+    rising: bool,
+    action: Arc<Mutex<Box<dyn Fn() + Send>>>,
+}
+
+// Lock a shared device for reading, even if a background thread panicked while holding it,
+// so that diagnostics such as `value` and `pin` keep working after the panic
+fn lock_device(device: &Mutex<OutputDevice>) -> MutexGuard<'_, OutputDevice> {
+    device.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Lock a servo's pin, even if a detach timer panicked while holding it
+fn lock_pin(pin: &Mutex<Box<dyn IoPin>>) -> MutexGuard<'_, Box<dyn IoPin>> {
+    pin.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// A negative count is stored while a device blinks continuously
+fn remaining_blinks(count: &AtomicI32) -> Option<i32> {
+    let count = count.load(Ordering::SeqCst);
+    if count < 0 {
+        None
+    } else {
+        Some(count)
+    }
+}
+
+/// How often writes deferred by a minimum toggle interval are checked
+const DEFERRED_WRITE_POLL: Duration = Duration::from_millis(10);
+
+// Apply the writes deferred by a device's minimum toggle interval once it has passed,
+// until `running` is cleared when the interval is set back to zero, or the device is dropped
+fn apply_deferred_writes(device: Weak<Mutex<OutputDevice>>, running: &AtomicBool) {
+    let mut clock = StoppableClock::new(TimingStrategy::Sleep, running);
+    while running.load(Ordering::SeqCst) {
+        let delay = match device.upgrade() {
+            None => return,
+            Some(device) => lock_device(&device).flush_pending(),
+        };
+        clock.sleep(delay.map_or(DEFERRED_WRITE_POLL, |delay| delay.min(DEFERRED_WRITE_POLL)));
+    }
+}
+
+type Condition = Box<dyn Fn() -> bool + Send>;
+
+// A blink sequence and the number of times to play it
+type Blink = (Vec<(f32, f32)>, Option<i32>);
+
+type NextBlink = Arc<Mutex<Option<Blink>>>;
+
+// The blinks played by a digital device's background thread, which can be replaced by `reblink`
+// at the end of any blink
+struct Blinks<'a> {
+    sequence: Vec<(f32, f32)>,
+    n: Option<i32>,
+    condition: Option<Condition>,
+    running: &'a AtomicBool,
+    remaining_blinks: &'a AtomicI32,
+    next: &'a Mutex<Option<Blink>>,
+}
+
+impl<'a> Blinks<'a> {
+    // Play the blinks, returning ``True`` if they finished and ``False`` if they were stopped
+    fn play<C: Clock>(&mut self, clock: &mut C, sink: &mut DigitalSink) -> bool {
+        let mut count = 0;
+        // each blink is played as a bounded blink of one, which counts down its own remaining blinks
+        let blink = AtomicI32::new(0);
+        loop {
+            {
+                let mut next = self.next.lock().unwrap();
+                if let Some((sequence, n)) = next.take() {
+                    sink.restart(sequence.len());
+                    self.sequence = sequence;
+                    self.n = n;
+                    self.condition = None;
+                    self.remaining_blinks
+                        .store(n.unwrap_or(-1), Ordering::SeqCst);
+                    count = 0;
+                }
+                let done = matches!(self.n, Some(end) if count >= end)
+                    || self
+                        .condition
+                        .as_ref()
+                        .is_some_and(|condition| !condition());
+                if done {
+                    if self.n.is_some() {
+                        self.remaining_blinks.store(0, Ordering::SeqCst);
+                    }
+                    self.running.store(false, Ordering::SeqCst);
+                    return true;
+                }
+            }
+            if !play(clock, sink, &self.sequence, Some(1), self.running, &blink) {
+                return false;
+            }
+            count += 1;
+            if self.n.is_some() {
+                self.remaining_blinks.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
 }
 
 macro_rules! impl_digital_output_device {
     () => {
-        fn blinker(&mut self, on_time: f32, off_time: f32, n: Option<i32>) {
+        fn blinker(
+            &mut self,
+            on_time: f32,
+            off_time: f32,
+            n: Option<i32>,
+            limit: Option<Duration>,
+        ) {
+            self.play_sequence(vec![(1.0, on_time), (0.0, off_time)], n, limit, None)
+        }
+
+        /// Make the device turn on and off repeatedly, advanced by `driver` rather than a
+        /// background thread. Nothing happens until `driver.tick` is called.
+        /// * `driver` - The TickDriver to register the blink with
+        /// * `on_time` - Number of seconds on
+        /// * `off_time` - Number of seconds off
+        /// * `n` - Number of times to blink, or `None` to blink until stopped
+        pub fn blink_ticked(
+            &mut self,
+            driver: &mut TickDriver,
+            on_time: f32,
+            off_time: f32,
+            n: Option<i32>,
+        ) {
+            self.stop();
+            // a count of zero means don't blink at all, leaving the device off
+            if matches!(n, Some(n) if n <= 0) {
+                self.remaining_blinks.store(0, Ordering::SeqCst);
+                return;
+            }
+            *self.next_blink.lock().unwrap() = None;
+            self.remaining_blinks.store(n.unwrap_or(-1), Ordering::SeqCst);
+            self.blinking.store(true, Ordering::SeqCst);
+            let sink = DigitalSink::new(&self.device, &self.overridden, self.on_cycle.clone(), 2);
+            driver.register(
+                sink,
+                vec![(1.0, on_time), (0.0, off_time)],
+                n,
+                &self.tick_generation,
+                &self.blinking,
+                &self.remaining_blinks,
+            );
+        }
+
+        fn play_sequence(
+            &mut self,
+            sequence: Vec<(f32, f32)>,
+            n: Option<i32>,
+            limit: Option<Duration>,
+            condition: Option<Condition>,
+        ) {
             self.stop();
+            check_intervals(&sequence, self.timing);
+            // a count of zero means don't blink at all, leaving the device off
+            if matches!(n, Some(n) if n <= 0) {
+                self.remaining_blinks.store(0, Ordering::SeqCst);
+                return;
+            }
 
             let device = Arc::clone(&self.device);
             let blinking = Arc::clone(&self.blinking);
+            let remaining_blinks = Arc::clone(&self.remaining_blinks);
+            let next_blink = Arc::clone(&self.next_blink);
+            let overridden = Arc::clone(&self.overridden);
+            let sync = self.sync.clone();
+            let sync_fallback = self.sync_fallback;
+            let timing = self.timing;
 
-            self.handle = Some(thread::spawn(move || {
-                blinking.store(true, Ordering::SeqCst);
-                match n {
-                    Some(end) => {
-                        for _ in 0..end {
-                            if !blinking.load(Ordering::SeqCst) {
-                                device.lock().unwrap().off();
-                                break;
-                            }
-                            device.lock().unwrap().on();
-                            thread::sleep(Duration::from_millis((on_time * 1000.0) as u64));
-                            device.lock().unwrap().off();
-                            thread::sleep(Duration::from_millis((off_time * 1000.0) as u64));
-                        }
+            *next_blink.lock().unwrap() = None;
+            remaining_blinks.store(n.unwrap_or(-1), Ordering::SeqCst);
+            blinking.store(true, Ordering::SeqCst);
+            let on_cycle = self.on_cycle.clone();
+            self.handle.spawn(move || {
+                let mut sink = DigitalSink::new(&device, &overridden, on_cycle, sequence.len());
+                let mut blinks = Blinks {
+                    sequence,
+                    n,
+                    condition,
+                    running: &blinking,
+                    remaining_blinks: &remaining_blinks,
+                    next: &next_blink,
+                };
+                // sleep in short slices, so that `stop` doesn't wait out a long step
+                let mut clock = StoppableClock::new(timing, &blinking);
+                let finished = match (&sync, limit) {
+                    (None, None) => blinks.play(&mut clock, &mut sink),
+                    (None, Some(total)) => {
+                        blinks.play(&mut LimitedClock::new(clock, total, &blinking), &mut sink)
                     }
-                    None => loop {
-                        if !blinking.load(Ordering::SeqCst) {
-                            device.lock().unwrap().off();
-                            break;
+                    (Some(pulses), limit) => {
+                        let mut clock = SyncClock {
+                            clock,
+                            pulses,
+                            fallback: sync_fallback,
+                            running: &blinking,
+                        };
+                        match limit {
+                            None => blinks.play(&mut clock, &mut sink),
+                            Some(total) => blinks.play(
+                                &mut LimitedClock::new(clock, total, &blinking),
+                                &mut sink,
+                            ),
                         }
-                        device.lock().unwrap().on();
-                        thread::sleep(Duration::from_millis((on_time * 1000.0) as u64));
-                        device.lock().unwrap().off();
-                        thread::sleep(Duration::from_millis((off_time * 1000.0) as u64));
-                    },
+                    }
+                };
+                if !finished {
+                    device.lock().unwrap().off();
+                }
+            });
+        }
+
+        /// Make the device turn on and off repeatedly in the background for as long as `condition`
+        /// returns ``True``. The condition is checked before each blink, so the device finishes
+        /// the blink it is in and then turns off once the condition no longer holds.
+        /// * `on_time` - Number of seconds on
+        /// * `off_time` - Number of seconds off
+        /// * `condition` - The function deciding whether to blink again, called from the background thread
+        pub fn blink_while<C>(&mut self, on_time: f32, off_time: f32, condition: C)
+        where
+            C: Fn() -> bool + Send + 'static,
+        {
+            self.play_sequence(
+                vec![(1.0, on_time), (0.0, off_time)],
+                None,
+                None,
+                Some(Box::new(condition)),
+            )
+        }
+
+        /// Change the blink to a new one at the end of the current blink, without turning the
+        /// device off in between as `stop` followed by `blink` would. This suits status indicators
+        /// that change their blink rate to show a new state. If the device isn't blinking,
+        /// this starts blinking straight away.
+        /// * `on_time` - Number of seconds on
+        /// * `off_time` - Number of seconds off
+        /// * `n` - Number of times to blink, or `None` to blink until stopped
+        pub fn reblink(&mut self, on_time: f32, off_time: f32, n: Option<i32>) {
+            {
+                // the lock is held while checking, so the blink can't finish before it sees the change
+                let mut next_blink = self.next_blink.lock().unwrap();
+                if self.is_blinking() {
+                    *next_blink = Some((vec![(1.0, on_time), (0.0, off_time)], n));
+                    return;
                 }
-            }));
+            }
+            self.blinker(on_time, off_time, n, None)
         }
+
         /// Returns ``True`` if the device is currently active and ``False`` otherwise.
+        /// A device that is blinking is considered active for the whole blink,
+        /// regardless of whether it is in its on or off phase.
         pub fn is_active(&self) -> bool {
-            Arc::clone(&self.device).lock().unwrap().is_active()
+            self.is_blinking() || lock_device(&self.device).is_active()
+        }
+
+        /// Returns ``True`` if the device is currently blinking in the background and ``False`` otherwise.
+        pub fn is_blinking(&self) -> bool {
+            self.blinking.load(Ordering::SeqCst)
+        }
+
+        /// Play one of the ready-made blink effects in the background until `stop` is called.
+        /// The device is on wherever the preset's brightness is at least half.
+        /// * `preset` - The effect to play
+        pub fn play_preset(&mut self, preset: BlinkPreset) {
+            self.play_sequence(preset.digital_sequence(), None, None, None)
+        }
+
+        /// Returns the number of blinks left in a bounded blink, or `None` if the device is blinking continuously.
+        pub fn remaining_blinks(&self) -> Option<i32> {
+            remaining_blinks(&self.remaining_blinks)
+        }
+
+        /// Register a function to be called with the index of each blink, counting from 0, as the
+        /// device turns on at its start. This makes it possible to follow the progress of a blink,
+        /// e.g. in tests or for telemetry. It replaces any function registered before and takes
+        /// effect from the next blink.
+        /// * `f` - The function to call from the background thread
+        pub fn on_cycle<F>(&mut self, f: F)
+        where
+            F: Fn(u32) + Send + 'static,
+        {
+            self.on_cycle = Some(CycleCallback(Arc::new(Mutex::new(Box::new(f)))));
+        }
+
+        /// Remove the function registered with `on_cycle`.
+        pub fn clear_on_cycle(&mut self) {
+            self.on_cycle = None;
+        }
+
+        /// Sync blinks to an external clock, such as a beat detector or a master clock elsewhere in
+        /// the program: each step of a blink, on or off, lasts until a pulse is received on `pulses`
+        /// rather than for its own time. Takes effect from the next blink. If the sender is dropped,
+        /// blinks go back to their own timing.
+        /// * `pulses` - The channel the pulses are sent on
+        pub fn sync_to(&mut self, pulses: Receiver<()>) {
+            self.sync = Some(Arc::new(Mutex::new(pulses)));
+        }
+
+        /// Stop syncing blinks to the channel given to `sync_to`, from the next blink.
+        pub fn unsync(&mut self) {
+            self.sync = None;
+        }
+
+        /// Set whether a blink synced with `sync_to` falls back to its own timing while no pulses
+        /// arrive. When ``True``, a step ends on a pulse or once its own time has passed, whichever
+        /// comes first; when ``False``, the default, a step lasts until the next pulse however long
+        /// that takes. Takes effect from the next blink.
+        pub fn set_sync_fallback(&mut self, fallback: bool) {
+            self.sync_fallback = fallback;
+        }
+
+        /// Set how the device waits between the steps of a blink, which takes effect from the next blink.
+        /// `TimingStrategy::Spin` gives the most accurate short intervals, e.g. for fast strobes,
+        /// at the cost of keeping a CPU core busy for the whole blink.
+        pub fn set_timing_strategy(&mut self, strategy: TimingStrategy) {
+            self.timing = strategy;
+        }
+
+        /// Returns how the device waits between the steps of a blink.
+        pub fn timing_strategy(&self) -> TimingStrategy {
+            self.timing
+        }
+
+        /// Returns the shortest on or off time the device's timing strategy can reliably blink
+        /// with. Shorter times are still attempted, with a warning.
+        pub fn min_blink_interval(&self) -> Duration {
+            self.timing.min_interval()
         }
         /// Turns the device on.
         pub fn on(&self) {
@@ -177,66 +1050,230 @@ macro_rules! impl_digital_output_device {
         }
 
         /// Reverse the state of the device. If it's on, turn it off; if it's off, turn it on.
-        pub fn toggle(&mut self) {
+        /// The state is read and written under a single lock, so the device can be toggled
+        /// from several threads through a shared reference.
+        pub fn toggle(&self) {
             self.device.lock().unwrap().toggle()
         }
 
         /// Returns ``True`` if the device is currently active and ``False`` otherwise.
         pub fn value(&self) -> bool {
-            self.device.lock().unwrap().value()
+            lock_device(&self.device).value()
         }
 
-        fn stop(&self) {
-            self.blinking.clone().store(false, Ordering::SeqCst);
-            self.device.lock().unwrap().pin.set_low();
+        /// Set the minimum time between changes of the pin, e.g. to stop a relay from chattering
+        /// and wearing out when it is switched rapidly. A write that would change the pin sooner
+        /// is deferred and applied in the background once the interval has passed, unless another
+        /// write supersedes it first. `value` reports the last value written, even while it is deferred.
+        /// * `interval` - The minimum time between changes, or zero to apply every write straight away
+        pub fn set_min_toggle_interval(&mut self, interval: Duration) {
+            lock_device(&self.device).set_min_toggle_interval(interval);
+            if interval == Duration::from_secs(0) {
+                self.deferred_writes.stop();
+            } else if !self.deferred_writes.running().load(Ordering::SeqCst) {
+                let device = Arc::downgrade(&self.device);
+                let running = self.deferred_writes.running();
+                self.deferred_writes
+                    .spawn(move || apply_deferred_writes(device, &running));
+            }
         }
 
-        /// When ``True``, the `value` property is ``True`` when the device's
-        /// `pin` is high. When ``False`` the `value` property is
-        /// ``True`` when the device's pin is low (i.e. the value is inverted).
-        /// Be warned that changing it will invert `value` (i.e. changing this property doesn't change
-        /// the device's pin state - it just changes how that state is interpreted).
-        pub fn active_high(&self) -> bool {
-            self.device.lock().unwrap().active_high()
+        /// Returns the minimum time between changes of the pin.
+        pub fn min_toggle_interval(&self) -> Duration {
+            lock_device(&self.device).min_toggle_interval
         }
 
-        /// Set the state for active_high
-        pub fn set_active_high(&mut self, value: bool) {
-            self.device.lock().unwrap().set_active_high(value)
+        /// Read the level of the pin and return ``True`` if it is at the active level.
+        pub fn read_pin(&self) -> bool {
+            lock_device(&self.device).read_pin()
         }
 
-        /// The `Pin` that the device is connected to.
-        pub fn pin(&self) -> u8 {
-            self.device.lock().unwrap().pin.pin()
+        /// Returns the total time the device has been on for, including the on phases of blinks.
+        pub fn active_duration(&self) -> Duration {
+            lock_device(&self.device).active_duration()
         }
 
-        /// Shut down the device and release all associated resources.
-        pub fn close(self) {
-            drop(self)
+        /// Reset the total time the device has been on for to zero.
+        pub fn reset_active_duration(&self) {
+            self.device.lock().unwrap().reset_active_duration()
         }
 
-        /// Block until background process is done
+        // Stop any blink, waiting for its thread to finish so that it can't write to the pin
+        // after the caller, and turn the device off
+        fn stop(&self) {
+            self.handle.stop();
+            self.tick_generation.fetch_add(1, Ordering::SeqCst);
+            let mut overridden = self
+                .overridden
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            overridden.forced = None;
+            lock_device(&self.device).off();
+        }
+
+        /// Force the device on, e.g. to light a status LED while debugging, until
+        /// `release_override` is called. Unlike `on`, this doesn't stop a blink, which carries
+        /// on in the background and takes over again when the override is released.
+        pub fn force_on(&mut self) {
+            self.force(true)
+        }
+
+        /// Force the device off until `release_override` is called. Unlike `off`, this doesn't
+        /// stop a blink, which carries on in the background and takes over again when the override is released.
+        pub fn force_off(&mut self) {
+            self.force(false)
+        }
+
+        fn force(&mut self, state: bool) {
+            let mut overridden = self.overridden.lock().unwrap();
+            let mut device = lock_device(&self.device);
+            if overridden.forced.is_none() && !self.is_blinking() {
+                overridden.resume = device.value();
+            }
+            overridden.forced = Some(state);
+            if state {
+                device.on()
+            } else {
+                device.off()
+            }
+        }
+
+        /// Release an override set with `force_on` or `force_off`, returning the device to the
+        /// state its blink is in now, or to the state it was in before the override if it isn't blinking.
+        pub fn release_override(&mut self) {
+            let mut overridden = self.overridden.lock().unwrap();
+            if overridden.forced.take().is_some() {
+                let mut device = lock_device(&self.device);
+                if overridden.resume {
+                    device.on()
+                } else {
+                    device.off()
+                }
+            }
+        }
+
+        /// Returns ``True`` if the device has been forced on or off with `force_on` or `force_off`.
+        pub fn is_overridden(&self) -> bool {
+            self.overridden.lock().unwrap().forced.is_some()
+        }
+
+        /// When ``True``, the `value` property is ``True`` when the device's
+        /// `pin` is high. When ``False`` the `value` property is
+        /// ``True`` when the device's pin is low (i.e. the value is inverted).
+        /// Be warned that changing it will invert `value` (i.e. changing this property doesn't change
+        /// the device's pin state - it just changes how that state is interpreted).
+        pub fn active_high(&self) -> bool {
+            lock_device(&self.device).active_high()
+        }
+
+        /// Set the state for active_high
+        pub fn set_active_high(&mut self, value: bool) {
+            self.device.lock().unwrap().set_active_high(value)
+        }
+
+        /// Stop any blink and write `value` once as if the device's active_high setting were
+        /// `active_high`, without changing the setting.
+        /// * `value` - ``True`` to drive the pin to the active level for `active_high`
+        /// * `active_high` - The polarity to use for this write only
+        pub fn write_state_with(&mut self, value: bool, active_high: bool) {
+            self.stop();
+            self.device
+                .lock()
+                .unwrap()
+                .write_state_with(value, active_high)
+        }
+
+        /// Set what the device does with its pin when it is active and when it is inactive.
+        /// * `active` - The drive used while the device is on
+        /// * `inactive` - The drive used while the device is off
+        pub fn set_drives(&mut self, active: PinDrive, inactive: PinDrive) {
+            lock_device(&self.device).set_drives(active, inactive)
+        }
+
+        /// Returns the drives used while the device is active and inactive, in that order.
+        pub fn drives(&self) -> (PinDrive, PinDrive) {
+            lock_device(&self.device).drives()
+        }
+
+        /// The `Pin` that the device is connected to.
+        pub fn pin(&self) -> u8 {
+            lock_device(&self.device).pin.pin()
+        }
+
+        /// Shut down the device and release all associated resources.
+        pub fn close(self) {
+            drop(self)
+        }
+
+        /// Pause the program until the device is off, including the end of a bounded blink,
+        /// or the timeout is reached. Returns ``True`` if the device turned off in time.
+        /// * `timeout` - Number of seconds to wait before giving up. If this is None, then wait indefinitely until the device is off.
+        pub fn wait_until_off(&self, timeout: Option<f32>) -> bool {
+            let start = Instant::now();
+            while self.is_active() {
+                if let Some(n) = timeout {
+                    if start.elapsed() >= Duration::from_millis((n * 1000.0) as u64) {
+                        return false;
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            true
+        }
+
+        /// Block until the blink running in the background is done.
+        /// Returns immediately if nothing is running.
         pub fn wait(&mut self) {
-            self.handle
-                .take()
-                .expect("Called stop on non-running thread")
-                .join()
-                .expect("Could not join spawned thread");
+            if let Some(handle) = self.handle.take() {
+                handle.join().expect("Could not join spawned thread");
+            }
         }
     };
 }
 
 impl DigitalOutputDevice {
     pub fn new(pin: u8) -> DigitalOutputDevice {
+        DigitalOutputDevice::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a DigitalOutputDevice with the pin number given, or a `GpioZeroError` if the pin can't be
+    /// accessed, e.g. because it is already in use or the program lacks permission
+    pub fn try_new(pin: u8) -> Result<DigitalOutputDevice, GpioZeroError> {
+        Ok(DigitalOutputDevice::from_device(OutputDevice::claim(
+            pin,
+            "DigitalOutputDevice",
+        )?))
+    }
+
+    // Returns a DigitalOutputDevice driving the pin of `device`
+    pub(crate) fn from_device(device: OutputDevice) -> DigitalOutputDevice {
+        let pin = device.pin.pin();
+        let device = Arc::new(Mutex::new(device));
+        let blinking = Arc::new(AtomicBool::new(false));
+        #[cfg(feature = "signal")]
+        crate::signal::register(&device, &blinking);
         DigitalOutputDevice {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
-            handle: None,
+            device,
+            handle: ThreadGuard::new(Arc::clone(&blinking)),
+            blinking,
             blink_count: None,
+            remaining_blinks: Arc::new(AtomicI32::new(0)),
+            timing: TimingStrategy::default(),
+            sync: None,
+            sync_fallback: false,
+            on_cycle: None,
+            next_blink: Arc::new(Mutex::new(None)),
+            overridden: Arc::new(Mutex::new(Override::default())),
+            tick_generation: Arc::new(AtomicU64::new(0)),
+            deferred_writes: ThreadGuard::new(Arc::new(AtomicBool::new(false))),
+            tags: Tags::new(pin),
         }
     }
 
+    impl_pin_numbering!();
+
     impl_digital_output_device!();
+    impl_tags!();
 
     /// Make the device turn on and off repeatedly in the background.
     /// Use `set_blink_count` to set the number of times to blink the device
@@ -245,12 +1282,22 @@ impl DigitalOutputDevice {
     ///
     pub fn blink(&mut self, on_time: f32, off_time: f32) {
         match self.blink_count {
-            None => self.blinker(on_time, off_time, None),
-            Some(n) => self.blinker(on_time, off_time, Some(n)),
+            None => self.blinker(on_time, off_time, None, None),
+            Some(n) => self.blinker(on_time, off_time, Some(n), None),
         }
     }
+    /// Make the device turn on and off repeatedly in the background, stopping after `total`
+    /// has elapsed, even in the middle of a blink.
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `total` - How long to blink for
+    ///
+    pub fn blink_for(&mut self, on_time: f32, off_time: f32, total: Duration) {
+        self.blinker(on_time, off_time, None, Some(total))
+    }
+
     /// Set the number of times to blink the device
-    /// * `n` - Number of times to blink
+    /// * `n` - Number of times to blink. With 0 or less, `blink` turns the device off instead of blinking
     pub fn set_blink_count(&mut self, n: i32) {
         self.blink_count = Some(n)
     }
@@ -271,27 +1318,117 @@ impl DigitalOutputDevice {
 pub struct LED {
     device: Arc<Mutex<OutputDevice>>,
     blinking: Arc<AtomicBool>,
-    handle: Option<JoinHandle<()>>,
+    handle: ThreadGuard,
     blink_count: Option<i32>,
+    remaining_blinks: Arc<AtomicI32>,
+    timing: TimingStrategy,
+    following: Option<Subscription>,
+    sync: Option<Arc<Mutex<Receiver<()>>>>,
+    sync_fallback: bool,
+    on_cycle: Option<CycleCallback>,
+    next_blink: NextBlink,
+    overridden: Arc<Mutex<Override>>,
+    tick_generation: Arc<AtomicU64>,
+    // applies the writes deferred by a minimum toggle interval while it is non-zero
+    deferred_writes: ThreadGuard,
+    tags: Tags,
 }
 
 impl LED {
     pub fn new(pin: u8) -> LED {
+        LED::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an LED attached to the pin number given on another Raspberry Pi, which is
+    /// controlled through the pigpio daemon running on `host`. Use `pins::with_pin_factory` with
+    /// a `RemotePinFactory` to handle connection errors, or to put other devices on a remote Pi.
+    /// * `host` - The host name or IP address of the remote Pi
+    /// * `pin` - The GPIO pin on the remote Pi which the device is attached to
+    pub fn new_remote(host: &str, pin: u8) -> LED {
+        RemotePinFactory::new(host)
+            .and_then(|factory| with_pin_factory(Arc::new(factory), || LED::try_new(pin)))
+            .unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an LED with the pin number given, or a `GpioZeroError` if the pin can't be
+    /// accessed, e.g. because it is already in use or the program lacks permission
+    pub fn try_new(pin: u8) -> Result<LED, GpioZeroError> {
+        Ok(LED::from_device(OutputDevice::claim(pin, "LED")?))
+    }
+
+    // Returns a LED driving the pin of `device`
+    pub(crate) fn from_device(device: OutputDevice) -> LED {
+        let pin = device.pin.pin();
+        let device = Arc::new(Mutex::new(device));
+        let blinking = Arc::new(AtomicBool::new(false));
+        #[cfg(feature = "signal")]
+        crate::signal::register(&device, &blinking);
         LED {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
-            handle: None,
+            device,
+            handle: ThreadGuard::new(Arc::clone(&blinking)),
+            blinking,
             blink_count: None,
+            remaining_blinks: Arc::new(AtomicI32::new(0)),
+            timing: TimingStrategy::default(),
+            following: None,
+            sync: None,
+            sync_fallback: false,
+            on_cycle: None,
+            next_blink: Arc::new(Mutex::new(None)),
+            overridden: Arc::new(Mutex::new(Override::default())),
+            tick_generation: Arc::new(AtomicU64::new(0)),
+            deferred_writes: ThreadGuard::new(Arc::new(AtomicBool::new(false))),
+            tags: Tags::new(pin),
         }
     }
 
+    impl_pin_numbering!();
+
     impl_digital_output_device!();
+    impl_tags!();
 
     /// Returns True if the device is currently active and False otherwise.
     pub fn is_lit(&self) -> bool {
         self.is_active()
     }
 
+    /// Make the LED track the state of a button, lighting while it is pressed, until `unfollow`
+    /// is called or the LED is dropped. Any blink is stopped.
+    /// The LED is updated by a handler registered on the button with `add_handler`, which runs
+    /// on the button's background interrupt thread.
+    /// * `button` - The button to follow
+    pub fn follow(&mut self, button: &mut Button) {
+        self.unfollow();
+        self.stop();
+
+        let device = Arc::clone(&self.device);
+        let blinking = Arc::clone(&self.blinking);
+        if button.is_active() {
+            lock_device(&self.device).on();
+        }
+        self.following = Some(button.subscribe(move |event| {
+            blinking.store(false, Ordering::SeqCst);
+            if event.active {
+                lock_device(&device).on();
+            } else {
+                lock_device(&device).off();
+            }
+        }));
+    }
+
+    /// Stop tracking the button given to `follow`, removing the LED's handler from the button.
+    /// The LED is left in its current state.
+    pub fn unfollow(&mut self) {
+        if let Some(following) = self.following.take() {
+            following.cancel();
+        }
+    }
+
+    /// Returns ``True`` if the LED is following a button.
+    pub fn is_following(&self) -> bool {
+        self.following.is_some()
+    }
+
     /// Make the device turn on and off repeatedly in the background.
     /// Use `set_blink_count` to set the number of times to blink the device    
     /// * `on_time` - Number of seconds on
@@ -299,12 +1436,22 @@ impl LED {
     ///
     pub fn blink(&mut self, on_time: f32, off_time: f32) {
         match self.blink_count {
-            None => self.blinker(on_time, off_time, None),
-            Some(n) => self.blinker(on_time, off_time, Some(n)),
+            None => self.blinker(on_time, off_time, None, None),
+            Some(n) => self.blinker(on_time, off_time, Some(n), None),
         }
     }
+    /// Make the device turn on and off repeatedly in the background, stopping after `total`
+    /// has elapsed, even in the middle of a blink.
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `total` - How long to blink for
+    ///
+    pub fn blink_for(&mut self, on_time: f32, off_time: f32, total: Duration) {
+        self.blinker(on_time, off_time, None, Some(total))
+    }
+
     /// Set the number of times to blink the device    
-    /// * `n` - Number of times to blink
+    /// * `n` - Number of times to blink. With 0 or less, `blink` turns the device off instead of blinking
     pub fn set_blink_count(&mut self, n: i32) {
         self.blink_count = Some(n)
     }
@@ -319,21 +1466,66 @@ impl LED {
 pub struct Buzzer {
     device: Arc<Mutex<OutputDevice>>,
     blinking: Arc<AtomicBool>,
-    handle: Option<JoinHandle<()>>,
+    handle: ThreadGuard,
     blink_count: Option<i32>,
+    remaining_blinks: Arc<AtomicI32>,
+    timing: TimingStrategy,
+    sync: Option<Arc<Mutex<Receiver<()>>>>,
+    sync_fallback: bool,
+    on_cycle: Option<CycleCallback>,
+    next_blink: NextBlink,
+    overridden: Arc<Mutex<Override>>,
+    tick_generation: Arc<AtomicU64>,
+    // applies the writes deferred by a minimum toggle interval while it is non-zero
+    deferred_writes: ThreadGuard,
+    tags: Tags,
 }
 
 impl Buzzer {
     pub fn new(pin: u8) -> Buzzer {
+        Buzzer::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a Buzzer with the pin number given, or a `GpioZeroError` if the pin can't be
+    /// accessed, e.g. because it is already in use or the program lacks permission
+    pub fn try_new(pin: u8) -> Result<Buzzer, GpioZeroError> {
+        Ok(Buzzer::from_device(OutputDevice::claim(pin, "Buzzer")?))
+    }
+
+    // Returns a Buzzer driving the pin of `device`
+    pub(crate) fn from_device(device: OutputDevice) -> Buzzer {
+        let pin = device.pin.pin();
+        let device = Arc::new(Mutex::new(device));
+        let blinking = Arc::new(AtomicBool::new(false));
+        #[cfg(feature = "signal")]
+        crate::signal::register(&device, &blinking);
         Buzzer {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
-            handle: None,
+            device,
+            handle: ThreadGuard::new(Arc::clone(&blinking)),
+            blinking,
             blink_count: None,
+            remaining_blinks: Arc::new(AtomicI32::new(0)),
+            timing: TimingStrategy::default(),
+            sync: None,
+            sync_fallback: false,
+            on_cycle: None,
+            next_blink: Arc::new(Mutex::new(None)),
+            overridden: Arc::new(Mutex::new(Override::default())),
+            tick_generation: Arc::new(AtomicU64::new(0)),
+            deferred_writes: ThreadGuard::new(Arc::new(AtomicBool::new(false))),
+            tags: Tags::new(pin),
         }
     }
 
+    impl_pin_numbering!();
+
     impl_digital_output_device!();
+    impl_tags!();
+
+    /// Returns True if the device is currently active and False otherwise.
+    pub fn is_beeping(&self) -> bool {
+        self.is_active()
+    }
 
     /// Make the device turn on and off repeatedly in the background.
     /// Use `set_beep_count` to set the number of times to beep the device    
@@ -342,12 +1534,22 @@ impl Buzzer {
     ///
     pub fn beep(&mut self, on_time: f32, off_time: f32) {
         match self.blink_count {
-            None => self.blinker(on_time, off_time, None),
-            Some(n) => self.blinker(on_time, off_time, Some(n)),
+            None => self.blinker(on_time, off_time, None, None),
+            Some(n) => self.blinker(on_time, off_time, Some(n), None),
         }
     }
+    /// Make the device turn on and off repeatedly in the background, stopping after `total`
+    /// has elapsed, even in the middle of a beep.
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `total` - How long to beep for
+    ///
+    pub fn beep_for(&mut self, on_time: f32, off_time: f32, total: Duration) {
+        self.blinker(on_time, off_time, None, Some(total))
+    }
+
     /// Set the number of times to beep the device    
-    /// * `n` - Number of times to beep
+    /// * `n` - Number of times to beep. With 0 or less, `beep` turns the device off instead of beeping
     pub fn set_beep_count(&mut self, n: i32) {
         self.blink_count = Some(n)
     }
@@ -358,10 +1560,82 @@ impl Buzzer {
 pub struct PWMOutputDevice {
     device: Arc<Mutex<OutputDevice>>,
     blinking: Arc<AtomicBool>,
-    handle: Option<JoinHandle<()>>,
+    handle: ThreadGuard,
     blink_count: Option<i32>,
+    remaining_blinks: Arc<AtomicI32>,
+    timing: TimingStrategy,
     active_state: bool,
     inactive_state: bool,
+    value: f64,
+    // the last duty cycle written by a fade started with `fade_to`, as the bits of an f64,
+    // which is the value of the device until it is next stopped
+    fading: Option<Arc<AtomicU64>>,
+    dither: bool,
+    gamma: f64,
+    frequency: f64,
+    on_step: Option<StepCallback>,
+    crossings: Vec<Crossing>,
+    fade_fps: f32,
+    tick_generation: Arc<AtomicU64>,
+    tags: Tags,
+}
+
+/// How often a device reads a new value from its source
+const SOURCE_DELAY: Duration = Duration::from_millis(10);
+
+type StepCallback = Arc<Mutex<Box<dyn Fn(f64) + Send>>>;
+
+/// How many steps per second a fade takes by default
+const DEFAULT_FADE_FPS: f32 = 25.0;
+
+/// The frequency of software PWM by default, in Hz
+const DEFAULT_PWM_FREQUENCY: f64 = 100.0;
+
+/// The highest frequency of software PWM that `PWMOutputDevice::set_frequency` accepts, in Hz.
+/// Software PWM is timed by a thread which the OS can wake tens of microseconds late, so at
+/// higher frequencies the jitter is a large part of each period and the duty cycle loses its meaning.
+/// Use a `HardwarePWMOutputDevice` for higher frequencies.
+pub const MAX_SOFTWARE_PWM_FREQUENCY: f64 = 1_000.0;
+
+// Builds the (value, seconds) steps of a fade from `low` up to `high` and back down, holding each end,
+// with `fps` steps per second of fading
+fn fade_sequence(
+    fps: f32,
+    low: f32,
+    high: f32,
+    on_time: f32,
+    off_time: f32,
+    fade_in_time: f32,
+    fade_out_time: f32,
+) -> Vec<(f32, f32)> {
+    let mut sequence: Vec<(f32, f32)> = Vec::new();
+    let range = high - low;
+    // create sequence for fading in
+    if fade_in_time > 0.0 {
+        for i in 0..(fps * fade_in_time) as i32 {
+            sequence.push((
+                low + range * i as f32 * (1.0 / fps) / fade_in_time,
+                1.0 / fps,
+            ))
+        }
+    }
+
+    // allow to stay on for on_time
+    sequence.push((high, on_time));
+
+    // create sequence for fading out
+    if fade_out_time > 0.0 {
+        for i in 0..(fps * fade_out_time) as i32 {
+            sequence.push((
+                high - range * i as f32 * (1.0 / fps) / fade_out_time,
+                1.0 / fps,
+            ))
+        }
+    }
+
+    // allow to stay off for off_time
+    sequence.push((low, off_time));
+    sequence
 }
 
 macro_rules! impl_pwm_device {
@@ -371,12 +1645,55 @@ macro_rules! impl_pwm_device {
         pub fn set_value(&mut self, duty: f64) {
             self.write_state(duty)
         }
+
+        /// Set the duty cycle of the PWM device like `set_value`, returning
+        /// `GpioZeroError::InvalidArgument` if `duty` is outside 0.0 to 1.0, or the error from the pin
+        /// if the duty cycle couldn't be written.
+        pub fn try_set_value(&mut self, duty: f64) -> Result<(), GpioZeroError> {
+            self.try_write_state(duty)
+        }
+
+        /// Returns the duty cycle the device was last set to, between 0.0 (off) and 1.0 (fully on).
+        pub fn value(&self) -> f64 {
+            match &self.fading {
+                Some(position) => f64::from_bits(position.load(Ordering::SeqCst)),
+                None => self.value,
+            }
+        }
+
+        /// Returns ``True`` if the device is on or blinking in the background and ``False`` otherwise.
+        pub fn is_active(&self) -> bool {
+            self.blinking.load(Ordering::SeqCst) || self.value() > 0.0
+        }
         /// Set the number of times to blink the device
-        /// * `n` - Number of times to blink
+        /// * `n` - Number of times to blink. With 0 or less, `blink` turns the device off instead of blinking
         pub fn set_blink_count(&mut self, n: i32) {
             self.blink_count = Some(n)
         }
 
+        /// Returns the number of blinks left in a bounded blink, or `None` if the device is blinking continuously.
+        pub fn remaining_blinks(&self) -> Option<i32> {
+            remaining_blinks(&self.remaining_blinks)
+        }
+
+        /// Set how the device waits between the steps of a blink, which takes effect from the next blink.
+        /// `TimingStrategy::Spin` gives the most accurate short intervals, e.g. for fast strobes,
+        /// at the cost of keeping a CPU core busy for the whole blink.
+        pub fn set_timing_strategy(&mut self, strategy: TimingStrategy) {
+            self.timing = strategy;
+        }
+
+        /// Returns how the device waits between the steps of a blink.
+        pub fn timing_strategy(&self) -> TimingStrategy {
+            self.timing
+        }
+
+        /// Returns the shortest on or off time the device's timing strategy can reliably blink
+        /// with. Shorter times are still attempted, with a warning.
+        pub fn min_blink_interval(&self) -> Duration {
+            self.timing.min_interval()
+        }
+
         fn blinker(
             &mut self,
             on_time: f32,
@@ -385,101 +1702,508 @@ macro_rules! impl_pwm_device {
             fade_out_time: f32,
             n: Option<i32>,
         ) {
-            let mut sequence: Vec<(f32, f32)> = Vec::new();
-            let fps = 25.0;
-            // create sequence for fading in
-            if fade_in_time > 0.0 {
-                for i in 0..fps as i32 * fade_in_time as i32 {
-                    sequence.push((i as f32 * (1.0 / fps) / fade_in_time, 1.0 / fps))
-                }
+            let sequence = fade_sequence(
+                self.fade_fps,
+                0.0,
+                1.0,
+                on_time,
+                off_time,
+                fade_in_time,
+                fade_out_time,
+            );
+            self.play_sequence(sequence, n);
+        }
+
+        /// Make the device fade in and out repeatedly, advanced by `driver` rather than a
+        /// background thread. Nothing happens until `driver.tick` is called, and fades are only
+        /// as smooth as the interval between ticks.
+        /// * `driver` - The TickDriver to register the blink with
+        /// * `on_time` - Number of seconds on
+        /// * `off_time` - Number of seconds off
+        /// * `fade_in_time` - Number of seconds to spend fading in
+        /// * `fade_out_time` - Number of seconds to spend fading out
+        /// * `n` - Number of times to blink, or `None` to blink until stopped
+        pub fn blink_ticked(
+            &mut self,
+            driver: &mut TickDriver,
+            on_time: f32,
+            off_time: f32,
+            fade_in_time: f32,
+            fade_out_time: f32,
+            n: Option<i32>,
+        ) {
+            self.stop();
+            // a count of zero means don't blink at all, leaving the device off
+            if matches!(n, Some(n) if n <= 0) {
+                self.remaining_blinks.store(0, Ordering::SeqCst);
+                return;
             }
+            let sequence = fade_sequence(
+                self.fade_fps,
+                0.0,
+                1.0,
+                on_time,
+                off_time,
+                fade_in_time,
+                fade_out_time,
+            );
+            self.remaining_blinks.store(n.unwrap_or(-1), Ordering::SeqCst);
+            self.blinking.store(true, Ordering::SeqCst);
+            driver.register(
+                TickedPwmSink(self.sink()),
+                sequence,
+                n,
+                &self.tick_generation,
+                &self.blinking,
+                &self.remaining_blinks,
+            );
+        }
 
-            // allow to stay on for on_time
-            sequence.push((1.0, on_time));
+        /// Make the device fade between two duty cycles repeatedly in the background.
+        /// * `low` - The duty cycle to fade out to, between 0.0 and 1.0
+        /// * `high` - The duty cycle to fade in to, greater than `low`
+        /// * `fade_in_time` - Number of seconds to spend fading in
+        /// * `fade_out_time` - Number of seconds to spend fading out
+        /// * `n` - Number of times to pulse, or `None` to pulse until stopped
+        pub fn pulse_between(
+            &mut self,
+            low: f64,
+            high: f64,
+            fade_in_time: f32,
+            fade_out_time: f32,
+            n: Option<i32>,
+        ) {
+            if !(0.0..=1.0).contains(&low) || !(0.0..=1.0).contains(&high) {
+                println!("low and high must be between 0.0 and 1.0");
+                return;
+            }
+            if low >= high {
+                println!("low must be less than high");
+                return;
+            }
+            let sequence = fade_sequence(
+                self.fade_fps,
+                low as f32,
+                high as f32,
+                0.0,
+                0.0,
+                fade_in_time,
+                fade_out_time,
+            );
+            self.play_sequence(sequence, n);
+        }
 
-            // create sequence for fading out
-            if fade_out_time > 0.0 {
-                for i in 0..fps as i32 * fade_out_time as i32 {
-                    sequence.push((1.0 - (i as f32 * (1.0 / fps) / fade_out_time), 1.0 / fps))
-                }
+        /// Make the device blink repeatedly in the background, resting at a dim level instead of
+        /// turning off between blinks, e.g. for ambient lighting that gently pulses brighter.
+        /// The device fades out to `rest_value` and stays there for the off phase, and is left
+        /// at it when a bounded blink finishes.
+        /// * `on_time` - Number of seconds on
+        /// * `off_time` - Number of seconds at `rest_value`
+        /// * `fade_in_time` - Number of seconds to spend fading in
+        /// * `fade_out_time` - Number of seconds to spend fading out
+        /// * `rest_value` - The duty cycle to rest at, between 0.0 and 1.0
+        /// * `n` - Number of times to blink, or `None` to blink until stopped
+        pub fn blink_with_rest(
+            &mut self,
+            on_time: f32,
+            off_time: f32,
+            fade_in_time: f32,
+            fade_out_time: f32,
+            rest_value: f64,
+            n: Option<i32>,
+        ) {
+            if !(0.0..=1.0).contains(&rest_value) {
+                println!("rest_value must be between 0.0 and 1.0");
+                return;
             }
+            let sequence = fade_sequence(
+                self.fade_fps,
+                rest_value as f32,
+                1.0,
+                on_time,
+                off_time,
+                fade_in_time,
+                fade_out_time,
+            );
+            self.play_sequence(sequence, n);
+        }
 
-            // allow to stay off for off_time
-            sequence.push((0.0, off_time));
+        /// Fade from the current duty cycle to `target` in the background, then stay there.
+        /// * `target` - The duty cycle to fade to, between 0.0 and 1.0
+        /// * `duration` - How long the fade takes
+        pub fn fade_to(&mut self, target: f64, duration: Duration) {
+            if !(0.0..=1.0).contains(&target) {
+                println!("target must be between 0.0 and 1.0");
+                return;
+            }
+            // carry on from where a previous fade got to, without releasing the PWM in between,
+            // which would drop the pin to 0% before the new fade starts
+            let value = self.value();
+            self.halt();
+            self.value = value;
+            let start = value as f32;
+            let target = target as f32;
+            let steps = (self.fade_fps * duration.as_secs_f32()) as i32;
+            let mut sequence: Vec<(f32, f32)> = (0..steps)
+                .map(|i| {
+                    (
+                        start + (target - start) * i as f32 / steps as f32,
+                        1.0 / self.fade_fps,
+                    )
+                })
+                .collect();
+            sequence.push((target, 0.0));
+            let position = Arc::new(AtomicU64::new(value.to_bits()));
+            let mut sink = self.sink();
+            sink.position = Some(Arc::clone(&position));
+            self.fading = Some(position);
+            self.spawn_sequence(sink, sequence, Some(1));
+        }
 
-            let device = Arc::clone(&self.device);
+        /// Set how many steps per second a fade takes, which takes effect from the next blink or pulse.
+        /// More steps give smoother fades, especially slow ones, at the cost of more CPU time.
+        /// * `fps` - Number of steps per second, 25 by default
+        pub fn set_fade_fps(&mut self, fps: f32) {
+            if fps <= 0.0 {
+                println!("fade fps must be greater than 0");
+            } else {
+                self.fade_fps = fps;
+            }
+        }
+
+        /// Returns how many steps per second a fade takes.
+        pub fn fade_fps(&self) -> f32 {
+            self.fade_fps
+        }
+
+        /// Make the device turn on at a given duty cycle and off repeatedly, without fading.
+        /// * `on_duty` - The duty cycle while on, between 0.0 and 1.0
+        /// * `on_time` - Number of seconds on
+        /// * `off_time` - Number of seconds off
+        /// * `n` - Number of times to blink, or `None` to blink until stopped
+        pub fn blink_at(&mut self, on_duty: f64, on_time: f32, off_time: f32, n: Option<i32>) {
+            if !(0.0..=1.0).contains(&on_duty) {
+                println!("on_duty must be between 0.0 and 1.0");
+                return;
+            }
+            self.play_sequence(vec![(on_duty as f32, on_time), (0.0, off_time)], n);
+        }
+
+        /// Play a `Pattern` once in the background.
+        /// * `pattern` - The sequence of brightness levels to play
+        pub fn play(&mut self, pattern: &Pattern) {
+            self.play_sequence(pattern.compile(), Some(1));
+        }
+
+        /// Play one of the ready-made blink effects in the background until `stop` is called.
+        /// * `preset` - The effect to play
+        pub fn play_preset(&mut self, preset: BlinkPreset) {
+            self.play_sequence(preset.pattern().compile(), None);
+        }
+
+        /// Drive the device through a precomputed waveform in the background, setting the duty
+        /// cycle to each value in turn at a fixed interval, e.g. to play a gamma corrected fade table.
+        /// * `values` - The duty cycles to play, each between 0.0 and 1.0
+        /// * `step` - How long to hold each value
+        /// * `n` - Number of times to play the waveform, or `None` to play it until stopped
+        pub fn play_values(&mut self, values: &[f64], step: Duration, n: Option<i32>) {
+            if values.iter().any(|value| !(0.0..=1.0).contains(value)) {
+                println!("values must be between 0.0 and 1.0");
+                return;
+            }
+            let step = step.as_secs_f32();
+            self.play_sequence(
+                values.iter().map(|value| (*value as f32, step)).collect(),
+                n,
+            );
+        }
+
+        fn play_sequence(&mut self, sequence: Vec<(f32, f32)>, n: Option<i32>) {
+            self.stop();
+            check_intervals(&sequence, self.timing);
+            // a count of zero means don't blink at all, leaving the device off
+            if matches!(n, Some(n) if n <= 0) {
+                self.remaining_blinks.store(0, Ordering::SeqCst);
+                return;
+            }
+
+            let sink = self.sink();
+            self.spawn_sequence(sink, sequence, n);
+        }
+
+        // Play `sequence` to `sink` on the background thread, which must have been stopped
+        fn spawn_sequence(
+            &mut self,
+            sink: PwmSink<Arc<Mutex<OutputDevice>>>,
+            sequence: Vec<(f32, f32)>,
+            n: Option<i32>,
+        ) {
             let blinking = Arc::clone(&self.blinking);
+            let remaining_blinks = Arc::clone(&self.remaining_blinks);
+            let timing = self.timing;
 
-            self.handle = Some(thread::spawn(move || {
-                blinking.store(true, Ordering::SeqCst);
-                match n {
-                    Some(end) => {
-                        for _ in 0..end {
-                            for (value, delay) in &sequence {
-                                if !blinking.load(Ordering::SeqCst) {
-                                    // device.lock().unwrap().off();
-                                    break;
-                                }
-                                device
-                                    .lock()
-                                    .unwrap()
-                                    .pin
-                                    .set_pwm_frequency(100.0, f64::from(*value))
-                                    .unwrap();
-                                thread::sleep(Duration::from_millis((delay * 1000 as f32) as u64));
-                            }
-                        }
+            remaining_blinks.store(n.unwrap_or(-1), Ordering::SeqCst);
+            blinking.store(true, Ordering::SeqCst);
+            self.handle.spawn(move || {
+                let mut sink = sink.lock();
+                play(
+                    &mut StoppableClock::new(timing, &blinking),
+                    &mut sink,
+                    &sequence,
+                    n,
+                    &blinking,
+                    &remaining_blinks,
+                );
+            });
+        }
+
+        /// Set the duty cycle from the values of `source` in the background, taking a new value
+        /// every 10ms until the source runs out or `stop` is called. Values are clamped to 0.0 - 1.0.
+        /// * `source` - The values to set, e.g. one of the generators in `tools`
+        pub fn set_source<I>(&mut self, source: I)
+        where
+            I: IntoIterator<Item = f64>,
+            I::IntoIter: Send + 'static,
+        {
+            self.stop();
+
+            let source = source.into_iter();
+            let blinking = Arc::clone(&self.blinking);
+            let sink = self.sink();
+            let timing = self.timing;
+
+            blinking.store(true, Ordering::SeqCst);
+            self.handle.spawn(move || {
+                let mut sink = sink.lock();
+                let mut clock = StoppableClock::new(timing, &blinking);
+                for value in source {
+                    if !blinking.load(Ordering::SeqCst) {
+                        break;
                     }
-                    None => loop {
-                        for (value, delay) in &sequence {
-                            if !blinking.load(Ordering::SeqCst) {
-                                // device.lock().unwrap().off();
-                                break;
-                            }
-                            device
-                                .lock()
-                                .unwrap()
-                                .pin
-                                .set_pwm_frequency(100.0, f64::from(*value))
-                                .unwrap();
-                            thread::sleep(Duration::from_millis((delay * 1000 as f32) as u64));
-                        }
-                    },
+                    sink.write(value.clamp(0.0, 1.0));
+                    clock.sleep(SOURCE_DELAY);
                 }
-            }));
+            });
         }
 
-        fn stop(&mut self) {
-            self.blinking.clone().store(false, Ordering::SeqCst);
-            if self.device.lock().unwrap().pin.clear_pwm().is_err() {
-                println!("Could not clear pwm for pin");
-            };
+        /// Set the duty cycle from an input in the background, calling `read` for a new value
+        /// every 10ms until `stop` is called. Values are clamped to 0.0 - 1.0.
+        /// * `read` - Returns the current value of the input, e.g. a potentiometer read through an ADC
+        pub fn set_source_from<F>(&mut self, read: F)
+        where
+            F: FnMut() -> f64 + Send + 'static,
+        {
+            self.set_source(std::iter::repeat_with(read))
+        }
+
+        /// Register a function to be called with the duty cycle at each step of a blink, pulse or pattern,
+        /// e.g. to synchronize other hardware with a fade. It replaces any function registered before
+        /// and takes effect from the next blink.
+        /// * `f` - The function to call from the background thread
+        pub fn on_step<F>(&mut self, f: F)
+        where
+            F: Fn(f64) + Send + 'static,
+        {
+            self.on_step = Some(Arc::new(Mutex::new(Box::new(f))));
+        }
+
+        /// Remove the function registered with `on_step`.
+        pub fn clear_on_step(&mut self) {
+            self.on_step = None;
+        }
+
+        /// Register a function to be called when the duty cycle passes `level` during a blink,
+        /// pulse, pattern or source, e.g. to play a sound as a fading LED reaches half brightness.
+        /// It takes effect from the next blink.
+        /// * `level` - The duty cycle to watch for, between 0.0 and 1.0
+        /// * `rising` - ``True`` to call `f` when the duty cycle rises to `level`, ``False`` when it falls to it
+        /// * `f` - The function to call from the background thread
+        pub fn when_crosses<F>(&mut self, level: f64, rising: bool, f: F)
+        where
+            F: Fn() + Send + 'static,
+        {
+            self.crossings.push(Crossing {
+                level,
+                rising,
+                action: Arc::new(Mutex::new(Box::new(f))),
+            });
+        }
+
+        /// Remove all the functions registered with `when_crosses`.
+        pub fn clear_when_crosses(&mut self) {
+            self.crossings.clear();
+        }
+
+        // The sink that background threads write the duty cycle to
+        fn sink(&self) -> PwmSink<Arc<Mutex<OutputDevice>>> {
+            PwmSink {
+                device: Arc::clone(&self.device),
+                on_step: self.on_step.clone(),
+                crossings: self.crossings.clone(),
+                gamma: self.gamma,
+                active_high: self.active_high(),
+                frequency: self.frequency,
+                running: Arc::clone(&self.blinking),
+                last: None,
+                position: None,
+            }
+        }
+
+        /// Block until the blink or pulse running in the background is done.
+        /// Returns immediately if nothing is running.
+        pub fn wait(&mut self) {
+            if let Some(handle) = self.handle.take() {
+                handle.join().expect("Could not join spawned thread");
+            }
+        }
+
+        /// Stop any blink or pulse running in the background and turn the device off.
+        pub fn stop(&mut self) {
+            self.halt();
+            self.value = 0.0;
+            let active_high = self.active_state;
+            lock_device(&self.device).release_pwm(active_high);
+        }
+
+        // Stop the background thread and any ticked blink, leaving the pin as it is
+        fn halt(&mut self) {
+            self.handle.stop();
+            self.tick_generation.fetch_add(1, Ordering::SeqCst);
+            self.fading = None;
         }
 
         fn write_state(&mut self, value: f64) {
+            if let Err(e) = self.try_write_state(value) {
+                println!("{}", e);
+            }
+        }
+
+        fn try_write_state(&mut self, value: f64) -> Result<(), GpioZeroError> {
             if !(value >= 0.0 && value <= 1.0) {
-                println!("Value must be between 0.0 and 1.0");
-                return;
+                return Err(GpioZeroError::InvalidArgument(
+                    "value must be between 0.0 and 1.0".to_string(),
+                ));
             }
             self.stop();
-            if self.active_high() {
-                self.device
-                    .lock()
-                    .unwrap()
-                    .pin
-                    .set_pwm_frequency(100.0, value)
-                    .unwrap()
+            let duty = value.powf(self.gamma);
+            let duty = if self.active_high() { duty } else { 1.0 - duty };
+            if self.dither {
+                self.ditherer(duty)?;
+            } else {
+                with_retry(retry_attempts(), RETRY_DELAY, || {
+                    lock_device(&self.device)
+                        .pin
+                        .set_pwm_frequency(self.frequency, duty)
+                })?;
+            }
+            self.value = value;
+            Ok(())
+        }
+
+        /// Set the frequency of the PWM signal in Hz, keeping the current value.
+        /// A blink running in the background keeps the old frequency until the next blink.
+        /// Returns `GpioZeroError::InvalidArgument`, leaving the frequency unchanged, if
+        /// `frequency` isn't greater than 0.0 and at most `MAX_SOFTWARE_PWM_FREQUENCY`.
+        pub fn set_frequency(&mut self, frequency: f64) -> Result<(), GpioZeroError> {
+            if !(frequency > 0.0 && frequency <= MAX_SOFTWARE_PWM_FREQUENCY) {
+                return Err(GpioZeroError::InvalidArgument(format!(
+                    "software PWM frequency must be greater than 0 and at most {} Hz, got {} Hz",
+                    MAX_SOFTWARE_PWM_FREQUENCY, frequency
+                )));
+            }
+            self.frequency = frequency;
+            if !self.blinking.load(Ordering::SeqCst) {
+                let value = self.value();
+                self.write_state(value);
+            }
+            Ok(())
+        }
+
+        /// Returns the frequency of the PWM signal in Hz.
+        pub fn frequency(&self) -> f64 {
+            self.frequency
+        }
+
+        /// Set the gamma correction applied to the value before it is written as the duty cycle,
+        /// so that `set_value` and fades look perceptually linear: the duty cycle is `value^gamma`.
+        /// Human perception of brightness is nonlinear, so an LED faded linearly seems to spend most
+        /// of the fade bright; a gamma of around 2.2 corrects this. `value` and the `on_step` and
+        /// `when_crosses` functions still see the uncorrected value. Takes effect from the next write or blink.
+        /// * `gamma` - The exponent, greater than 0.0. The default of 1.0 applies no correction
+        pub fn set_gamma(&mut self, gamma: f64) {
+            if gamma > 0.0 {
+                self.gamma = gamma;
             } else {
-                self.device
-                    .lock()
-                    .unwrap()
+                println!("gamma must be greater than 0.0");
+            }
+        }
+
+        /// Returns the gamma correction applied to the value before it is written as the duty cycle.
+        pub fn gamma(&self) -> f64 {
+            self.gamma
+        }
+
+        /// Enable or disable temporal dithering.
+        /// When enabled, a duty cycle that falls between two of the 100 steps of the
+        /// software PWM is approximated by alternating between the neighbouring steps
+        /// in the background, giving smoother control at low brightness.
+        pub fn set_dither(&mut self, value: bool) {
+            self.dither = value;
+        }
+
+        // Write the lower of the two nearest steps straight away, so an error is returned to the
+        // caller, then alternate between them in the background for a fractional step
+        fn ditherer(&mut self, duty: f64) -> Result<(), GpioZeroError> {
+            let steps = 100.0;
+            let low = (duty * steps).floor();
+            let fraction = duty * steps - low;
+            with_retry(retry_attempts(), RETRY_DELAY, || {
+                lock_device(&self.device)
                     .pin
-                    .set_pwm_frequency(100.0, 1.0 - value)
-                    .unwrap()
+                    .set_pwm_frequency(self.frequency, low / steps)
+            })?;
+            if fraction <= 0.0 {
+                return Ok(());
             }
+
+            let device = Arc::clone(&self.device);
+            let blinking = Arc::clone(&self.blinking);
+            let frequency = self.frequency;
+            let active_high = self.active_high();
+
+            blinking.store(true, Ordering::SeqCst);
+            self.handle.spawn(move || {
+                // spread the fraction over successive PWM periods using error diffusion
+                let mut error = 0.0;
+                while blinking.load(Ordering::SeqCst) {
+                    error += fraction;
+                    let step = if error >= 1.0 {
+                        error -= 1.0;
+                        low + 1.0
+                    } else {
+                        low
+                    };
+                    let mut device = lock_device(&device);
+                    if let Err(e) = with_retry(retry_attempts(), RETRY_DELAY, || {
+                        device.pin.set_pwm_frequency(frequency, step / steps)
+                    }) {
+                        println!("Could not set pwm for pin, stopping: {}", e);
+                        blinking.store(false, Ordering::SeqCst);
+                        device.release_pwm(active_high);
+                        break;
+                    }
+                    drop(device);
+                    thread::sleep(Duration::from_millis(10));
+                }
+            });
+            Ok(())
         }
 
-        /// Set the state for active_high
+        /// Set the state for active_high. Set it to ``False`` for devices which are on when the pin
+        /// is low, such as a common-anode LED: the duty cycle written to the pin is then inverted,
+        /// while `value` still reports the logical brightness. The device keeps its logical value,
+        /// so the pin is rewritten with the inverted duty cycle; a blink running in the background
+        /// keeps the old polarity until the next blink.
         pub fn set_active_high(&mut self, value: bool) {
             if value {
                 self.active_state = true;
@@ -488,12 +2212,14 @@ macro_rules! impl_pwm_device {
                 self.active_state = false;
                 self.inactive_state = true;
             }
+            if !self.blinking.load(Ordering::SeqCst) {
+                let value = self.value();
+                self.write_state(value);
+            }
         }
-        /// When ``True``, the `value` property is ``True`` when the device's
-        /// `pin` is high. When ``False`` the `value` property is
-        /// ``True`` when the device's pin is low (i.e. the value is inverted).
-        /// Be warned that changing it will invert `value` (i.e. changing this property doesn't change
-        /// the device's pin state - it just changes how that state is interpreted).
+        /// When ``True``, the duty cycle written to the pin is the `value` of the device.
+        /// When ``False`` the duty cycle is inverted, so a `value` of 0.25 drives the pin
+        /// high 75% of the time.
         pub fn active_high(&self) -> bool {
             self.active_state
         }
@@ -516,17 +2242,42 @@ impl PWMOutputDevice {
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
     pub fn new(pin: u8) -> PWMOutputDevice {
-        PWMOutputDevice {
-            device: Arc::new(Mutex::new(OutputDevice::new(pin))),
-            blinking: Arc::new(AtomicBool::new(false)),
-            handle: None,
+        PWMOutputDevice::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a PWMOutputDevice with the pin number given, or a `GpioZeroError` if the pin can't be
+    /// accessed, e.g. because it is already in use or the program lacks permission
+    pub fn try_new(pin: u8) -> Result<PWMOutputDevice, GpioZeroError> {
+        let device = Arc::new(Mutex::new(OutputDevice::claim(pin, "PWMOutputDevice")?));
+        let blinking = Arc::new(AtomicBool::new(false));
+        #[cfg(feature = "signal")]
+        crate::signal::register(&device, &blinking);
+        Ok(PWMOutputDevice {
+            device,
+            handle: ThreadGuard::new(Arc::clone(&blinking)),
+            blinking,
             blink_count: None,
+            remaining_blinks: Arc::new(AtomicI32::new(0)),
+            timing: TimingStrategy::default(),
             active_state: true,
             inactive_state: false,
-        }
+            value: 0.0,
+            fading: None,
+            dither: false,
+            gamma: 1.0,
+            frequency: DEFAULT_PWM_FREQUENCY,
+            on_step: None,
+            crossings: Vec::new(),
+            fade_fps: DEFAULT_FADE_FPS,
+            tick_generation: Arc::new(AtomicU64::new(0)),
+            tags: Tags::new(pin),
+        })
     }
 
+    impl_pin_numbering!();
+
     impl_pwm_device!();
+    impl_tags!();
 
     /// Make the device turn on and off repeatedly
     /// * `on_time` - Number of seconds on
@@ -541,68 +2292,1339 @@ impl PWMOutputDevice {
         }
     }
 
-    /// Make the device fade in and out repeatedly.    
-    /// * `fade_in_time` - Number of seconds to spend fading in
-    /// * `fade_out_time` - Number of seconds to spend fading out
-    ///
-    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) {
-        self.blink(0.0, 0.0, fade_in_time, fade_out_time)
+    /// Make the device fade in and out repeatedly.    
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    ///
+    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) {
+        self.blink(0.0, 0.0, fade_in_time, fade_out_time)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for DigitalOutputDevice {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Drop for LED {
+    fn drop(&mut self) {
+        self.unfollow();
+        self.stop();
+    }
+}
+
+impl Drop for Buzzer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Drop for PWMOutputDevice {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Represents a light emitting diode (LED) with variable brightness.
+/// A typical configuration of such a device is to connect a GPIO pin
+/// to the anode (long leg) of the LED, and the cathode (short leg) to ground,
+/// with an optional resistor to prevent the LED from burning out.
+pub struct PWMLED(PWMOutputDevice);
+
+impl PWMLED {
+    /// Returns a PMWLED with the pin number given
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    ///    
+    pub fn new(pin: u8) -> PWMLED {
+        PWMLED(PWMOutputDevice::new(pin))
+    }
+
+    /// Returns a PWMLED with the pin number given, or a `GpioZeroError` if the pin can't be
+    /// accessed, e.g. because it is already in use or the program lacks permission
+    pub fn try_new(pin: u8) -> Result<PWMLED, GpioZeroError> {
+        Ok(PWMLED(PWMOutputDevice::try_new(pin)?))
+    }
+
+    impl_pin_numbering!();
+
+    /// Make the device turn on and off repeatedly
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    ///
+    pub fn blink(&mut self, on_time: f32, off_time: f32, fade_in_time: f32, fade_out_time: f32) {
+        self.0.blink(on_time, off_time, fade_in_time, fade_out_time)
+    }
+
+    /// Make the device fade in and out repeatedly, advanced by `driver` rather than a
+    /// background thread. Nothing happens until `driver.tick` is called.
+    /// * `n` - Number of times to blink, or `None` to blink until stopped
+    pub fn blink_ticked(
+        &mut self,
+        driver: &mut TickDriver,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) {
+        self.0
+            .blink_ticked(driver, on_time, off_time, fade_in_time, fade_out_time, n)
+    }
+
+    /// Turns the device on.
+    pub fn on(&mut self) {
+        self.0.on();
+    }
+
+    /// Turns the device off.
+    pub fn off(&mut self) {
+        self.0.off();
+    }
+
+    /// Stop any blink or pulse running in the background and turn the device off.
+    pub fn stop(&mut self) {
+        self.0.stop();
+    }
+
+    /// Block until the blink or pulse running in the background is done.
+    /// Returns immediately if nothing is running.
+    pub fn wait(&mut self) {
+        self.0.wait();
+    }
+
+    /// Set the brightness from the values of `source` in the background, taking a new value
+    /// every 10ms until the source runs out or `stop` is called.
+    /// * `source` - The values to set, e.g. one of the generators in `tools`
+    pub fn set_source<I>(&mut self, source: I)
+    where
+        I: IntoIterator<Item = f64>,
+        I::IntoIter: Send + 'static,
+    {
+        self.0.set_source(source);
+    }
+
+    /// Set the brightness from an input in the background, calling `read` for a new value
+    /// every 10ms until `stop` is called. Values are clamped to 0.0 - 1.0.
+    /// * `read` - Returns the current value of the input, between 0.0 and 1.0
+    ///
+    /// # Example
+    ///
+    /// Dim the LED with a potentiometer read through channel 0 of an MCP3008 ADC:
+    ///
+    /// ```no_run
+    /// use rust_gpiozero::*;
+    ///
+    /// let mut led = PWMLED::new(17);
+    /// let mut adc = BitBangSpi::new(10, 9, 11, 8);
+    /// led.set_source_from(move || {
+    ///     let reply = adc.transfer(&[0x01, 0x80, 0x00]);
+    ///     f64::from(u16::from(reply[1] & 0x03) << 8 | u16::from(reply[2])) / 1023.0
+    /// });
+    /// led.wait();
+    /// ```
+    pub fn set_source_from<F>(&mut self, read: F)
+    where
+        F: FnMut() -> f64 + Send + 'static,
+    {
+        self.0.set_source_from(read);
+    }
+
+    /// Register a function to be called with the brightness at each step of a blink, pulse or pattern.
+    /// * `f` - The function to call from the background thread
+    pub fn on_step<F>(&mut self, f: F)
+    where
+        F: Fn(f64) + Send + 'static,
+    {
+        self.0.on_step(f);
+    }
+
+    /// Remove the function registered with `on_step`.
+    pub fn clear_on_step(&mut self) {
+        self.0.clear_on_step();
+    }
+
+    /// Register a function to be called when the brightness passes `level` during a blink,
+    /// pulse, pattern or source.
+    /// * `level` - The brightness to watch for, between 0.0 and 1.0
+    /// * `rising` - ``True`` to call `f` when the brightness rises to `level`, ``False`` when it falls to it
+    /// * `f` - The function to call from the background thread
+    pub fn when_crosses<F>(&mut self, level: f64, rising: bool, f: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        self.0.when_crosses(level, rising, f);
+    }
+
+    /// Remove all the functions registered with `when_crosses`.
+    pub fn clear_when_crosses(&mut self) {
+        self.0.clear_when_crosses();
+    }
+
+    /// Returns ``True`` if the LED is lit or blinking in the background and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.0.is_active()
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+
+    /// Make the device fade in and out repeatedly.
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    ///
+    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) {
+        self.0.pulse(fade_in_time, fade_out_time);
+    }
+
+    /// Fade from the current brightness to `target` in the background, then stay there.
+    /// * `target` - The brightness to fade to, between 0.0 and 1.0
+    /// * `duration` - How long the fade takes
+    pub fn fade_to(&mut self, target: f64, duration: Duration) {
+        self.0.fade_to(target, duration);
+    }
+
+    /// Set how many steps per second a fade takes, which takes effect from the next blink or pulse.
+    /// * `fps` - Number of steps per second, 25 by default
+    pub fn set_fade_fps(&mut self, fps: f32) {
+        self.0.set_fade_fps(fps);
+    }
+
+    /// Make the LED turn on at a given brightness and off repeatedly, without fading.
+    /// * `on_duty` - The brightness while on, between 0.0 and 1.0
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds off
+    /// * `n` - Number of times to blink, or `None` to blink until stopped
+    pub fn blink_at(&mut self, on_duty: f64, on_time: f32, off_time: f32, n: Option<i32>) {
+        self.0.blink_at(on_duty, on_time, off_time, n);
+    }
+
+    /// Make the device fade between two brightness levels repeatedly in the background.
+    /// * `low` - The brightness to fade out to, between 0.0 and 1.0
+    /// * `high` - The brightness to fade in to, greater than `low`
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    /// * `n` - Number of times to pulse, or `None` to pulse until stopped
+    pub fn pulse_between(
+        &mut self,
+        low: f64,
+        high: f64,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        n: Option<i32>,
+    ) {
+        self.0
+            .pulse_between(low, high, fade_in_time, fade_out_time, n);
+    }
+
+    /// Make the LED blink repeatedly in the background, resting at a dim glow instead of
+    /// turning off between blinks.
+    /// * `on_time` - Number of seconds on
+    /// * `off_time` - Number of seconds at `rest_value`
+    /// * `fade_in_time` - Number of seconds to spend fading in
+    /// * `fade_out_time` - Number of seconds to spend fading out
+    /// * `rest_value` - The brightness to rest at, between 0.0 and 1.0
+    /// * `n` - Number of times to blink, or `None` to blink until stopped
+    pub fn blink_with_rest(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        rest_value: f64,
+        n: Option<i32>,
+    ) {
+        self.0.blink_with_rest(
+            on_time,
+            off_time,
+            fade_in_time,
+            fade_out_time,
+            rest_value,
+            n,
+        );
+    }
+
+    /// Set the duty cycle of the PWM device. 0.0 is off, 1.0 is fully on.
+    /// Values in between may be specified for varying levels of power in the device.
+    pub fn set_value(&mut self, value: f64) {
+        self.0.set_value(value);
+    }
+
+    /// Set the brightness like `set_value`, returning `GpioZeroError::InvalidArgument` if `value`
+    /// is outside 0.0 to 1.0, or the error from the pin if it couldn't be written.
+    pub fn try_set_value(&mut self, value: f64) -> Result<(), GpioZeroError> {
+        self.0.try_set_value(value)
+    }
+
+    /// Set the state for active_high. Set it to ``False`` for a common-anode LED, which is lit
+    /// when the pin is low; `value` still reports the brightness of the LED.
+    pub fn set_active_high(&mut self, value: bool) {
+        self.0.set_active_high(value)
+    }
+
+    /// Returns ``False`` if the duty cycle written to the pin is inverted for an active-low LED.
+    pub fn active_high(&self) -> bool {
+        self.0.active_high()
+    }
+
+    /// Set the frequency of the PWM signal in Hz, at most `MAX_SOFTWARE_PWM_FREQUENCY`.
+    pub fn set_frequency(&mut self, frequency: f64) -> Result<(), GpioZeroError> {
+        self.0.set_frequency(frequency)
+    }
+
+    /// Returns the frequency of the PWM signal in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.0.frequency()
+    }
+
+    /// Play a `Pattern` once in the background.
+    /// * `pattern` - The sequence of brightness levels to play
+    pub fn play(&mut self, pattern: &Pattern) {
+        self.0.play(pattern);
+    }
+
+    /// Play one of the ready-made blink effects in the background until `stop` is called.
+    /// * `preset` - The effect to play
+    pub fn play_preset(&mut self, preset: BlinkPreset) {
+        self.0.play_preset(preset);
+    }
+
+    /// Drive the LED through a precomputed waveform in the background, setting the brightness
+    /// to each value in turn at a fixed interval, e.g. to play a gamma corrected fade table.
+    /// * `values` - The brightness levels to play, each between 0.0 and 1.0
+    /// * `step` - How long to hold each value
+    /// * `n` - Number of times to play the waveform, or `None` to play it until stopped
+    pub fn play_values(&mut self, values: &[f64], step: Duration, n: Option<i32>) {
+        self.0.play_values(values, step, n);
+    }
+
+    /// Attach a piece of metadata to the device, e.g. its location or circuit, replacing
+    /// any value the key had. Tagged devices can be found with `registry::find_by_tag`.
+    /// * `key` - The name of the tag
+    /// * `value` - The value of the tag
+    pub fn set_tag(&mut self, key: &str, value: &str) {
+        self.0.set_tag(key, value)
+    }
+
+    /// Returns the value of the tag `key`, or `None` if it isn't set.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.0.tag(key)
+    }
+
+    /// Returns all of the device's tags.
+    pub fn tags(&self) -> &HashMap<String, String> {
+        self.0.tags()
+    }
+
+    /// Remove the tag `key` from the device, returning its value if it was set.
+    pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+        self.0.remove_tag(key)
+    }
+
+    /// Returns the number of blinks left in a bounded blink, or `None` if the device is blinking continuously.
+    pub fn remaining_blinks(&self) -> Option<i32> {
+        self.0.remaining_blinks()
+    }
+
+    /// Set how the LED waits between the steps of a blink or fade, which takes effect from the next blink.
+    pub fn set_timing_strategy(&mut self, strategy: TimingStrategy) {
+        self.0.set_timing_strategy(strategy);
+    }
+
+    /// Returns how the LED waits between the steps of a blink or fade.
+    pub fn timing_strategy(&self) -> TimingStrategy {
+        self.0.timing_strategy()
+    }
+
+    /// Returns the shortest on or off time the LED's timing strategy can reliably blink with.
+    pub fn min_blink_interval(&self) -> Duration {
+        self.0.min_blink_interval()
+    }
+
+    /// Set the gamma correction applied to the brightness before it is written as the duty cycle,
+    /// so that fades look perceptually linear. A gamma of around 2.2 suits most LEDs.
+    /// * `gamma` - The exponent, greater than 0.0. The default of 1.0 applies no correction
+    pub fn set_gamma(&mut self, gamma: f64) {
+        self.0.set_gamma(gamma);
+    }
+
+    /// Returns the gamma correction applied to the brightness.
+    pub fn gamma(&self) -> f64 {
+        self.0.gamma()
+    }
+
+    /// Enable or disable temporal dithering, for smoother control at low brightness.
+    pub fn set_dither(&mut self, value: bool) {
+        self.0.set_dither(value);
+    }
+
+    /// Returns the brightness the LED was last set to, between 0.0 (off) and 1.0 (fully on).
+    pub fn value(&self) -> f64 {
+        self.0.value()
+    }
+
+    /// Set the number of times to blink the device    
+    /// * `n` - Number of times to blink. With 0 or less, `blink` turns the device off instead of blinking
+    pub fn set_blink_count(&mut self, n: i32) {
+        self.0.blink_count = Some(n)
+    }
+}
+
+/// A color as red, green and blue components, each between 0.0 (off) and 1.0 (full brightness)
+pub type Color = (f64, f64, f64);
+
+/// Represents a full color LED component (composed of red, green, and blue LEDs).
+/// Connect the common cathode (longest leg) to a ground pin; connect each of the other legs
+/// (representing the red, green, and blue anodes) to any GPIO pins.
+///
+/// All three channels are driven from a single background thread, so during a fade they change
+/// in lockstep and the LED passes through the intermediate colors of the fade.
+pub struct RGBLED {
+    channels: Arc<Mutex<Vec<OutputDevice>>>,
+    color: Color,
+    blinking: Arc<AtomicBool>,
+    handle: ThreadGuard,
+    remaining_blinks: Arc<AtomicI32>,
+    fade_fps: f32,
+    timing: TimingStrategy,
+}
+
+// Writes the steps of a color fade to the three channels of an RGBLED. Each value written
+// is how far the fade is from `from` (0.0) to `to` (1.0), and all channels are set from it at once.
+struct ColorSink<'a> {
+    channels: MutexGuard<'a, Vec<OutputDevice>>,
+    from: Color,
+    to: Color,
+    running: &'a AtomicBool,
+}
+
+impl<'a> Sink for ColorSink<'a> {
+    fn write(&mut self, value: f64) {
+        let from = [self.from.0, self.from.1, self.from.2];
+        let to = [self.to.0, self.to.1, self.to.2];
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            let duty = from[i] + (to[i] - from[i]) * value;
+            // give up on the fade rather than panic, as PwmSink does
+            if let Err(e) = with_retry(retry_attempts(), RETRY_DELAY, || {
+                channel.pin.set_pwm_frequency(DEFAULT_PWM_FREQUENCY, duty)
+            }) {
+                println!("Could not set pwm for pin, stopping: {}", e);
+                self.running.store(false, Ordering::SeqCst);
+                return;
+            }
+        }
+    }
+}
+
+// Check that each component of a color is between 0.0 and 1.0
+fn is_valid_color(color: Color) -> bool {
+    [color.0, color.1, color.2]
+        .iter()
+        .all(|c| (0.0..=1.0).contains(c))
+}
+
+impl RGBLED {
+    /// Returns an RGBLED with the pin numbers given, which is turned off
+    ///
+    /// * `red` - The GPIO pin which the red anode is attached to
+    /// * `green` - The GPIO pin which the green anode is attached to
+    /// * `blue` - The GPIO pin which the blue anode is attached to
+    ///
+    pub fn new(red: u8, green: u8, blue: u8) -> RGBLED {
+        RGBLED::try_new(red, green, blue).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an RGBLED with the pin numbers given, which is turned off, or a `GpioZeroError`
+    /// if a pin can't be accessed. Pins acquired before the failure are released.
+    pub fn try_new(red: u8, green: u8, blue: u8) -> Result<RGBLED, GpioZeroError> {
+        let blinking = Arc::new(AtomicBool::new(false));
+        Ok(RGBLED {
+            channels: Arc::new(Mutex::new(vec![
+                OutputDevice::claim(red, "RGBLED")?,
+                OutputDevice::claim(green, "RGBLED")?,
+                OutputDevice::claim(blue, "RGBLED")?,
+            ])),
+            color: (0.0, 0.0, 0.0),
+            handle: ThreadGuard::new(Arc::clone(&blinking)),
+            blinking,
+            remaining_blinks: Arc::new(AtomicI32::new(0)),
+            fade_fps: DEFAULT_FADE_FPS,
+            timing: TimingStrategy::default(),
+        })
+    }
+
+    /// Set the color of the LED, stopping any blink or fade.
+    /// * `color` - The red, green and blue components, each between 0.0 and 1.0
+    pub fn set_color(&mut self, color: Color) {
+        if !is_valid_color(color) {
+            println!("color components must be between 0.0 and 1.0");
+            return;
+        }
+        self.stop();
+        ColorSink {
+            channels: lock_channels(&self.channels),
+            from: color,
+            to: color,
+            running: &self.blinking,
+        }
+        .write(0.0);
+        self.color = color;
+    }
+
+    /// Returns the color the LED was last set to, or is fading to.
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// Turns the LED on, making it white.
+    pub fn on(&mut self) {
+        self.set_color((1.0, 1.0, 1.0))
+    }
+
+    /// Turns the LED off.
+    pub fn off(&mut self) {
+        self.set_color((0.0, 0.0, 0.0))
+    }
+
+    /// Returns ``True`` if any of the channels are on.
+    pub fn is_lit(&self) -> bool {
+        self.blinking.load(Ordering::SeqCst) || self.color != (0.0, 0.0, 0.0)
+    }
+
+    /// Returns ``True`` if any of the channels are on. This is the same as `is_lit`.
+    pub fn is_active(&self) -> bool {
+        self.is_lit()
+    }
+
+    /// Fade from the current color to `target` in the background, then stay there.
+    /// * `target` - The color to fade to
+    /// * `duration` - How long the fade takes
+    pub fn fade_to_color(&mut self, target: Color, duration: Duration) {
+        if !is_valid_color(target) {
+            println!("color components must be between 0.0 and 1.0");
+            return;
+        }
+        let steps = (self.fade_fps * duration.as_secs_f32()) as i32;
+        let mut sequence: Vec<(f32, f32)> = (0..steps)
+            .map(|i| (i as f32 / steps as f32, 1.0 / self.fade_fps))
+            .collect();
+        sequence.push((1.0, 0.0));
+        let from = self.color;
+        self.play_sequence(sequence, from, target, Some(1));
+        self.color = target;
+    }
+
+    /// Make the LED fade between two colors repeatedly in the background.
+    /// * `on_time` - Number of seconds at `on_color`
+    /// * `off_time` - Number of seconds at `off_color`
+    /// * `fade_in_time` - Number of seconds to spend fading from `off_color` to `on_color`
+    /// * `fade_out_time` - Number of seconds to spend fading from `on_color` to `off_color`
+    /// * `on_color` - The color when on
+    /// * `off_color` - The color when off
+    /// * `n` - Number of times to blink, or `None` to blink until stopped
+    #[allow(clippy::too_many_arguments)]
+    pub fn blink(
+        &mut self,
+        on_time: f32,
+        off_time: f32,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        on_color: Color,
+        off_color: Color,
+        n: Option<i32>,
+    ) {
+        if !is_valid_color(on_color) || !is_valid_color(off_color) {
+            println!("color components must be between 0.0 and 1.0");
+            return;
+        }
+        let sequence = fade_sequence(
+            self.fade_fps,
+            0.0,
+            1.0,
+            on_time,
+            off_time,
+            fade_in_time,
+            fade_out_time,
+        );
+        self.play_sequence(sequence, off_color, on_color, n);
+        self.color = off_color;
+    }
+
+    /// Make the LED fade between two colors repeatedly in the background, without holding either.
+    /// * `fade_in_time` - Number of seconds to spend fading from `off_color` to `on_color`
+    /// * `fade_out_time` - Number of seconds to spend fading from `on_color` to `off_color`
+    /// * `on_color` - The color to fade in to
+    /// * `off_color` - The color to fade out to
+    /// * `n` - Number of times to pulse, or `None` to pulse until stopped
+    pub fn pulse(
+        &mut self,
+        fade_in_time: f32,
+        fade_out_time: f32,
+        on_color: Color,
+        off_color: Color,
+        n: Option<i32>,
+    ) {
+        self.blink(
+            0.0,
+            0.0,
+            fade_in_time,
+            fade_out_time,
+            on_color,
+            off_color,
+            n,
+        )
+    }
+
+    // Play `sequence` in the background, where each value is how far the LED is from `from` to `to`
+    fn play_sequence(&mut self, sequence: Vec<(f32, f32)>, from: Color, to: Color, n: Option<i32>) {
+        self.stop();
+        if matches!(n, Some(n) if n <= 0) {
+            return;
+        }
+
+        let channels = Arc::clone(&self.channels);
+        let blinking = Arc::clone(&self.blinking);
+        let remaining_blinks = Arc::clone(&self.remaining_blinks);
+        let timing = self.timing;
+
+        remaining_blinks.store(n.unwrap_or(-1), Ordering::SeqCst);
+        blinking.store(true, Ordering::SeqCst);
+        self.handle.spawn(move || {
+            let mut sink = ColorSink {
+                channels: lock_channels(&channels),
+                from,
+                to,
+                running: &blinking,
+            };
+            let finished = play(
+                &mut StoppableClock::new(timing, &blinking),
+                &mut sink,
+                &sequence,
+                n,
+                &blinking,
+                &remaining_blinks,
+            );
+            if finished {
+                blinking.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+
+    /// Returns the number of blinks left in a bounded blink, or `None` if the LED is blinking continuously.
+    pub fn remaining_blinks(&self) -> Option<i32> {
+        remaining_blinks(&self.remaining_blinks)
+    }
+
+    /// Set how many steps per second a fade takes, which takes effect from the next blink or fade.
+    /// * `fps` - Number of steps per second, 25 by default
+    pub fn set_fade_fps(&mut self, fps: f32) {
+        if fps <= 0.0 {
+            println!("fade fps must be greater than 0");
+        } else {
+            self.fade_fps = fps;
+        }
+    }
+
+    /// Set how the LED waits between the steps of a blink or fade, which takes effect from the next blink.
+    pub fn set_timing_strategy(&mut self, strategy: TimingStrategy) {
+        self.timing = strategy;
+    }
+
+    /// Stop any blink or fade running in the background, leaving the LED at its current color.
+    pub fn stop(&mut self) {
+        self.blinking.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Block until the blink or fade running in the background is done.
+    /// Returns immediately if nothing is running.
+    pub fn wait(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+impl Drop for RGBLED {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Lock the channels of an RGBLED, even if a background thread panicked while holding them
+fn lock_channels(channels: &Mutex<Vec<OutputDevice>>) -> MutexGuard<'_, Vec<OutputDevice>> {
+    channels.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Represents a PWM pin used as a simple digital to analog converter.
+///
+/// The output is only analog once it is smoothed by an RC low-pass filter between the pin and the load,
+/// with a time constant well above the 10ms period of the software PWM. The voltage reported by `voltage`
+/// is an estimate of the filtered output, assuming the pin swings between 0V and `vref` and the load draws
+/// negligible current from the filter.
+pub struct AnalogOutputDevice {
+    device: PWMOutputDevice,
+    vref: f64,
+}
+
+impl AnalogOutputDevice {
+    /// Returns an AnalogOutputDevice with the pin number and reference voltage given
+    ///
+    /// * `pin` - The GPIO pin which the filter is attached to
+    /// * `vref` - The voltage of the pin when it is high, e.g. 3.3
+    ///
+    pub fn new(pin: u8, vref: f64) -> AnalogOutputDevice {
+        AnalogOutputDevice::try_new(pin, vref).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an AnalogOutputDevice with the pin number and reference voltage given,
+    /// `GpioZeroError::InvalidArgument` if `vref` isn't greater than 0, or a `GpioZeroError`
+    /// if the pin can't be accessed
+    pub fn try_new(pin: u8, vref: f64) -> Result<AnalogOutputDevice, GpioZeroError> {
+        if vref <= 0.0 {
+            return Err(GpioZeroError::InvalidArgument(
+                "vref must be greater than 0".to_string(),
+            ));
+        }
+        Ok(AnalogOutputDevice {
+            device: PWMOutputDevice::try_new(pin)?,
+            vref,
+        })
+    }
+
+    /// Set the output voltage by setting the duty cycle to `voltage / vref`.
+    /// Voltages outside of 0.0 - `vref` are clamped.
+    /// * `voltage` - The voltage to output, in volts
+    pub fn set_voltage(&mut self, voltage: f64) {
+        self.device
+            .set_value(voltage.clamp(0.0, self.vref) / self.vref);
+    }
+
+    /// Returns the estimated output voltage of the filter, in volts.
+    pub fn voltage(&self) -> f64 {
+        self.device.value() * self.vref
+    }
+
+    /// Returns the reference voltage the output is scaled to.
+    pub fn vref(&self) -> f64 {
+        self.vref
+    }
+
+    /// Set the duty cycle directly. 0.0 is 0V, 1.0 is `vref`.
+    pub fn set_value(&mut self, value: f64) {
+        self.device.set_value(value);
+    }
+
+    /// Returns the duty cycle the device was last set to.
+    pub fn value(&self) -> f64 {
+        self.device.value()
+    }
+
+    /// Returns ``True`` if the output is above 0V and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.value() > 0.0
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+macro_rules! impl_digital_value_device {
+    ($($t:ty),*) => {
+        $(
+            impl ValueDevice for $t {
+                fn value(&self) -> f64 {
+                    if <$t>::value(self) {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+
+                fn set_value(&mut self, value: f64) {
+                    if value > 0.0 {
+                        self.on()
+                    } else {
+                        self.off()
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_digital_value_device!(OutputDevice, DigitalOutputDevice, LED, Buzzer);
+
+impl ValueDevice for PWMOutputDevice {
+    fn value(&self) -> f64 {
+        PWMOutputDevice::value(self)
+    }
+
+    fn set_value(&mut self, value: f64) {
+        PWMOutputDevice::set_value(self, value)
+    }
+}
+
+impl ValueDevice for HardwarePWMOutputDevice {
+    fn value(&self) -> f64 {
+        HardwarePWMOutputDevice::value(self)
+    }
+
+    fn set_value(&mut self, value: f64) {
+        HardwarePWMOutputDevice::set_value(self, value)
+    }
+}
+
+impl ValueDevice for PWMLED {
+    fn value(&self) -> f64 {
+        PWMLED::value(self)
+    }
+
+    fn set_value(&mut self, value: f64) {
+        PWMLED::set_value(self, value)
+    }
+}
+
+macro_rules! impl_output_device_trait {
+    ($($t:ty),*) => {
+        $(
+            impl OutputDeviceTrait for $t {
+                fn on(&mut self) {
+                    <$t>::on(self)
+                }
+
+                fn off(&mut self) {
+                    <$t>::off(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_output_device_trait!(
+    OutputDevice,
+    DigitalOutputDevice,
+    LED,
+    Buzzer,
+    PWMOutputDevice,
+    PWMLED,
+    HardwarePWMOutputDevice
+);
+
+impl_device_trait!(
+    OutputDevice,
+    DigitalOutputDevice,
+    LED,
+    Buzzer,
+    PWMOutputDevice,
+    PWMLED,
+    RGBLED,
+    AnalogOutputDevice,
+    LEDBoard,
+    Energenie,
+    HardwarePWMOutputDevice,
+    Motor,
+    ContinuousServo,
+    StatusIndicator,
+    TonalBuzzer
+);
+
+/// Represents a collection of LEDs which can be controlled together.
+/// The LEDs can be created by the board, or configured individually beforehand
+/// (e.g. mixing active-high and active-low LEDs) and handed to `LEDBoard::from_leds`.
+#[derive(Debug)]
+pub struct LEDBoard {
+    leds: Vec<LED>,
+}
+
+impl LEDBoard {
+    /// Returns an LEDBoard with an LED attached to each of the pin numbers given
+    /// * `pins` - The GPIO pins which the LEDs are attached to
+    pub fn new(pins: &[u8]) -> LEDBoard {
+        LEDBoard::try_new(pins).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an LEDBoard with an LED attached to each of the pin numbers given, or
+    /// a `GpioZeroError` if a pin can't be accessed. Pins acquired before the failure are released.
+    pub fn try_new(pins: &[u8]) -> Result<LEDBoard, GpioZeroError> {
+        Ok(LEDBoard {
+            leds: pins
+                .iter()
+                .map(|pin| LED::try_new(*pin))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Returns an LEDBoard made of the LEDs given
+    /// * `leds` - The LEDs on the board, with their own configuration
+    pub fn from_leds(leds: Vec<LED>) -> LEDBoard {
+        LEDBoard { leds }
+    }
+
+    /// Turns all the LEDs on, respecting the `active_high` setting of each LED.
+    pub fn on(&self) {
+        for led in &self.leds {
+            led.on();
+        }
+    }
+
+    /// Turns all the LEDs off, respecting the `active_high` setting of each LED.
+    pub fn off(&self) {
+        for led in &self.leds {
+            led.off();
+        }
+    }
+
+    /// Reverse the state of each LED.
+    pub fn toggle(&mut self) {
+        for led in &mut self.leds {
+            led.toggle();
+        }
+    }
+
+    /// Returns ``True`` if any of the LEDs is currently active and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.leds.iter().any(|led| led.is_active())
+    }
+
+    /// Returns whether each LED is currently active, in order.
+    pub fn value(&self) -> Vec<bool> {
+        self.leds.iter().map(|led| led.value()).collect()
+    }
+
+    /// Returns the LEDs on the board.
+    pub fn leds(&mut self) -> &mut [LED] {
+        &mut self.leds
+    }
+
+    /// Shut down the board and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+/// The states shown by a `StatusIndicator`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Solid on
+    Ok,
+    /// Blinking slowly, once every two seconds
+    Warning,
+    /// Blinking fast, five times a second
+    Error,
+    /// Off
+    Offline,
+}
+
+// The LED behind a StatusIndicator
+enum IndicatorLed {
+    Digital(LED),
+    Pwm(PWMLED),
+}
+
+/// Represents a single LED showing the state of a program, such as whether it is
+/// running normally or has hit an error, as one of the `Status` patterns.
+///
+/// # Example
+///
+/// ```no_run
+/// use rust_gpiozero::*;
+///
+/// let mut status = StatusIndicator::new(17);
+/// status.set_state(Status::Ok);
+/// // ... lose the connection
+/// status.set_state(Status::Warning);
+/// ```
+pub struct StatusIndicator {
+    led: IndicatorLed,
+    state: Status,
+}
+
+impl StatusIndicator {
+    /// Returns a StatusIndicator with an LED attached to the pin number given, showing `Status::Offline`
+    /// * `pin` - The GPIO pin which the LED is attached to
+    pub fn new(pin: u8) -> StatusIndicator {
+        StatusIndicator::from_led(LED::new(pin))
+    }
+
+    /// Returns a StatusIndicator with an LED attached to the pin number given, showing
+    /// `Status::Offline`, or a `GpioZeroError` if the pin can't be accessed
+    pub fn try_new(pin: u8) -> Result<StatusIndicator, GpioZeroError> {
+        Ok(StatusIndicator::from_led(LED::try_new(pin)?))
+    }
+
+    /// Returns a StatusIndicator showing its states on the LED given, showing `Status::Offline`.
+    /// A change between the two blinking states takes effect at the end of the current blink,
+    /// so the LED never flickers off in between.
+    /// * `led` - The LED, with its own configuration
+    pub fn from_led(led: LED) -> StatusIndicator {
+        led.off();
+        StatusIndicator {
+            led: IndicatorLed::Digital(led),
+            state: Status::Offline,
+        }
+    }
+
+    /// Returns a StatusIndicator showing its states on the PWMLED given, showing `Status::Offline`.
+    /// A PWMLED breathes in and out for `Status::Warning` instead of blinking, and starts
+    /// its new pattern straight away when the state changes.
+    /// * `led` - The PWMLED, with its own configuration
+    pub fn from_pwmled(mut led: PWMLED) -> StatusIndicator {
+        led.off();
+        StatusIndicator {
+            led: IndicatorLed::Pwm(led),
+            state: Status::Offline,
+        }
+    }
+
+    /// Show `state` on the LED. Setting the state it is already in does nothing, so it can be
+    /// called every time the state is checked without restarting the blink.
+    pub fn set_state(&mut self, state: Status) {
+        if state == self.state {
+            return;
+        }
+        self.state = state;
+        match &mut self.led {
+            IndicatorLed::Digital(led) => match state {
+                Status::Ok => led.on(),
+                Status::Warning => led.reblink(1.0, 1.0, None),
+                Status::Error => led.reblink(0.1, 0.1, None),
+                Status::Offline => led.off(),
+            },
+            IndicatorLed::Pwm(led) => match state {
+                Status::Ok => led.on(),
+                Status::Warning => led.pulse(1.0, 1.0),
+                Status::Error => led.blink(0.1, 0.1, 0.0, 0.0),
+                Status::Offline => led.off(),
+            },
+        }
+    }
+
+    /// Returns the state shown on the LED.
+    pub fn state(&self) -> Status {
+        self.state
+    }
+
+    /// Returns ``True`` unless the indicator is showing `Status::Offline`.
+    pub fn is_active(&self) -> bool {
+        self.state != Status::Offline
+    }
+
+    /// Shut down the indicator and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+// The pins of the Pi-mote board, shared by every `Energenie` socket in the process, like
+// gpiozero's `_EnergenieMaster`. Transmitting a code holds the lock, so sockets switched from
+// different threads don't garble each other's codes.
+#[derive(Debug)]
+struct EnergenieMaster {
+    signal: Vec<OutputDevice>,
+    // only held to keep the modulation select pin low
+    _mode: OutputDevice,
+    enable: OutputDevice,
+}
+
+// The board in use, which is released once the last socket is dropped
+static ENERGENIE_MASTER: Mutex<Weak<Mutex<EnergenieMaster>>> = Mutex::new(Weak::new());
+
+impl EnergenieMaster {
+    // Returns the board in use by other sockets, or claims its pins if there are none
+    fn get() -> Result<Arc<Mutex<EnergenieMaster>>, GpioZeroError> {
+        let mut master = ENERGENIE_MASTER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(master) = master.upgrade() {
+            return Ok(master);
+        }
+        let mut mode = OutputDevice::claim(24, "Energenie")?;
+        mode.off();
+        let mut enable = OutputDevice::claim(25, "Energenie")?;
+        enable.off();
+        let shared = Arc::new(Mutex::new(EnergenieMaster {
+            signal: [17, 22, 23, 27]
+                .iter()
+                .map(|pin| OutputDevice::claim(*pin, "Energenie"))
+                .collect::<Result<_, _>>()?,
+            _mode: mode,
+            enable,
+        }));
+        *master = Arc::downgrade(&shared);
+        Ok(shared)
+    }
+
+    // Transmit a four bit code
+    fn transmit(&mut self, code: u8) {
+        for (bit, pin) in self.signal.iter_mut().enumerate() {
+            if code & (1 << bit) != 0 {
+                pin.on();
+            } else {
+                pin.off();
+            }
+        }
+        // give the encoder time to settle before transmitting
+        thread::sleep(Duration::from_millis(100));
+        self.enable.on();
+        thread::sleep(Duration::from_millis(250));
+        self.enable.off();
+    }
+}
+
+/// Represents a socket controlled by the Energenie Pi-mote RF transmitter board.
+///
+/// The board is controlled by writing a four bit code to GPIO 17, 22, 23 and 27,
+/// then enabling the modulator on GPIO 25 to transmit it. GPIO 24 selects the
+/// modulation and is held low for ASK. These pins are shared by all the sockets, so
+/// an Energenie can be created for each of the four sockets at once.
+#[derive(Debug)]
+pub struct Energenie {
+    socket: u8,
+    master: Arc<Mutex<EnergenieMaster>>,
+    value: bool,
+}
+
+impl Energenie {
+    /// Returns an Energenie for the socket given, which is turned off
+    ///
+    /// * `socket` - The number of the socket to control, from 1 to 4
+    ///
+    pub fn new(socket: u8) -> Energenie {
+        Energenie::try_new(socket).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an Energenie for the socket given, which is turned off,
+    /// `GpioZeroError::InvalidArgument` if the socket isn't between 1 and 4, or
+    /// a `GpioZeroError` if the board's pins can't be accessed
+    pub fn try_new(socket: u8) -> Result<Energenie, GpioZeroError> {
+        if !(1..=4).contains(&socket) {
+            return Err(GpioZeroError::InvalidArgument(
+                "socket must be between 1 and 4".to_string(),
+            ));
+        }
+        let mut energenie = Energenie {
+            socket,
+            master: EnergenieMaster::get()?,
+            value: false,
+        };
+        energenie.off();
+        Ok(energenie)
+    }
+
+    // Transmit the code for switching the socket on or off
+    fn write_state(&mut self, value: bool) {
+        // bits 0 to 2 select the socket, counting down from 7 for socket 1, and bit 3 is on/off
+        let code = (8 - self.socket) | if value { 0b1000 } else { 0 };
+        self.master
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .transmit(code);
+        self.value = value;
+    }
+
+    /// Turns the socket on.
+    pub fn on(&mut self) {
+        self.write_state(true)
+    }
+
+    /// Turns the socket off.
+    pub fn off(&mut self) {
+        self.write_state(false)
+    }
+
+    /// Returns ``True`` if the socket was last turned on and ``False`` otherwise.
+    /// The sockets don't report their state, so this may be wrong if the socket was
+    /// switched by another transmitter.
+    pub fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Returns ``True`` if the socket was last turned on and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.value
+    }
+
+    /// Returns the number of the socket being controlled.
+    pub fn socket(&self) -> u8 {
+        self.socket
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+/// How often a hardware PWM fade steps the duty cycle, in microseconds
+const HARDWARE_FADE_STEP_MICROS: u64 = 1000;
+
+/// Generic output device driven by one of the Raspberry Pi's hardware PWM channels.
+/// Unlike `PWMOutputDevice` the signal is generated by the hardware, so it is stable at any
+/// frequency, but only GPIO 12, 13, 18 and 19 are supported (see `supports_hardware_pwm`).
+/// The PWM channels need to be enabled with the `pwm-2chan` device tree overlay.
+#[derive(Debug)]
+pub struct HardwarePWMOutputDevice {
+    pwm: Pwm,
+    pin: u8,
+    value: f64,
+    _reservation: Reservation,
+}
+
+impl HardwarePWMOutputDevice {
+    /// Returns a HardwarePWMOutputDevice with the pin number given and a frequency of 100 Hz
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to. Must support hardware PWM.
+    ///
+    pub fn new(pin: u8) -> HardwarePWMOutputDevice {
+        HardwarePWMOutputDevice::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a HardwarePWMOutputDevice with the pin number given and a frequency of 100 Hz,
+    /// `GpioZeroError::InvalidArgument` if the pin doesn't support hardware PWM, or
+    /// `GpioZeroError::Pwm` if the PWM channel can't be accessed, e.g. because it isn't enabled
+    pub fn try_new(pin: u8) -> Result<HardwarePWMOutputDevice, GpioZeroError> {
+        if !supports_hardware_pwm(pin) {
+            return Err(GpioZeroError::InvalidArgument(format!(
+                "GPIO {} does not support hardware PWM, use GPIO 12, 13, 18 or 19 instead",
+                pin
+            )));
+        }
+        let channel = match pin {
+            12 | 18 => Channel::Pwm0,
+            _ => Channel::Pwm1,
+        };
+        let reservation = Reservation::new(LOCAL_PINS.to_string(), pin, "HardwarePWMOutputDevice")?;
+        let pwm = Pwm::with_frequency(channel, 100.0, 0.0, Polarity::Normal, true)?;
+        Ok(HardwarePWMOutputDevice {
+            pwm,
+            pin,
+            value: 0.0,
+            _reservation: reservation,
+        })
+    }
+
+    /// Set the duty cycle of the PWM device. 0.0 is off, 1.0 is fully on.
+    /// Values in between may be specified for varying levels of power in the device.
+    pub fn set_value(&mut self, value: f64) {
+        if let Err(e) = self.try_set_value(value) {
+            println!("{}", e);
+        }
     }
-}
 
-/// Represents a light emitting diode (LED) with variable brightness.
-/// A typical configuration of such a device is to connect a GPIO pin
-/// to the anode (long leg) of the LED, and the cathode (short leg) to ground,
-/// with an optional resistor to prevent the LED from burning out.
-pub struct PWMLED(PWMOutputDevice);
+    /// Set the duty cycle like `set_value`, returning `GpioZeroError::InvalidArgument` if `value`
+    /// is outside 0.0 to 1.0, or `GpioZeroError::Pwm` if the duty cycle couldn't be written.
+    pub fn try_set_value(&mut self, value: f64) -> Result<(), GpioZeroError> {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(GpioZeroError::InvalidArgument(
+                "value must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+        self.pwm.set_duty_cycle(value)?;
+        self.value = value;
+        Ok(())
+    }
 
-impl PWMLED {
-    /// Returns a PMWLED with the pin number given
-    ///
-    /// * `pin` - The GPIO pin which the device is attached to
-    ///    
-    pub fn new(pin: u8) -> PWMLED {
-        PWMLED(PWMOutputDevice::new(pin))
+    /// Returns the duty cycle the device was last set to, between 0.0 (off) and 1.0 (fully on).
+    pub fn value(&self) -> f64 {
+        self.value
     }
 
-    /// Make the device turn on and off repeatedly
-    /// * `on_time` - Number of seconds on
-    /// * `off_time` - Number of seconds off
-    /// * `fade_in_time` - Number of seconds to spend fading in
-    /// * `fade_out_time` - Number of seconds to spend fading out
-    ///
-    pub fn blink(&mut self, on_time: f32, off_time: f32, fade_in_time: f32, fade_out_time: f32) {
-        self.0.blink(on_time, off_time, fade_in_time, fade_out_time)
+    /// Returns ``True`` if the device is on and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.value > 0.0
     }
 
     /// Turns the device on.
     pub fn on(&mut self) {
-        self.0.on();
+        self.set_value(1.0)
     }
 
     /// Turns the device off.
     pub fn off(&mut self) {
-        self.0.off();
+        self.set_value(0.0)
     }
 
-    /// Make the device fade in and out repeatedly.
+    /// Fade from the current duty cycle to `target`, blocking until the fade is done.
+    /// The duty cycle is stepped every millisecond, timed with a busy wait at the end of each step,
+    /// and each step is output by the hardware without jitter. This gives a much smoother fade than
+    /// `PWMOutputDevice::fade_to`, whose 25 steps a second are also subject to jitter in the
+    /// software PWM under CPU load, at the cost of blocking and keeping a CPU core busy.
+    /// * `target` - The duty cycle to fade to, between 0.0 and 1.0
+    /// * `duration` - How long the fade takes
+    pub fn fade_to(&mut self, target: f64, duration: Duration) {
+        if !(0.0..=1.0).contains(&target) {
+            println!("target must be between 0.0 and 1.0");
+            return;
+        }
+        let start = Instant::now();
+        let from = self.value;
+        let steps = (duration.as_micros() as u64 / HARDWARE_FADE_STEP_MICROS).max(1);
+        for step in 1..=steps {
+            let value = from + (target - from) * step as f64 / steps as f64;
+            if self.pwm.set_duty_cycle(value).is_err() {
+                println!("Failed to set duty cycle");
+                return;
+            }
+            self.value = value;
+            let deadline = Duration::from_micros(step * HARDWARE_FADE_STEP_MICROS);
+            if let Some(remaining) = deadline.checked_sub(start.elapsed()) {
+                sleep_micros(remaining.as_micros() as u64);
+            }
+        }
+    }
+
+    /// Make the device fade in and out `n` times, blocking until it is done.
+    /// The fades are timed as for `fade_to`.
     /// * `fade_in_time` - Number of seconds to spend fading in
     /// * `fade_out_time` - Number of seconds to spend fading out
-    ///
-    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32) {
-        self.0.pulse(fade_in_time, fade_out_time);
+    /// * `n` - Number of times to pulse
+    pub fn pulse(&mut self, fade_in_time: f32, fade_out_time: f32, n: u32) {
+        for _ in 0..n {
+            self.fade_to(1.0, Duration::from_secs_f32(fade_in_time));
+            self.fade_to(0.0, Duration::from_secs_f32(fade_out_time));
+        }
     }
 
-    /// Set the duty cycle of the PWM device. 0.0 is off, 1.0 is fully on.
-    /// Values in between may be specified for varying levels of power in the device.
-    pub fn set_value(&mut self, value: f64) {
-        self.0.set_value(value);
+    /// Set the frequency of the PWM signal in Hz, keeping the current duty cycle.
+    pub fn set_frequency(&mut self, frequency: f64) {
+        if self.pwm.set_frequency(frequency, self.value).is_err() {
+            println!("Failed to set frequency");
+        }
     }
 
-    /// Set the number of times to blink the device    
-    /// * `n` - Number of times to blink
-    pub fn set_blink_count(&mut self, n: i32) {
-        self.0.blink_count = Some(n)
+    /// Returns the frequency of the PWM signal in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.pwm.frequency().unwrap_or(0.0)
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
     }
 }
 
@@ -622,12 +3644,19 @@ impl Motor {
     /// * `forward_pin` - The GPIO pin that the forward input of the motor driver chip is connected to
     /// * `backward` - The GPIO pin that the backward input of the motor driver chip is connected to
     pub fn new(forward_pin: u8, backward_pin: u8) -> Motor {
-        let forward = PWMOutputDevice::new(forward_pin);
-        let backward = PWMOutputDevice::new(backward_pin);
-        Motor {
+        Motor::try_new(forward_pin, backward_pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a Motor on the pins given, or a `GpioZeroError` if a pin can't be accessed
+    /// * `forward_pin` - The GPIO pin that the forward input of the motor driver chip is connected to
+    /// * `backward_pin` - The GPIO pin that the backward input of the motor driver chip is connected to
+    pub fn try_new(forward_pin: u8, backward_pin: u8) -> Result<Motor, GpioZeroError> {
+        let forward = PWMOutputDevice::try_new(forward_pin)?;
+        let backward = PWMOutputDevice::try_new(backward_pin)?;
+        Ok(Motor {
             devices: MotorCompositeDevice(forward, backward),
             speed: 1.0,
-        }
+        })
     }
 
     /// Drive the motor forwards at the current speed.
@@ -659,15 +3688,31 @@ impl Motor {
         }
         self.speed = speed
     }
+
+    /// Returns ``True`` if the motor is turning and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.devices.0.is_active() || self.devices.1.is_active()
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
 }
 
 /// Represents a PWM-controlled servo motor connected to a GPIO pin.
 //reference :https://github.com/golemparts/rppal/blob/master/examples/gpio_servo_softpwm.rs
 pub struct Servo {
-    pin: IoPin,
+    pin: Arc<Mutex<Box<dyn IoPin>>>,
     min_pulse_width: u64,
     max_pulse_width: u64,
     frame_width: u64,
+    frame_timeout: Option<Duration>,
+    // set while a timer is waiting to stop the pulses after the last move
+    detaching: Arc<AtomicBool>,
+    // the detach timer, joined before every move so only the timer of the last move is left
+    detach: ThreadGuard,
+    _reservation: Reservation,
 }
 
 impl Servo {
@@ -677,44 +3722,38 @@ impl Servo {
     /// * `pin` - The GPIO pin which the device is attached to
     ///  
     pub fn new(pin: u8) -> Servo {
-        match Gpio::new() {
-            Err(e) => panic!("{:?}", e),
-            Ok(gpio) => match gpio.get(pin) {
-                Err(e) => panic!("{:?}", e),
-                Ok(pin) => Servo {
-                    pin: pin.into_io(Mode::Output),
-                    min_pulse_width: 1000,
-                    max_pulse_width: 2000,
-                    frame_width: 20,
-                },
-            },
-        }
+        Servo::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a Servo with the pin number given and the default pulse widths, or
+    /// a `GpioZeroError` if the pin can't be accessed
+    pub fn try_new(pin: u8) -> Result<Servo, GpioZeroError> {
+        let (reservation, pin) = claim_io(pin, "Servo", Mode::Output)?;
+        let detaching = Arc::new(AtomicBool::new(false));
+        Ok(Servo {
+            pin: Arc::new(Mutex::new(pin)),
+            min_pulse_width: 1000,
+            max_pulse_width: 2000,
+            frame_width: 20,
+            frame_timeout: None,
+            detach: ThreadGuard::new(Arc::clone(&detaching)),
+            detaching,
+            _reservation: reservation,
+        })
     }
 
+    impl_pin_numbering!();
+
     /// Set the servo to its minimum position.
     pub fn min(&mut self) {
-        if self
-            .pin
-            .set_pwm(
-                Duration::from_millis(self.frame_width),
-                Duration::from_micros(self.min_pulse_width),
-            )
-            .is_err()
-        {
+        if !self.write_pulse(self.min_pulse_width) {
             println!("Failed to set servo to minimum position")
         }
     }
 
     /// Set the servo to its maximum position.
     pub fn max(&mut self) {
-        if self
-            .pin
-            .set_pwm(
-                Duration::from_millis(self.frame_width),
-                Duration::from_micros(self.max_pulse_width),
-            )
-            .is_err()
-        {
+        if !self.write_pulse(self.max_pulse_width) {
             println!("Failed to set servo to maximum position")
         }
     }
@@ -722,18 +3761,48 @@ impl Servo {
     /// Set the servo to its neutral position.
     pub fn mid(&mut self) {
         let mid_value = (self.min_pulse_width + self.max_pulse_width) / 2;
-        if self
-            .pin
-            .set_pwm(
-                Duration::from_millis(self.frame_width),
-                Duration::from_micros(mid_value),
-            )
-            .is_err()
-        {
+        if !self.write_pulse(mid_value) {
             println!("Failed to set servo to neutral position")
         }
     }
 
+    // Start sending pulses of the given width, in microseconds, and stop them again
+    // after the frame timeout. Returns ``False`` if the pulses couldn't be set.
+    fn write_pulse(&mut self, pulse_width: u64) -> bool {
+        // cancel the timer of the previous move, so it can't stop the pulses of this one
+        self.detach.stop();
+        let result = lock_pin(&self.pin).set_pwm(
+            Duration::from_millis(self.frame_width),
+            Duration::from_micros(pulse_width),
+        );
+        if let Some(timeout) = self.frame_timeout {
+            let pin = Arc::clone(&self.pin);
+            let detaching = Arc::clone(&self.detaching);
+            self.detach.spawn(move || {
+                StoppableClock::new(TimingStrategy::Sleep, &detaching).sleep(timeout);
+                if detaching.load(Ordering::SeqCst) {
+                    let mut pin = lock_pin(&pin);
+                    let _ = pin.clear_pwm();
+                    pin.set_low();
+                }
+            });
+        }
+        result.is_ok()
+    }
+
+    /// Stop sending pulses after each move once `timeout` has passed, letting the servo go slack.
+    /// This stops a stationary servo from jittering and drawing current, at the cost of it no longer
+    /// holding its position against a load. The next move starts the pulses again.
+    /// * `timeout` - How long to keep sending pulses after a move, or `None` to send them continuously
+    pub fn set_frame_timeout(&mut self, timeout: Option<Duration>) {
+        self.frame_timeout = timeout;
+    }
+
+    /// Get how long pulses are sent for after a move, or `None` if they are sent continuously
+    pub fn get_frame_timeout(&self) -> Option<Duration> {
+        self.frame_timeout
+    }
+
     /// Set the servo's minimum pulse width
     pub fn set_min_pulse_width(&mut self, value: u64) {
         if value >= self.max_pulse_width {
@@ -745,16 +3814,60 @@ impl Servo {
 
     /// Set the servo's maximum pulse width
     pub fn set_max_pulse_width(&mut self, value: u64) {
-        if value >= self.frame_width {
+        if value >= self.frame_width * 1000 {
             println!("max_pulse_width must be less than frame_width");
+        } else if value <= self.min_pulse_width {
+            println!("max_pulse_width must be greater than min_pulse_width");
         } else {
             self.max_pulse_width = value;
         }
     }
 
+    /// Set the servo's minimum and maximum pulse widths, in microseconds.
+    /// Both must be greater than zero and less than the frame width, and `min` must be less than `max`.
+    pub fn set_pulse_widths(&mut self, min: u64, max: u64) -> Result<(), GpioZeroError> {
+        if min == 0 || max == 0 {
+            return Err(GpioZeroError::InvalidArgument(
+                "pulse widths must be greater than 0".to_string(),
+            ));
+        }
+        if min >= max {
+            return Err(GpioZeroError::InvalidArgument(
+                "min_pulse_width must be less than max_pulse_width".to_string(),
+            ));
+        }
+        if max >= self.frame_width * 1000 {
+            return Err(GpioZeroError::InvalidArgument(
+                "pulse widths must be less than frame_width".to_string(),
+            ));
+        }
+        self.min_pulse_width = min;
+        self.max_pulse_width = max;
+        Ok(())
+    }
+
+    /// Set the position of the servo, from -1.0 (minimum position) to 1.0 (maximum position).
+    /// Values outside of this range are clamped, so the pulse width always stays
+    /// between `min_pulse_width` and `max_pulse_width`.
+    pub fn set_value(&mut self, value: f64) {
+        let value = value.clamp(-1.0, 1.0);
+        let min = self.min_pulse_width as f64;
+        let max = self.max_pulse_width as f64;
+        let pulse_width = ((min + (value + 1.0) / 2.0 * (max - min)).round() as u64)
+            .clamp(self.min_pulse_width, self.max_pulse_width);
+        if !self.write_pulse(pulse_width) {
+            println!("Failed to set servo to {}", value)
+        }
+    }
+
     /// Set the servo's frame width(The time between control pulses, measured in milliseconds.)
+    /// The frame must be longer than `max_pulse_width`, or the pulses would never end.
     pub fn set_frame_width(&mut self, value: u64) {
-        self.frame_width = value;
+        if value * 1000 <= self.max_pulse_width {
+            println!("frame_width must be greater than max_pulse_width");
+        } else {
+            self.frame_width = value;
+        }
     }
 
     /// Get the servo's minimum pulse width
@@ -772,3 +3885,379 @@ impl Servo {
         self.frame_width
     }
 }
+
+impl Drop for Servo {
+    fn drop(&mut self) {
+        // cancel any pending detach timer, then stop the pulses straight away
+        self.detach.stop();
+        let mut pin = lock_pin(&self.pin);
+        let _ = pin.clear_pwm();
+        pin.set_low();
+    }
+}
+
+/// Represents a rotational PWM-controlled servo motor which can be set to particular angles
+/// (assuming valid minimum and maximum angles are provided).
+pub struct AngularServo {
+    servo: Servo,
+    min_angle: f64,
+    max_angle: f64,
+    trim: i64,
+    angle: Option<f64>,
+}
+
+impl AngularServo {
+    /// Returns an AngularServo with the pin number given with default `min_angle` of -90,
+    /// `max_angle` of 90 and no trim
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    ///
+    pub fn new(pin: u8) -> AngularServo {
+        AngularServo::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns an AngularServo with the pin number given and the default angles, or
+    /// a `GpioZeroError` if the pin can't be accessed
+    pub fn try_new(pin: u8) -> Result<AngularServo, GpioZeroError> {
+        Ok(AngularServo {
+            servo: Servo::try_new(pin)?,
+            min_angle: -90.0,
+            max_angle: 90.0,
+            trim: 0,
+            angle: None,
+        })
+    }
+
+    impl_pin_numbering!();
+
+    /// Set the servo to the given angle, in degrees.
+    /// The angle is clamped to the range between `min_angle` and `max_angle`
+    /// and mapped linearly onto the servo's pulse width range.
+    pub fn set_angle(&mut self, angle: f64) {
+        let (low, high) = if self.min_angle <= self.max_angle {
+            (self.min_angle, self.max_angle)
+        } else {
+            (self.max_angle, self.min_angle)
+        };
+        let angle = angle.clamp(low, high);
+
+        let min_pulse_width = self.servo.min_pulse_width as f64;
+        let max_pulse_width = self.servo.max_pulse_width as f64;
+        let ratio = if (self.max_angle - self.min_angle).abs() > f64::EPSILON {
+            (angle - self.min_angle) / (self.max_angle - self.min_angle)
+        } else {
+            0.5
+        };
+        let pulse_width =
+            min_pulse_width + ratio * (max_pulse_width - min_pulse_width) + self.trim as f64;
+
+        if !self.servo.write_pulse(pulse_width.max(0.0) as u64) {
+            println!("Failed to set servo to angle {}", angle);
+        } else {
+            self.angle = Some(angle);
+        }
+    }
+
+    /// Returns the last angle the servo was set to, or `None` if it hasn't been set yet.
+    pub fn angle(&self) -> Option<f64> {
+        self.angle
+    }
+
+    /// Set the servo to its minimum angle.
+    pub fn min(&mut self) {
+        self.set_angle(self.min_angle)
+    }
+
+    /// Set the servo to its maximum angle.
+    pub fn max(&mut self) {
+        self.set_angle(self.max_angle)
+    }
+
+    /// Set the servo to its neutral position, halfway between `min_angle` and `max_angle`.
+    pub fn mid(&mut self) {
+        self.set_angle((self.min_angle + self.max_angle) / 2.0)
+    }
+
+    /// Set the angle the servo reaches at its minimum pulse width
+    pub fn set_min_angle(&mut self, value: f64) {
+        self.min_angle = value;
+    }
+
+    /// Set the angle the servo reaches at its maximum pulse width
+    pub fn set_max_angle(&mut self, value: f64) {
+        self.max_angle = value;
+    }
+
+    /// Get the angle the servo reaches at its minimum pulse width
+    pub fn get_min_angle(&self) -> f64 {
+        self.min_angle
+    }
+
+    /// Get the angle the servo reaches at its maximum pulse width
+    pub fn get_max_angle(&self) -> f64 {
+        self.max_angle
+    }
+
+    /// Set the servo's trim, an offset in microseconds added to every pulse width
+    /// to correct mechanical centering errors
+    pub fn set_trim(&mut self, value: i64) {
+        self.trim = value;
+    }
+
+    /// Get the servo's trim in microseconds
+    pub fn get_trim(&self) -> i64 {
+        self.trim
+    }
+
+    /// Set the servo's minimum pulse width
+    pub fn set_min_pulse_width(&mut self, value: u64) {
+        self.servo.set_min_pulse_width(value)
+    }
+
+    /// Set the servo's maximum pulse width
+    pub fn set_max_pulse_width(&mut self, value: u64) {
+        self.servo.set_max_pulse_width(value)
+    }
+
+    /// Set the servo's frame width(The time between control pulses, measured in milliseconds.)
+    pub fn set_frame_width(&mut self, value: u64) {
+        self.servo.set_frame_width(value)
+    }
+
+    /// Stop sending pulses after each move once `timeout` has passed, letting the servo go slack.
+    /// * `timeout` - How long to keep sending pulses after a move, or `None` to send them continuously
+    pub fn set_frame_timeout(&mut self, timeout: Option<Duration>) {
+        self.servo.set_frame_timeout(timeout)
+    }
+}
+
+/// Represents a continuous rotation servo, which treats the pulse width as a speed rather than
+/// a position. The neutral pulse width stops it, and pulse widths either side of it turn it
+/// one way or the other, faster the further they are from neutral.
+pub struct ContinuousServo {
+    servo: Servo,
+    value: f64,
+}
+
+impl ContinuousServo {
+    /// Returns a ContinuousServo with the pin number given with default `min_pulse_width` of 1ms,
+    /// `max_pulse_width` of 2ms and `frame_width` of 20ms
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    ///
+    pub fn new(pin: u8) -> ContinuousServo {
+        ContinuousServo::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a ContinuousServo with the pin number given and the default pulse widths, or
+    /// a `GpioZeroError` if the pin can't be accessed
+    pub fn try_new(pin: u8) -> Result<ContinuousServo, GpioZeroError> {
+        Ok(ContinuousServo {
+            servo: Servo::try_new(pin)?,
+            value: 0.0,
+        })
+    }
+
+    impl_pin_numbering!();
+
+    /// Set the speed and direction of rotation, from -1.0 (full speed backward) through
+    /// 0.0 (stopped) to 1.0 (full speed forward). Values outside of this range are clamped.
+    pub fn set_value(&mut self, value: f64) {
+        let value = value.clamp(-1.0, 1.0);
+        self.servo.set_value(value);
+        self.value = value;
+    }
+
+    /// Returns the speed and direction the servo was last set to, from -1.0 to 1.0.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Turn the servo forward.
+    /// * `speed` - The speed, between 0.0 (stopped) and 1.0 (full speed)
+    pub fn forward(&mut self, speed: f64) {
+        if !(0.0..=1.0).contains(&speed) {
+            println!("Speed must be between 0.0 and 1.0");
+            return;
+        }
+        self.set_value(speed)
+    }
+
+    /// Turn the servo backward.
+    /// * `speed` - The speed, between 0.0 (stopped) and 1.0 (full speed)
+    pub fn backward(&mut self, speed: f64) {
+        if !(0.0..=1.0).contains(&speed) {
+            println!("Speed must be between 0.0 and 1.0");
+            return;
+        }
+        self.set_value(-speed)
+    }
+
+    /// Stop the servo by sending the neutral pulse width.
+    pub fn stop(&mut self) {
+        self.set_value(0.0)
+    }
+
+    /// Returns ``True`` if the servo is turning and ``False`` otherwise.
+    pub fn is_active(&self) -> bool {
+        self.value != 0.0
+    }
+
+    /// Set the servo's minimum pulse width, which turns it backward at full speed
+    pub fn set_min_pulse_width(&mut self, value: u64) {
+        self.servo.set_min_pulse_width(value)
+    }
+
+    /// Set the servo's maximum pulse width, which turns it forward at full speed
+    pub fn set_max_pulse_width(&mut self, value: u64) {
+        self.servo.set_max_pulse_width(value)
+    }
+
+    /// Set the servo's frame width(The time between control pulses, measured in milliseconds.)
+    pub fn set_frame_width(&mut self, value: u64) {
+        self.servo.set_frame_width(value)
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(self) {
+        drop(self)
+    }
+}
+
+/// Represents a tonal buzzer, which can play notes at specific frequencies
+/// using software PWM.
+///
+/// Connect the cathode (negative pin) of the buzzer to a ground pin;
+/// connect the other side to any GPIO pin.
+pub struct TonalBuzzer {
+    device: Arc<Mutex<OutputDevice>>,
+    playing: Arc<AtomicBool>,
+    handle: ThreadGuard,
+}
+
+impl TonalBuzzer {
+    /// Returns a TonalBuzzer with the pin number given
+    ///
+    /// * `pin` - The GPIO pin which the device is attached to
+    ///
+    pub fn new(pin: u8) -> TonalBuzzer {
+        TonalBuzzer::try_new(pin).unwrap_or_else(|e| panic!("{:?}", e))
+    }
+
+    /// Returns a TonalBuzzer with the pin number given, or a `GpioZeroError` if the pin
+    /// can't be accessed
+    pub fn try_new(pin: u8) -> Result<TonalBuzzer, GpioZeroError> {
+        let device = Arc::new(Mutex::new(OutputDevice::claim(pin, "TonalBuzzer")?));
+        let playing = Arc::new(AtomicBool::new(false));
+        #[cfg(feature = "signal")]
+        crate::signal::register(&device, &playing);
+        Ok(TonalBuzzer {
+            device,
+            handle: ThreadGuard::new(Arc::clone(&playing)),
+            playing,
+        })
+    }
+
+    impl_pin_numbering!();
+
+    /// Play a tone at the given frequency until `stop` is called.
+    /// * `frequency` - The frequency of the tone in Hz
+    pub fn play(&mut self, frequency: f64) {
+        self.stop();
+        if lock_device(&self.device)
+            .pin
+            .set_pwm_frequency(frequency, 0.5)
+            .is_err()
+        {
+            println!("Failed to play tone at {} Hz", frequency);
+        } else {
+            self.playing.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Play a melody in the RTTTL format in the background.
+    /// Returns an error without playing anything if the melody is malformed.
+    /// * `rtttl` - The melody, e.g. `"Beep:d=4,o=5,b=120:8c,8e,8g,4c6"`
+    pub fn play_rtttl(&mut self, rtttl: &str) -> Result<(), crate::rtttl::ParseError> {
+        let notes = crate::rtttl::parse(rtttl)?;
+        self.stop();
+
+        let device = Arc::clone(&self.device);
+        let playing = Arc::clone(&self.playing);
+
+        self.handle.spawn(move || {
+            // `stop` clears `playing` and waits for the thread, so a note is cut short rather than
+            // left to play over whatever is played next
+            let mut clock = StoppableClock::new(TimingStrategy::Sleep, &playing);
+            for note in notes {
+                if !playing.load(Ordering::SeqCst) {
+                    break;
+                }
+                {
+                    let mut device = lock_device(&device);
+                    let result = match note.frequency {
+                        Some(frequency) => device.pin.set_pwm_frequency(frequency, 0.5),
+                        None => device.pin.clear_pwm(),
+                    };
+                    if let Err(e) = result {
+                        println!("Stopped playing melody: {}", e);
+                        break;
+                    }
+                    if note.frequency.is_none() {
+                        device.off();
+                    }
+                }
+                clock.sleep(note.duration);
+            }
+            let mut device = lock_device(&device);
+            let _ = device.pin.clear_pwm();
+            device.off();
+            playing.store(false, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+
+    /// Stop playing any tone or melody, waiting for a melody's thread to finish.
+    pub fn stop(&mut self) {
+        self.handle.stop();
+        let mut device = lock_device(&self.device);
+        if device.pin.clear_pwm().is_err() {
+            println!("Could not clear pwm for pin");
+        }
+        device.off();
+    }
+
+    /// Returns ``True`` if the buzzer is currently playing a tone or melody and ``False`` otherwise.
+    pub fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::SeqCst)
+    }
+
+    /// Returns ``True`` if the buzzer is currently playing and ``False`` otherwise. This is the same as `is_playing`.
+    pub fn is_active(&self) -> bool {
+        self.is_playing()
+    }
+
+    /// Block until the melody playing in the background is done
+    pub fn wait(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("Could not join spawned thread");
+        }
+    }
+
+    /// The `Pin` that the device is connected to.
+    pub fn pin(&self) -> u8 {
+        lock_device(&self.device).pin.pin()
+    }
+
+    /// Shut down the device and release all associated resources.
+    pub fn close(mut self) {
+        self.stop();
+        drop(self)
+    }
+}
+
+impl Drop for TonalBuzzer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,230 @@
+//! Declarative brightness patterns for PWM devices such as `PWMLED`
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut led = PWMLED::new(17);
+//!
+//! // flash twice, then breathe in and out
+//! let pattern = Pattern::seq(vec![
+//!     Pattern::repeat(2, Pattern::seq(vec![Pattern::on(0.2), Pattern::off(0.2)])),
+//!     Pattern::fade(0.0, 1.0, 1.0),
+//!     Pattern::fade(1.0, 0.0, 1.0),
+//! ]);
+//! led.play(&pattern);
+//! ```
+
+/// Number of steps per second used to approximate fades
+const FPS: f32 = 25.0;
+
+/// A sequence of brightness levels over time, built from combinators.
+/// Brightness is between 0.0 (off) and 1.0 (fully on) and durations are in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// Stay at a brightness for a number of seconds
+    Hold(f32, f32),
+    /// Fade linearly from one brightness to another over a number of seconds
+    Fade(f32, f32, f32),
+    /// Play a pattern a number of times
+    Repeat(u32, Box<Pattern>),
+    /// Play patterns one after the other
+    Seq(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// Fully on for `duration` seconds
+    pub fn on(duration: f32) -> Pattern {
+        Pattern::Hold(1.0, duration)
+    }
+
+    /// Off for `duration` seconds
+    pub fn off(duration: f32) -> Pattern {
+        Pattern::Hold(0.0, duration)
+    }
+
+    /// At brightness `value` for `duration` seconds
+    pub fn hold(value: f32, duration: f32) -> Pattern {
+        Pattern::Hold(value, duration)
+    }
+
+    /// Fade from brightness `from` to `to` over `duration` seconds
+    pub fn fade(from: f32, to: f32, duration: f32) -> Pattern {
+        Pattern::Fade(from, to, duration)
+    }
+
+    /// Play `pattern` `n` times
+    pub fn repeat(n: u32, pattern: Pattern) -> Pattern {
+        Pattern::Repeat(n, Box::new(pattern))
+    }
+
+    /// Play `patterns` one after the other
+    pub fn seq(patterns: Vec<Pattern>) -> Pattern {
+        Pattern::Seq(patterns)
+    }
+
+    /// Compiles the pattern into the list of `(brightness, seconds)` steps played by a device
+    pub fn compile(&self) -> Vec<(f32, f32)> {
+        let mut sequence = Vec::new();
+        self.compile_into(&mut sequence);
+        sequence
+    }
+
+    fn compile_into(&self, sequence: &mut Vec<(f32, f32)>) {
+        match self {
+            Pattern::Hold(value, duration) => sequence.push((*value, *duration)),
+            Pattern::Fade(from, to, duration) => {
+                let steps = (FPS * duration) as i32;
+                for i in 0..steps {
+                    sequence.push((from + (to - from) * i as f32 / steps as f32, 1.0 / FPS));
+                }
+                sequence.push((*to, 0.0));
+            }
+            Pattern::Repeat(n, pattern) => {
+                for _ in 0..*n {
+                    pattern.compile_into(sequence);
+                }
+            }
+            Pattern::Seq(patterns) => {
+                for pattern in patterns {
+                    pattern.compile_into(sequence);
+                }
+            }
+        }
+    }
+}
+
+/// Length of a Morse code dot in `BlinkPreset::SosMorse`, in seconds
+const MORSE_UNIT: f32 = 0.2;
+
+/// Ready-made blink effects, played with `play_preset` on output devices.
+/// Digital devices can't fade, so they are on wherever the preset's brightness is at least half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlinkPreset {
+    /// SOS in Morse code (`... --- ...`), followed by a pause
+    SosMorse,
+    /// Slowly fade in and out, one breath every two seconds
+    SlowPulse,
+    /// Flash on and off ten times a second
+    FastStrobe,
+    /// Two quick beats followed by a pause, like a heartbeat
+    Heartbeat,
+    /// Two short flashes followed by a pause
+    DoubleBlink,
+}
+
+impl BlinkPreset {
+    /// Returns one cycle of the preset; devices repeat it until stopped
+    pub fn pattern(self) -> Pattern {
+        match self {
+            BlinkPreset::SosMorse => {
+                let dot = Pattern::seq(vec![Pattern::on(MORSE_UNIT), Pattern::off(MORSE_UNIT)]);
+                let dash = Pattern::seq(vec![
+                    Pattern::on(3.0 * MORSE_UNIT),
+                    Pattern::off(MORSE_UNIT),
+                ]);
+                // the gap between letters is three units, one of which ends the last symbol
+                let letter_gap = Pattern::off(2.0 * MORSE_UNIT);
+                Pattern::seq(vec![
+                    Pattern::repeat(3, dot.clone()),
+                    letter_gap.clone(),
+                    Pattern::repeat(3, dash),
+                    letter_gap,
+                    Pattern::repeat(3, dot),
+                    // and the gap between words is seven
+                    Pattern::off(6.0 * MORSE_UNIT),
+                ])
+            }
+            BlinkPreset::SlowPulse => Pattern::seq(vec![
+                Pattern::fade(0.0, 1.0, 1.0),
+                Pattern::fade(1.0, 0.0, 1.0),
+            ]),
+            BlinkPreset::FastStrobe => Pattern::seq(vec![Pattern::on(0.05), Pattern::off(0.05)]),
+            BlinkPreset::Heartbeat => Pattern::seq(vec![
+                Pattern::on(0.1),
+                Pattern::off(0.1),
+                Pattern::hold(0.6, 0.1),
+                Pattern::off(0.7),
+            ]),
+            BlinkPreset::DoubleBlink => Pattern::seq(vec![
+                Pattern::repeat(2, Pattern::seq(vec![Pattern::on(0.15), Pattern::off(0.15)])),
+                Pattern::off(0.7),
+            ]),
+        }
+    }
+
+    /// Returns one cycle of the preset as on/off steps for a digital device, merging
+    /// consecutive steps at the same level
+    pub fn digital_sequence(self) -> Vec<(f32, f32)> {
+        let mut sequence: Vec<(f32, f32)> = Vec::new();
+        for (value, duration) in self.pattern().compile() {
+            let value = if value >= 0.5 { 1.0 } else { 0.0 };
+            match sequence.last_mut() {
+                Some((last, total)) if *last == value => *total += duration,
+                _ => sequence.push((value, duration)),
+            }
+        }
+        sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_steps(actual: &[(f32, f32)], expected: &[(f32, f32)]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for ((value, duration), (expected_value, expected_duration)) in actual.iter().zip(expected)
+        {
+            assert!((value - expected_value).abs() < 1e-4, "{:?}", actual);
+            assert!((duration - expected_duration).abs() < 1e-4, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn fades_are_compiled_into_steps_ending_at_the_target() {
+        let steps = Pattern::fade(0.0, 1.0, 0.2).compile();
+        assert_steps(
+            &steps,
+            &[
+                (0.0, 0.04),
+                (0.2, 0.04),
+                (0.4, 0.04),
+                (0.6, 0.04),
+                (0.8, 0.04),
+                (1.0, 0.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn repeats_and_sequences_are_flattened_in_order() {
+        let pattern = Pattern::seq(vec![
+            Pattern::repeat(2, Pattern::seq(vec![Pattern::on(0.1), Pattern::off(0.2)])),
+            Pattern::hold(0.5, 0.3),
+        ]);
+        assert_steps(
+            &pattern.compile(),
+            &[(1.0, 0.1), (0.0, 0.2), (1.0, 0.1), (0.0, 0.2), (0.5, 0.3)],
+        );
+        assert!(Pattern::repeat(0, Pattern::on(1.0)).compile().is_empty());
+    }
+
+    #[test]
+    fn digital_sequences_round_to_on_or_off_and_merge_steps() {
+        assert_steps(
+            &BlinkPreset::Heartbeat.digital_sequence(),
+            &[(1.0, 0.1), (0.0, 0.1), (1.0, 0.1), (0.0, 0.7)],
+        );
+        assert_steps(
+            &BlinkPreset::DoubleBlink.digital_sequence(),
+            &[(1.0, 0.15), (0.0, 0.15), (1.0, 0.15), (0.0, 0.85)],
+        );
+        // the fade is on from the first step at half brightness or more
+        assert_steps(
+            &BlinkPreset::SlowPulse.digital_sequence(),
+            &[(0.0, 0.52), (1.0, 1.0), (0.0, 0.48)],
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! The pin layer that devices are built on, so that backends other than rppal can be swapped in
+//!
+//! Devices never talk to the GPIO hardware directly. Each one asks the current `PinFactory` for
+//! its pins when it is constructed, and drives them through the `InputPin` and `IoPin` traits.
+//! The factory is `RppalPinFactory` by default; `set_pin_factory` replaces it for every device
+//! constructed afterwards, and `with_pin_factory` for the devices constructed in a closure.
+//!
+//! Off a Raspberry Pi, e.g. when developing on a laptop, the default can be made `MockPinFactory`
+//! instead, so programs run with devices that do nothing rather than failing to access the pins.
+//! This is opted into by setting the `GPIOZERO_MOCK` environment variable to anything but `0`,
+//! or by enabling the `mock` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::pins::{set_pin_factory, RppalPinFactory};
+//! use rust_gpiozero::*;
+//!
+//! set_pin_factory(RppalPinFactory);
+//! let mut led = LED::new(17);
+//! led.on();
+//! ```
+use crate::devices::{retry_attempts, with_retry, RETRY_DELAY};
+use crate::error::GpioZeroError;
+use crate::mock::MockPinFactory;
+use rppal::gpio::{self, Gpio, Level, Mode, PullUpDown, Trigger};
+use std::cell::RefCell;
+use std::env;
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+/// A pin used as an input, e.g. by a `Button`.
+/// Backends without edge detection can leave the interrupt methods at their defaults, which
+/// return `GpioZeroError::Unsupported`; devices then fall back to polling the level.
+pub trait InputPin: fmt::Debug + Send {
+    /// Returns the BCM GPIO number of the pin
+    fn pin(&self) -> u8;
+
+    /// Returns the current level of the pin
+    fn read(&self) -> Level;
+
+    /// Configure a synchronous interrupt for `poll_interrupt`
+    fn set_interrupt(&mut self, trigger: Trigger) -> Result<(), GpioZeroError> {
+        let _ = trigger;
+        Err(GpioZeroError::Unsupported(format!(
+            "interrupts on GPIO {}",
+            self.pin()
+        )))
+    }
+
+    /// Block until the interrupt set with `set_interrupt` fires or the timeout is reached,
+    /// returning the new level or `None` on a timeout
+    fn poll_interrupt(
+        &mut self,
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Level>, GpioZeroError> {
+        let _ = (reset, timeout);
+        Err(GpioZeroError::Unsupported(format!(
+            "interrupts on GPIO {}",
+            self.pin()
+        )))
+    }
+
+    /// Call `callback` from a background thread with the new level on every edge matching `trigger`
+    fn set_async_interrupt(
+        &mut self,
+        trigger: Trigger,
+        callback: Box<dyn FnMut(Level) + Send>,
+    ) -> Result<(), GpioZeroError> {
+        let _ = (trigger, callback);
+        Err(GpioZeroError::Unsupported(format!(
+            "interrupts on GPIO {}",
+            self.pin()
+        )))
+    }
+
+    /// Remove the callback set with `set_async_interrupt`
+    fn clear_async_interrupt(&mut self) -> Result<(), GpioZeroError> {
+        Ok(())
+    }
+}
+
+/// A pin that can be switched between modes, read and written, e.g. the pin of an `LED`.
+/// Backends without software PWM can leave the PWM methods at their defaults, which return
+/// `GpioZeroError::Unsupported`.
+pub trait IoPin: fmt::Debug + Send {
+    /// Returns the BCM GPIO number of the pin
+    fn pin(&self) -> u8;
+
+    /// Returns the current mode of the pin
+    fn mode(&self) -> Mode;
+
+    /// Switch the pin to the mode given
+    fn set_mode(&mut self, mode: Mode);
+
+    /// Returns the current level of the pin
+    fn read(&self) -> Level;
+
+    /// Drive the pin to the level given, if it is an output
+    fn write(&mut self, level: Level);
+
+    /// Drive the pin low
+    fn set_low(&mut self) {
+        self.write(Level::Low)
+    }
+
+    /// Drive the pin high
+    fn set_high(&mut self) {
+        self.write(Level::High)
+    }
+
+    /// Start a PWM signal with the period and pulse width given
+    fn set_pwm(&mut self, period: Duration, pulse_width: Duration) -> Result<(), GpioZeroError> {
+        let _ = (period, pulse_width);
+        Err(GpioZeroError::Unsupported(format!(
+            "PWM on GPIO {}",
+            self.pin()
+        )))
+    }
+
+    /// Start a PWM signal with the frequency in Hz and duty cycle (0.0 to 1.0) given
+    fn set_pwm_frequency(&mut self, frequency: f64, duty_cycle: f64) -> Result<(), GpioZeroError> {
+        let _ = (frequency, duty_cycle);
+        Err(GpioZeroError::Unsupported(format!(
+            "PWM on GPIO {}",
+            self.pin()
+        )))
+    }
+
+    /// Stop the PWM signal, if there is one
+    fn clear_pwm(&mut self) -> Result<(), GpioZeroError> {
+        Ok(())
+    }
+}
+
+/// The `PinFactory::id` of factories driving the pins of this machine
+pub const LOCAL_PINS: &str = "local";
+
+/// Hands out the pins that devices are built on.
+/// A factory only acquires pins; reserving them so that two devices can't share one is done
+/// by the devices, whichever factory is in use, keyed by the factory's `id` and the pin number.
+pub trait PinFactory: Send + Sync {
+    /// Acquire `pin` as an input with the pull given
+    fn input_pin(&self, pin: u8, pull: PullUpDown) -> Result<Box<dyn InputPin>, GpioZeroError>;
+
+    /// Acquire `pin` for reading and writing, starting in the mode given
+    fn io_pin(&self, pin: u8, mode: Mode) -> Result<Box<dyn IoPin>, GpioZeroError>;
+
+    /// Returns ``True`` if `pin` could be acquired right now, without acquiring it
+    fn pin_available(&self, pin: u8) -> bool;
+
+    /// Returns the identity of the pins the factory drives, e.g. the address of a remote Pi,
+    /// so that pins with the same number on different hardware are reserved separately.
+    /// Factories driving the same pins return the same id; the default is `LOCAL_PINS`.
+    fn id(&self) -> String {
+        LOCAL_PINS.to_string()
+    }
+}
+
+/// The default factory, which drives the Raspberry Pi's GPIO pins through rppal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RppalPinFactory;
+
+impl RppalPinFactory {
+    fn get(&self, pin: u8) -> Result<gpio::Pin, GpioZeroError> {
+        let gpio = with_retry(retry_attempts(), RETRY_DELAY, Gpio::new)?;
+        Ok(with_retry(retry_attempts(), RETRY_DELAY, || gpio.get(pin))?)
+    }
+}
+
+impl PinFactory for RppalPinFactory {
+    fn input_pin(&self, pin: u8, pull: PullUpDown) -> Result<Box<dyn InputPin>, GpioZeroError> {
+        let pin = self.get(pin)?;
+        Ok(Box::new(match pull {
+            PullUpDown::PullUp => pin.into_input_pullup(),
+            PullUpDown::PullDown => pin.into_input_pulldown(),
+            PullUpDown::Off => pin.into_input(),
+        }))
+    }
+
+    fn io_pin(&self, pin: u8, mode: Mode) -> Result<Box<dyn IoPin>, GpioZeroError> {
+        Ok(Box::new(self.get(pin)?.into_io(mode)))
+    }
+
+    fn pin_available(&self, pin: u8) -> bool {
+        match Gpio::new() {
+            Err(_) => false,
+            Ok(gpio) => gpio.get(pin).is_ok(),
+        }
+    }
+}
+
+impl InputPin for gpio::InputPin {
+    fn pin(&self) -> u8 {
+        gpio::InputPin::pin(self)
+    }
+
+    fn read(&self) -> Level {
+        gpio::InputPin::read(self)
+    }
+
+    fn set_interrupt(&mut self, trigger: Trigger) -> Result<(), GpioZeroError> {
+        Ok(gpio::InputPin::set_interrupt(self, trigger)?)
+    }
+
+    fn poll_interrupt(
+        &mut self,
+        reset: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Level>, GpioZeroError> {
+        Ok(gpio::InputPin::poll_interrupt(self, reset, timeout)?)
+    }
+
+    fn set_async_interrupt(
+        &mut self,
+        trigger: Trigger,
+        callback: Box<dyn FnMut(Level) + Send>,
+    ) -> Result<(), GpioZeroError> {
+        Ok(gpio::InputPin::set_async_interrupt(
+            self, trigger, callback,
+        )?)
+    }
+
+    fn clear_async_interrupt(&mut self) -> Result<(), GpioZeroError> {
+        Ok(gpio::InputPin::clear_async_interrupt(self)?)
+    }
+}
+
+impl IoPin for gpio::IoPin {
+    fn pin(&self) -> u8 {
+        gpio::IoPin::pin(self)
+    }
+
+    fn mode(&self) -> Mode {
+        gpio::IoPin::mode(self)
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        gpio::IoPin::set_mode(self, mode)
+    }
+
+    fn read(&self) -> Level {
+        gpio::IoPin::read(self)
+    }
+
+    fn write(&mut self, level: Level) {
+        gpio::IoPin::write(self, level)
+    }
+
+    fn set_pwm(&mut self, period: Duration, pulse_width: Duration) -> Result<(), GpioZeroError> {
+        Ok(gpio::IoPin::set_pwm(self, period, pulse_width)?)
+    }
+
+    fn set_pwm_frequency(&mut self, frequency: f64, duty_cycle: f64) -> Result<(), GpioZeroError> {
+        Ok(gpio::IoPin::set_pwm_frequency(self, frequency, duty_cycle)?)
+    }
+
+    fn clear_pwm(&mut self) -> Result<(), GpioZeroError> {
+        Ok(gpio::IoPin::clear_pwm(self)?)
+    }
+}
+
+// The factory used by devices constructed from now on, or `None` for the default
+static PIN_FACTORY: RwLock<Option<Arc<dyn PinFactory>>> = RwLock::new(None);
+
+thread_local! {
+    // The factory set by `with_pin_factory` on this thread, which takes precedence over `PIN_FACTORY`
+    static SCOPED_PIN_FACTORY: RefCell<Option<Arc<dyn PinFactory>>> = RefCell::new(None);
+}
+
+/// Set the factory that devices constructed from now on get their pins from.
+/// Devices that already exist keep the pins they have.
+pub fn set_pin_factory<F: PinFactory + 'static>(factory: F) {
+    *PIN_FACTORY.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(factory));
+}
+
+/// The environment variable which makes `MockPinFactory` the default factory when set to
+/// anything but `0`, e.g. `GPIOZERO_MOCK=1`
+pub const MOCK_ENV_VAR: &str = "GPIOZERO_MOCK";
+
+// The factory used until `set_pin_factory` is called: `MockPinFactory` if asked for with the
+// `mock` feature or `GPIOZERO_MOCK`, and `RppalPinFactory` otherwise
+fn default_pin_factory() -> Arc<dyn PinFactory> {
+    let requested = env::var(MOCK_ENV_VAR).is_ok_and(|value| !value.is_empty() && value != "0");
+    if cfg!(feature = "mock") || requested {
+        Arc::new(MockPinFactory::new())
+    } else {
+        Arc::new(RppalPinFactory)
+    }
+}
+
+/// Returns the factory that devices get their pins from, which is `RppalPinFactory` unless
+/// `set_pin_factory` has been called, or `MockPinFactory` if it was asked for with the `mock`
+/// feature or `MOCK_ENV_VAR`. The default is chosen the first time it is needed.
+pub fn pin_factory() -> Arc<dyn PinFactory> {
+    if let Some(factory) = SCOPED_PIN_FACTORY.with(|scoped| scoped.borrow().clone()) {
+        return factory;
+    }
+    if let Some(factory) = PIN_FACTORY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+    {
+        return factory;
+    }
+    PIN_FACTORY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(default_pin_factory)
+        .clone()
+}
+
+/// Construct devices with `f` using `factory` for their pins, e.g. to put a single device on a
+/// different backend from the rest. Only devices constructed on the current thread within `f` are
+/// affected, and the previous factory is restored afterwards.
+///
+/// ```no_run
+/// use rust_gpiozero::mock::MockPinFactory;
+/// use rust_gpiozero::pins::with_pin_factory;
+/// use rust_gpiozero::*;
+/// use std::sync::Arc;
+///
+/// let led = LED::new(17);
+/// let simulated = with_pin_factory(Arc::new(MockPinFactory::new()), || LED::new(18));
+/// ```
+pub fn with_pin_factory<R, F: FnOnce() -> R>(factory: Arc<dyn PinFactory>, f: F) -> R {
+    // restores the previous factory even if `f` panics
+    struct Restore(Option<Arc<dyn PinFactory>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            SCOPED_PIN_FACTORY.with(|scoped| *scoped.borrow_mut() = previous);
+        }
+    }
+    let _restore = Restore(SCOPED_PIN_FACTORY.with(|scoped| scoped.replace(Some(factory))));
+    f()
+}
@@ -0,0 +1,110 @@
+//! A crate-wide registry of the tags attached to devices, so that devices can be found by
+//! metadata such as their location or circuit rather than by pin number
+//!
+//! Devices are registered under the pin factory they were built on, and the lookups below only
+//! see the devices of the current pin factory. Use `pins::with_pin_factory` to look up the
+//! devices of another factory, e.g. a remote Pi.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut led = LED::new(17);
+//! led.set_tag("location", "kitchen");
+//!
+//! assert_eq!(registry::find_by_tag("location", "kitchen"), vec![17]);
+//! ```
+
+use crate::pins::pin_factory;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// The tags of every tagged device, by the id of its pin factory and its pin.
+// A pin can only be held by one device at a time.
+static REGISTRY: Mutex<BTreeMap<(String, u8), HashMap<String, String>>> =
+    Mutex::new(BTreeMap::new());
+
+fn registry() -> MutexGuard<'static, BTreeMap<(String, u8), HashMap<String, String>>> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the pins of the devices that have the tag `key` set to `value`, in ascending order.
+/// Devices are owned by the code that created them, so the registry identifies them by pin.
+pub fn find_by_tag(key: &str, value: &str) -> Vec<u8> {
+    let factory = pin_factory().id();
+    registry()
+        .iter()
+        .filter(|((id, _), tags)| {
+            *id == factory && tags.get(key).map(String::as_str) == Some(value)
+        })
+        .map(|((_, pin), _)| *pin)
+        .collect()
+}
+
+/// Returns the tags of the device on `pin`, which are empty if it has none or no device is using the pin.
+pub fn tags_of(pin: u8) -> HashMap<String, String> {
+    registry()
+        .get(&(pin_factory().id(), pin))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Returns the pins of all the devices that have at least one tag, in ascending order.
+pub fn tagged_pins() -> Vec<u8> {
+    let factory = pin_factory().id();
+    registry()
+        .keys()
+        .filter(|(id, _)| *id == factory)
+        .map(|(_, pin)| *pin)
+        .collect()
+}
+
+/// The tags of a single device, which are published to the registry while the device exists
+/// and removed from it when the device is dropped.
+#[derive(Debug)]
+pub(crate) struct Tags {
+    key: (String, u8),
+    tags: HashMap<String, String>,
+}
+
+impl Tags {
+    /// Returns an empty set of tags for the device on `pin` of the current pin factory
+    pub(crate) fn new(pin: u8) -> Tags {
+        Tags {
+            key: (pin_factory().id(), pin),
+            tags: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: &str) {
+        self.tags.insert(key.to_string(), value.to_string());
+        registry().insert(self.key.clone(), self.tags.clone());
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+
+    pub(crate) fn all(&self) -> &HashMap<String, String> {
+        &self.tags
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Option<String> {
+        let value = self.tags.remove(key);
+        if self.tags.is_empty() {
+            registry().remove(&self.key);
+        } else {
+            registry().insert(self.key.clone(), self.tags.clone());
+        }
+        value
+    }
+}
+
+impl Drop for Tags {
+    fn drop(&mut self) {
+        if !self.tags.is_empty() {
+            registry().remove(&self.key);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! A pin backend that controls the pins of another Raspberry Pi through its pigpio daemon
+//!
+//! The remote Pi needs `pigpiod` running and listening on the network, e.g. with
+//! `sudo pigpiod` after enabling remote GPIO in `raspi-config`. Every read and write is a round
+//! trip over the network, so timings are far looser than on local pins. Remote pins have no
+//! interrupts through this backend, so input devices poll the level.
+//!
+//! Pins are reserved separately for each remote Pi, so a device on a remote pin can share its
+//! number with a device on a local pin or on another remote Pi in the same program.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::*;
+//!
+//! let mut led = LED::new_remote("192.168.1.10", 17);
+//! led.blink(1.0, 1.0);
+//! led.wait();
+//! ```
+use crate::error::GpioZeroError;
+use crate::pins::{InputPin, IoPin, PinFactory};
+use rppal::gpio::{Level, Mode, PullUpDown};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// The port pigpiod listens on by default
+pub const DEFAULT_PIGPIO_PORT: u16 = 8888;
+
+// pigpio socket commands, from pigpio.h
+const CMD_MODES: u32 = 0;
+const CMD_MODEG: u32 = 1;
+const CMD_PUD: u32 = 2;
+const CMD_READ: u32 = 3;
+const CMD_WRITE: u32 = 4;
+const CMD_PWM: u32 = 5;
+const CMD_PRS: u32 = 6;
+const CMD_PFS: u32 = 7;
+
+// The error pigpio returns for a GPIO number that doesn't exist
+const PI_BAD_GPIO: i32 = -3;
+
+// The PWM range set on remote pins, i.e. the number of steps in a duty cycle
+const PWM_RANGE: u32 = 10_000;
+
+// The mode numbers pigpio uses, which are in a different order from rppal's
+fn mode_to_pigpio(mode: Mode) -> u32 {
+    match mode {
+        Mode::Input => 0,
+        Mode::Output => 1,
+        Mode::Alt0 => 4,
+        Mode::Alt1 => 5,
+        Mode::Alt2 => 6,
+        Mode::Alt3 => 7,
+        Mode::Alt4 => 3,
+        Mode::Alt5 => 2,
+    }
+}
+
+fn mode_from_pigpio(mode: u32) -> Mode {
+    match mode {
+        1 => Mode::Output,
+        4 => Mode::Alt0,
+        5 => Mode::Alt1,
+        6 => Mode::Alt2,
+        7 => Mode::Alt3,
+        3 => Mode::Alt4,
+        2 => Mode::Alt5,
+        _ => Mode::Input,
+    }
+}
+
+// A connection to pigpiod, shared by all the pins of a factory
+#[derive(Debug)]
+struct Connection {
+    stream: Mutex<TcpStream>,
+}
+
+impl Connection {
+    // Send a command and return pigpio's result, which is an error if it is negative
+    fn command(&self, cmd: u32, p1: u32, p2: u32) -> Result<u32, GpioZeroError> {
+        let mut request = [0u8; 16];
+        request[0..4].copy_from_slice(&cmd.to_le_bytes());
+        request[4..8].copy_from_slice(&p1.to_le_bytes());
+        request[8..12].copy_from_slice(&p2.to_le_bytes());
+        let mut response = [0u8; 16];
+        {
+            let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+            stream.write_all(&request)?;
+            stream.read_exact(&mut response)?;
+        }
+        let result = i32::from_le_bytes([response[12], response[13], response[14], response[15]]);
+        if result == PI_BAD_GPIO {
+            return Err(GpioZeroError::PinUnavailable(p1 as u8));
+        }
+        if result < 0 {
+            return Err(GpioZeroError::Io(io::Error::other(format!(
+                "pigpio command {} failed with error {}",
+                cmd, result
+            ))));
+        }
+        Ok(result as u32)
+    }
+}
+
+/// A `PinFactory` whose pins are on another Raspberry Pi, controlled through its pigpio daemon.
+/// Clones share the same connection.
+#[derive(Debug, Clone)]
+pub struct RemotePinFactory {
+    host: String,
+    port: u16,
+    connection: Arc<Connection>,
+}
+
+impl RemotePinFactory {
+    /// Returns a factory connected to pigpiod on `host` at `DEFAULT_PIGPIO_PORT`
+    /// * `host` - The host name or IP address of the remote Pi
+    pub fn new(host: &str) -> Result<RemotePinFactory, GpioZeroError> {
+        RemotePinFactory::connect(host, DEFAULT_PIGPIO_PORT)
+    }
+
+    /// Returns a factory connected to pigpiod on `host` at the port given, or
+    /// `GpioZeroError::Io` if the daemon can't be reached
+    pub fn connect(host: &str, port: u16) -> Result<RemotePinFactory, GpioZeroError> {
+        let stream = TcpStream::connect((host, port))?;
+        // commands are tiny and each waits for its reply, so don't let them sit in a buffer
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        Ok(RemotePinFactory {
+            host: host.to_string(),
+            port,
+            connection: Arc::new(Connection {
+                stream: Mutex::new(stream),
+            }),
+        })
+    }
+
+    /// Returns the host the factory is connected to
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    fn remote_pin(
+        &self,
+        pin: u8,
+        mode: Mode,
+        pull: PullUpDown,
+    ) -> Result<RemotePin, GpioZeroError> {
+        let pud = match pull {
+            PullUpDown::Off => 0,
+            PullUpDown::PullDown => 1,
+            PullUpDown::PullUp => 2,
+        };
+        self.connection.command(CMD_PUD, u32::from(pin), pud)?;
+        self.connection
+            .command(CMD_MODES, u32::from(pin), mode_to_pigpio(mode))?;
+        Ok(RemotePin {
+            pin,
+            connection: Arc::clone(&self.connection),
+        })
+    }
+}
+
+impl PinFactory for RemotePinFactory {
+    fn input_pin(&self, pin: u8, pull: PullUpDown) -> Result<Box<dyn InputPin>, GpioZeroError> {
+        Ok(Box::new(self.remote_pin(pin, Mode::Input, pull)?))
+    }
+
+    fn io_pin(&self, pin: u8, mode: Mode) -> Result<Box<dyn IoPin>, GpioZeroError> {
+        Ok(Box::new(self.remote_pin(pin, mode, PullUpDown::Off)?))
+    }
+
+    fn pin_available(&self, pin: u8) -> bool {
+        // pigpio doesn't track which pins are in use, only which exist
+        self.connection
+            .command(CMD_MODEG, u32::from(pin), 0)
+            .is_ok()
+    }
+
+    fn id(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+// A pin on the remote Pi
+#[derive(Debug)]
+struct RemotePin {
+    pin: u8,
+    connection: Arc<Connection>,
+}
+
+impl InputPin for RemotePin {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn read(&self) -> Level {
+        // a pin that can't be read, e.g. because the connection dropped, is reported low
+        match self.connection.command(CMD_READ, u32::from(self.pin), 0) {
+            Ok(1) => Level::High,
+            _ => Level::Low,
+        }
+    }
+}
+
+impl IoPin for RemotePin {
+    fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn mode(&self) -> Mode {
+        match self.connection.command(CMD_MODEG, u32::from(self.pin), 0) {
+            Ok(mode) => mode_from_pigpio(mode),
+            Err(_) => Mode::Input,
+        }
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        if let Err(e) =
+            self.connection
+                .command(CMD_MODES, u32::from(self.pin), mode_to_pigpio(mode))
+        {
+            println!("Could not set the mode of remote GPIO {}: {}", self.pin, e);
+        }
+    }
+
+    fn read(&self) -> Level {
+        InputPin::read(self)
+    }
+
+    fn write(&mut self, level: Level) {
+        let value = (level == Level::High) as u32;
+        if let Err(e) = self
+            .connection
+            .command(CMD_WRITE, u32::from(self.pin), value)
+        {
+            println!("Could not write to remote GPIO {}: {}", self.pin, e);
+        }
+    }
+
+    fn set_pwm(&mut self, period: Duration, pulse_width: Duration) -> Result<(), GpioZeroError> {
+        let period = period.as_secs_f64();
+        if period <= 0.0 {
+            return Err(GpioZeroError::InvalidArgument(
+                "the PWM period must be greater than 0".to_string(),
+            ));
+        }
+        self.set_pwm_frequency(1.0 / period, pulse_width.as_secs_f64() / period)
+    }
+
+    fn set_pwm_frequency(&mut self, frequency: f64, duty_cycle: f64) -> Result<(), GpioZeroError> {
+        let pin = u32::from(self.pin);
+        // pigpio picks the closest frequency it supports
+        self.connection
+            .command(CMD_PFS, pin, frequency.round() as u32)?;
+        self.connection.command(CMD_PRS, pin, PWM_RANGE)?;
+        let duty = (duty_cycle.clamp(0.0, 1.0) * f64::from(PWM_RANGE)).round() as u32;
+        self.connection.command(CMD_PWM, pin, duty)?;
+        Ok(())
+    }
+
+    fn clear_pwm(&mut self) -> Result<(), GpioZeroError> {
+        self.connection.command(CMD_PWM, u32::from(self.pin), 0)?;
+        Ok(())
+    }
+}
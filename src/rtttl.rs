@@ -0,0 +1,202 @@
+//! Parser for melodies in the RTTTL (Ring Tone Text Transfer Language) format
+//!
+//! An RTTTL string has three sections separated by colons: a name, the default
+//! duration, octave and tempo, and a comma separated list of notes, e.g.
+//!
+//! ```text
+//! Beep:d=4,o=5,b=120:8c,8e,8g,4c6,4p
+//! ```
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// A single note of a melody
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    /// The frequency of the note in Hz, or `None` for a pause
+    pub frequency: Option<f64>,
+    /// How long the note is played for
+    pub duration: Duration,
+}
+
+/// Returned when an RTTTL string is malformed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl ParseError {
+    fn new(message: String) -> ParseError {
+        ParseError { message }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid RTTTL: {}", self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parses an RTTTL string into the list of notes it describes
+/// * `rtttl` - The melody, in the form `name:d=4,o=6,b=63:notes`
+pub fn parse(rtttl: &str) -> Result<Vec<Note>, ParseError> {
+    let sections: Vec<&str> = rtttl.split(':').collect();
+    if sections.len() != 3 {
+        return Err(ParseError::new(
+            "expected three sections separated by `:`".to_string(),
+        ));
+    }
+
+    // defaults defined by the RTTTL specification
+    let mut duration = 4;
+    let mut octave = 6;
+    let mut bpm = 63;
+    for setting in sections[1].split(',') {
+        let setting = setting.trim();
+        if setting.is_empty() {
+            continue;
+        }
+        let mut parts = setting.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = match parts.next().map(|v| v.trim().parse::<u32>()) {
+            Some(Ok(value)) => value,
+            _ => return Err(ParseError::new(format!("invalid setting `{}`", setting))),
+        };
+        match key {
+            "d" => duration = value,
+            "o" => octave = value,
+            "b" => bpm = value,
+            _ => return Err(ParseError::new(format!("unknown setting `{}`", key))),
+        }
+    }
+    if !is_valid_duration(duration) {
+        return Err(ParseError::new(format!(
+            "invalid default duration {}",
+            duration
+        )));
+    }
+    if bpm == 0 {
+        return Err(ParseError::new("tempo must be greater than 0".to_string()));
+    }
+
+    let mut notes = Vec::new();
+    for token in sections[2].split(',') {
+        let token = token.trim();
+        if token.is_empty() {
+            continue;
+        }
+        notes.push(parse_note(token, duration, octave, bpm)?);
+    }
+    Ok(notes)
+}
+
+fn is_valid_duration(duration: u32) -> bool {
+    [1, 2, 4, 8, 16, 32].contains(&duration)
+}
+
+fn parse_note(token: &str, duration: u32, octave: u32, bpm: u32) -> Result<Note, ParseError> {
+    let invalid = || ParseError::new(format!("invalid note `{}`", token));
+    let mut chars = token.chars().peekable();
+
+    let mut digits = String::new();
+    while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        digits.push(*c);
+        chars.next();
+    }
+    let duration = if digits.is_empty() {
+        duration
+    } else {
+        digits.parse().map_err(|_| invalid())?
+    };
+    if !is_valid_duration(duration) {
+        return Err(invalid());
+    }
+
+    // semitones above C, or None for a pause
+    let mut semitone = match chars.next().map(|c| c.to_ascii_lowercase()) {
+        Some('c') => Some(0),
+        Some('d') => Some(2),
+        Some('e') => Some(4),
+        Some('f') => Some(5),
+        Some('g') => Some(7),
+        Some('a') => Some(9),
+        Some('b') | Some('h') => Some(11),
+        Some('p') => None,
+        _ => return Err(invalid()),
+    };
+    if chars.peek() == Some(&'#') {
+        chars.next();
+        semitone = semitone.map(|s| s + 1);
+    }
+
+    let mut dotted = false;
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        dotted = true;
+    }
+    let octave = match chars.peek().and_then(|c| c.to_digit(10)) {
+        Some(o) => {
+            chars.next();
+            o
+        }
+        None => octave,
+    };
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        dotted = true;
+    }
+    if chars.next().is_some() {
+        return Err(invalid());
+    }
+
+    // a whole note lasts four beats
+    let mut millis = 240_000.0 / f64::from(bpm) / f64::from(duration);
+    if dotted {
+        millis *= 1.5;
+    }
+    Ok(Note {
+        // A4 (440 Hz) is 57 semitones above C0
+        frequency: semitone.map(|s| 440.0 * 2f64.powf((f64::from(octave * 12 + s) - 57.0) / 12.0)),
+        duration: Duration::from_millis(millis as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_take_their_defaults_from_the_settings() {
+        let notes = parse("Beep:d=4,o=5,b=120:8c,p,a.,c#6").unwrap();
+        let durations: Vec<u64> = notes
+            .iter()
+            .map(|n| n.duration.as_millis() as u64)
+            .collect();
+        assert_eq!(durations, vec![250, 500, 750, 500]);
+        let frequencies: Vec<Option<u64>> = notes
+            .iter()
+            .map(|n| n.frequency.map(|f| f.round() as u64))
+            .collect();
+        assert_eq!(frequencies, vec![Some(523), None, Some(880), Some(1109)]);
+    }
+
+    #[test]
+    fn settings_default_to_the_specification() {
+        let notes = parse("Defaults::c").unwrap();
+        // d=4 at b=63, with o=6 making the note C6
+        assert_eq!(notes[0].duration, Duration::from_millis(952));
+        assert_eq!(notes[0].frequency.map(|f| f.round() as u64), Some(1047));
+    }
+
+    #[test]
+    fn malformed_melodies_are_rejected() {
+        assert!(parse("no sections").is_err());
+        assert!(parse("Beep:x=4:c").is_err());
+        assert!(parse("Beep:d=3:c").is_err());
+        assert!(parse("Beep:b=0:c").is_err());
+        assert!(parse("Beep:d=4:3c").is_err());
+        assert!(parse("Beep:d=4:cx").is_err());
+    }
+}
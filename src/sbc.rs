@@ -0,0 +1,249 @@
+//! A pin backend for Linux single board computers other than the Raspberry Pi
+//!
+//! rppal only knows the Raspberry Pi's SoCs, but any board whose kernel exposes its GPIO
+//! controllers as character devices, e.g. an Orange Pi or a BeagleBone, can drive devices through
+//! `SbcPinFactory`. The board is inspected when the factory is constructed: its model is read
+//! from the device tree and every `/dev/gpiochipN` is opened to count its lines.
+//!
+//! Pins are numbered across all the chips in order, so the lines of `gpiochip0` come first,
+//! followed by those of `gpiochip1` and so on. On a Raspberry Pi this matches the BCM numbering.
+//! Only the first 256 lines can be used, since pins are numbered with a `u8`.
+//! Lines have no software PWM or interrupts through this backend, as with `GpiodPinFactory`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::pins::set_pin_factory;
+//! use rust_gpiozero::sbc::SbcPinFactory;
+//! use rust_gpiozero::*;
+//!
+//! let factory = SbcPinFactory::detect().expect("no GPIO chips found");
+//! println!("{} with {} GPIO lines", factory.board().model(), factory.board().lines());
+//! set_pin_factory(factory);
+//!
+//! let mut led = LED::new(17);
+//! let mut button = Button::new(2);
+//! button.wait_for_press(None);
+//! led.on();
+//! ```
+use crate::error::GpioZeroError;
+use crate::gpiod::{GpioChipInfo, GpiodPinFactory};
+use crate::pins::{InputPin, IoPin, PinFactory};
+use rppal::gpio::{Mode, PullUpDown};
+use std::fs;
+use std::path::Path;
+
+// Where the kernel publishes the board's model name on device tree systems
+const MODEL_PATH: &str = "/proc/device-tree/model";
+
+/// What was found on the board when an `SbcPinFactory` was constructed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    model: String,
+    chips: Vec<GpioChipInfo>,
+}
+
+impl Board {
+    /// Returns the model name from the device tree, e.g. `"Xunlong Orange Pi Zero"`,
+    /// or `"unknown"` if the board doesn't publish one
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the GPIO chips on the board, in the order their lines are numbered
+    pub fn chips(&self) -> &[GpioChipInfo] {
+        &self.chips
+    }
+
+    /// Returns the number of GPIO lines across all the chips
+    pub fn lines(&self) -> u32 {
+        self.chips.iter().map(|chip| chip.lines).sum()
+    }
+
+    /// Returns ``True`` if the board is a Raspberry Pi, where `RppalPinFactory` also works
+    pub fn is_raspberry_pi(&self) -> bool {
+        self.model.starts_with("Raspberry Pi")
+    }
+}
+
+// The model name in the device tree is NUL terminated
+fn model_name(raw: &str) -> String {
+    raw.trim_end_matches('\0').trim().to_string()
+}
+
+/// A `PinFactory` that drives the lines of every GPIO character device on the board
+#[derive(Debug, Clone)]
+pub struct SbcPinFactory {
+    board: Board,
+    chips: Vec<GpiodPinFactory>,
+}
+
+impl SbcPinFactory {
+    /// Returns a factory for the GPIO chips of the board the program is running on, or
+    /// `GpioZeroError::Unsupported` if the board has none, or
+    /// `GpioZeroError::PermissionDenied` if the program isn't allowed to open them
+    pub fn detect() -> Result<SbcPinFactory, GpioZeroError> {
+        let model = fs::read_to_string(MODEL_PATH)
+            .map(|model| model_name(&model))
+            .unwrap_or_else(|_| "unknown".to_string());
+        SbcPinFactory::from_dir(model, Path::new("/dev"))
+    }
+
+    fn from_dir(model: String, dir: &Path) -> Result<SbcPinFactory, GpioZeroError> {
+        let mut numbers: Vec<u32> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_prefix("gpiochip"))
+                    .and_then(|number| number.parse().ok())
+            })
+            .collect();
+        // sort numerically, so that gpiochip10 comes after gpiochip9
+        numbers.sort_unstable();
+
+        let mut chips = Vec::new();
+        let mut infos = Vec::new();
+        for number in numbers {
+            let chip = GpiodPinFactory::open(dir.join(format!("gpiochip{}", number)))?;
+            infos.push(chip.info()?);
+            chips.push(chip);
+        }
+        if chips.is_empty() {
+            return Err(GpioZeroError::Unsupported(format!(
+                "GPIO on {}, which has no GPIO character devices",
+                model
+            )));
+        }
+        Ok(SbcPinFactory {
+            board: Board {
+                model,
+                chips: infos,
+            },
+            chips,
+        })
+    }
+
+    /// Returns what was found on the board when the factory was constructed
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    // The chip holding `pin`, and the offset of its line on that chip
+    fn locate(&self, pin: u8) -> Result<(&GpiodPinFactory, u32), GpioZeroError> {
+        let mut offset = u32::from(pin);
+        for (chip, info) in self.chips.iter().zip(&self.board.chips) {
+            if offset < info.lines {
+                return Ok((chip, offset));
+            }
+            offset -= info.lines;
+        }
+        Err(GpioZeroError::PinUnavailable(pin))
+    }
+}
+
+impl PinFactory for SbcPinFactory {
+    fn input_pin(&self, pin: u8, pull: PullUpDown) -> Result<Box<dyn InputPin>, GpioZeroError> {
+        let (chip, offset) = self.locate(pin)?;
+        Ok(Box::new(chip.request(pin, offset, Mode::Input, pull)?))
+    }
+
+    fn io_pin(&self, pin: u8, mode: Mode) -> Result<Box<dyn IoPin>, GpioZeroError> {
+        let (chip, offset) = self.locate(pin)?;
+        Ok(Box::new(chip.request(
+            pin,
+            offset,
+            mode,
+            PullUpDown::Off,
+        )?))
+    }
+
+    fn pin_available(&self, pin: u8) -> bool {
+        match self.locate(pin) {
+            Ok((chip, offset)) => chip.line_available(offset),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn chip(lines: u32) -> GpioChipInfo {
+        GpioChipInfo {
+            name: "gpiochip".to_string(),
+            label: "test".to_string(),
+            lines,
+        }
+    }
+
+    // An empty directory standing in for `/dev`, removed when dropped
+    struct Dev(std::path::PathBuf);
+
+    impl Dev {
+        fn new(name: &str) -> Dev {
+            let dir =
+                std::env::temp_dir().join(format!("rust_gpiozero_{}_{}", name, process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Dev(dir)
+        }
+    }
+
+    impl Drop for Dev {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn model_names_are_read_without_the_terminator() {
+        assert_eq!(
+            model_name("Raspberry Pi 4 Model B Rev 1.4\0"),
+            "Raspberry Pi 4 Model B Rev 1.4"
+        );
+        assert_eq!(
+            model_name("Xunlong Orange Pi Zero\n"),
+            "Xunlong Orange Pi Zero"
+        );
+    }
+
+    #[test]
+    fn boards_count_the_lines_of_all_their_chips() {
+        let board = Board {
+            model: "Raspberry Pi 5 Model B Rev 1.0".to_string(),
+            chips: vec![chip(54), chip(8)],
+        };
+        assert_eq!(board.lines(), 62);
+        assert!(board.is_raspberry_pi());
+        let board = Board {
+            model: "Xunlong Orange Pi Zero".to_string(),
+            chips: vec![chip(224)],
+        };
+        assert!(!board.is_raspberry_pi());
+    }
+
+    #[test]
+    fn a_board_without_gpio_chips_is_unsupported() {
+        let dev = Dev::new("no_chips");
+        fs::write(dev.0.join("gpiomem"), "").unwrap();
+        fs::write(dev.0.join("gpiochipx"), "").unwrap();
+        assert!(matches!(
+            SbcPinFactory::from_dir("Test Board".to_string(), &dev.0),
+            Err(GpioZeroError::Unsupported(message)) if message.contains("Test Board")
+        ));
+    }
+
+    #[test]
+    fn gpio_chips_are_found_by_name() {
+        // a file named like a chip is opened, but isn't a GPIO character device
+        let dev = Dev::new("fake_chip");
+        fs::write(dev.0.join("gpiochip0"), "").unwrap();
+        assert!(matches!(
+            SbcPinFactory::from_dir("Test Board".to_string(), &dev.0),
+            Err(GpioZeroError::Io(_))
+        ));
+    }
+}
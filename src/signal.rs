@@ -0,0 +1,45 @@
+//! Opt-in handling of SIGINT (Ctrl-C), enabled with the `signal` feature.
+//!
+//! Nothing is installed unless `install_signal_handler` is called, so the library
+//! never takes over signal handling from the application using it.
+use crate::output_devices::OutputDevice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+type Registration = (Weak<Mutex<OutputDevice>>, Weak<AtomicBool>);
+
+static DEVICES: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+
+/// Keep track of an output device so that it can be turned off on SIGINT
+pub(crate) fn register(device: &Arc<Mutex<OutputDevice>>, running: &Arc<AtomicBool>) {
+    let mut devices = DEVICES.lock().unwrap();
+    devices.retain(|(device, _)| device.strong_count() > 0);
+    devices.push((Arc::downgrade(device), Arc::downgrade(running)));
+}
+
+/// Install a handler for SIGINT (Ctrl-C) which stops all background threads,
+/// drives every output device that is still alive to its inactive state and exits the process.
+///
+/// This replaces any handler previously installed for SIGINT and can only be called once.
+pub fn install_signal_handler() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        let devices = match DEVICES.lock() {
+            Ok(devices) => devices,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for (_, running) in devices.iter() {
+            if let Some(running) = running.upgrade() {
+                running.store(false, Ordering::SeqCst);
+            }
+        }
+        for (device, _) in devices.iter() {
+            if let Some(device) = device.upgrade() {
+                match device.lock() {
+                    Ok(mut device) => device.shutdown(),
+                    Err(poisoned) => poisoned.into_inner().shutdown(),
+                }
+            }
+        }
+        std::process::exit(130);
+    })
+}
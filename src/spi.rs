@@ -0,0 +1,79 @@
+//! Software SPI for devices wired to arbitrary GPIO pins
+use crate::devices::sleep_micros;
+use crate::input_devices::InputDevice;
+use crate::output_devices::OutputDevice;
+
+/// A bit-banged SPI bus in mode 0 (clock idle low, data sampled on the rising edge),
+/// transferring the most significant bit first.
+/// It is much slower than the hardware SPI bus, but can be used on any GPIO pins.
+#[derive(Debug)]
+pub struct BitBangSpi {
+    mosi: OutputDevice,
+    miso: InputDevice,
+    sclk: OutputDevice,
+    cs: OutputDevice,
+    clock_delay: u64,
+}
+
+impl BitBangSpi {
+    /// Returns a BitBangSpi on the pins given, with a clock delay of 1μs
+    ///
+    /// * `mosi` - The GPIO pin used to send data to the device
+    /// * `miso` - The GPIO pin used to receive data from the device
+    /// * `sclk` - The GPIO pin used for the clock
+    /// * `cs` - The GPIO pin used to select the device, which is active low
+    ///
+    pub fn new(mosi: u8, miso: u8, sclk: u8, cs: u8) -> BitBangSpi {
+        let mut cs = OutputDevice::new(cs);
+        cs.set_active_high(false);
+        cs.off();
+        let mut sclk = OutputDevice::new(sclk);
+        sclk.off();
+        BitBangSpi {
+            mosi: OutputDevice::new(mosi),
+            miso: InputDevice::new(miso),
+            sclk,
+            cs,
+            clock_delay: 1,
+        }
+    }
+
+    /// Send `data` to the device while reading the same number of bytes back.
+    /// The device is selected for the duration of the transfer.
+    pub fn transfer(&mut self, data: &[u8]) -> Vec<u8> {
+        self.cs.on();
+        let received = data.iter().map(|byte| self.transfer_byte(*byte)).collect();
+        self.cs.off();
+        received
+    }
+
+    fn transfer_byte(&mut self, byte: u8) -> u8 {
+        let mut received = 0;
+        for bit in (0..8).rev() {
+            if byte & (1 << bit) != 0 {
+                self.mosi.on();
+            } else {
+                self.mosi.off();
+            }
+            sleep_micros(self.clock_delay);
+            self.sclk.on();
+            if self.miso.is_active() {
+                received |= 1 << bit;
+            }
+            sleep_micros(self.clock_delay);
+            self.sclk.off();
+        }
+        received
+    }
+
+    /// Set the time to wait between clock edges, in microseconds.
+    /// A longer delay gives slow devices or long wires time to settle.
+    pub fn set_clock_delay(&mut self, us: u64) {
+        self.clock_delay = us;
+    }
+
+    /// Get the time to wait between clock edges, in microseconds
+    pub fn get_clock_delay(&self) -> u64 {
+        self.clock_delay
+    }
+}
@@ -0,0 +1,270 @@
+//! Driving blinks and fades from the caller's own loop, without background threads.
+//!
+//! Devices normally play a blink on a thread of their own. For single-threaded runtimes, a
+//! blink can instead be registered with a `TickDriver`, which advances every registered
+//! blink each time `tick` is called.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::tick::TickDriver;
+//! use rust_gpiozero::*;
+//! use std::thread;
+//! use std::time::{Duration, Instant};
+//!
+//! let mut driver = TickDriver::new();
+//! let mut led = LED::new(17);
+//! let mut pwm_led = PWMLED::new(18);
+//!
+//! led.blink_ticked(&mut driver, 0.5, 0.5, Some(10));
+//! pwm_led.blink_ticked(&mut driver, 0.0, 0.0, 1.0, 1.0, None);
+//!
+//! while !driver.is_idle() {
+//!     driver.tick(Instant::now());
+//!     thread::sleep(Duration::from_millis(5));
+//! }
+//! ```
+use crate::timing::{seconds, Sink};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+/// Advances the blinks and fades registered with it from a single `tick` call, in place of
+/// each device's background thread. Steps are only as accurate as the interval between ticks.
+#[derive(Debug, Default)]
+pub struct TickDriver {
+    patterns: Vec<Ticked>,
+}
+
+impl TickDriver {
+    /// Returns a TickDriver with nothing registered
+    pub fn new() -> TickDriver {
+        TickDriver::default()
+    }
+
+    /// Advance every registered blink to `now`, writing each step that has started since the
+    /// last tick. Blinks which have finished, been stopped or whose device has been dropped are removed.
+    pub fn tick(&mut self, now: Instant) {
+        self.patterns.retain_mut(|pattern| pattern.advance(now));
+    }
+
+    /// Returns ``True`` if no blinks are registered, e.g. because they have all finished.
+    pub fn is_idle(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    // Register the `(value, seconds)` steps of `sequence` to be written to `sink`, `n` times or
+    // forever if `n` is `None`. The blink belongs to the device whose tick generation is
+    // `generation`, and is dropped when the device is, or when it bumps the generation to stop it.
+    pub(crate) fn register<S: Sink + Send + 'static>(
+        &mut self,
+        sink: S,
+        sequence: Vec<(f32, f32)>,
+        n: Option<i32>,
+        generation: &Arc<AtomicU64>,
+        running: &Arc<AtomicBool>,
+        remaining_blinks: &Arc<AtomicI32>,
+    ) {
+        if sequence.is_empty() {
+            return;
+        }
+        // a sequence that takes no time would repeat forever within a single tick
+        let n = if sequence
+            .iter()
+            .all(|(_, delay)| seconds(*delay) == Duration::from_secs(0))
+        {
+            Some(n.unwrap_or(1))
+        } else {
+            n
+        };
+        self.patterns.push(Ticked {
+            sink: Box::new(sink),
+            sequence,
+            n,
+            owner: Arc::downgrade(generation),
+            generation: generation.load(Ordering::SeqCst),
+            running: Arc::clone(running),
+            remaining_blinks: Arc::clone(remaining_blinks),
+            step: 0,
+            count: 0,
+            deadline: None,
+        });
+    }
+}
+
+// A blink registered with a TickDriver, and how far through it the driver is
+struct Ticked {
+    sink: Box<dyn Sink + Send>,
+    sequence: Vec<(f32, f32)>,
+    n: Option<i32>,
+    owner: Weak<AtomicU64>,
+    generation: u64,
+    running: Arc<AtomicBool>,
+    remaining_blinks: Arc<AtomicI32>,
+    step: usize,
+    count: i32,
+    // when the current step ends, or `None` if the first step hasn't been written yet
+    deadline: Option<Instant>,
+}
+
+impl fmt::Debug for Ticked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ticked")
+            .field("sequence", &self.sequence)
+            .field("n", &self.n)
+            .field("step", &self.step)
+            .field("count", &self.count)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl Ticked {
+    // Returns ``True`` if the device still exists and hasn't stopped or replaced this blink
+    fn is_current(&self) -> bool {
+        self.owner
+            .upgrade()
+            .is_some_and(|generation| generation.load(Ordering::SeqCst) == self.generation)
+            && self.running.load(Ordering::SeqCst)
+    }
+
+    // Write every step which has started by `now`. Returns ``False`` once the blink is over.
+    fn advance(&mut self, now: Instant) -> bool {
+        if !self.is_current() {
+            return false;
+        }
+        let mut deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => {
+                let (value, delay) = self.sequence[0];
+                self.sink.write(f64::from(value));
+                now + seconds(delay)
+            }
+        };
+        while now >= deadline {
+            self.step += 1;
+            if self.step == self.sequence.len() {
+                self.step = 0;
+                if let Some(n) = self.n {
+                    self.count += 1;
+                    self.remaining_blinks.fetch_sub(1, Ordering::SeqCst);
+                    if self.count >= n {
+                        self.remaining_blinks.store(0, Ordering::SeqCst);
+                        self.running.store(false, Ordering::SeqCst);
+                        return false;
+                    }
+                }
+            }
+            let (value, delay) = self.sequence[self.step];
+            self.sink.write(f64::from(value));
+            deadline += seconds(delay);
+        }
+        self.deadline = Some(deadline);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // A sink whose values can still be read once the driver owns it
+    struct SharedSink(Arc<Mutex<Vec<f64>>>);
+
+    impl Sink for SharedSink {
+        fn write(&mut self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    struct Blink {
+        values: Arc<Mutex<Vec<f64>>>,
+        generation: Arc<AtomicU64>,
+        running: Arc<AtomicBool>,
+        remaining_blinks: Arc<AtomicI32>,
+    }
+
+    impl Blink {
+        fn register(driver: &mut TickDriver, sequence: Vec<(f32, f32)>, n: Option<i32>) -> Blink {
+            let blink = Blink {
+                values: Arc::new(Mutex::new(Vec::new())),
+                generation: Arc::new(AtomicU64::new(0)),
+                running: Arc::new(AtomicBool::new(true)),
+                remaining_blinks: Arc::new(AtomicI32::new(n.unwrap_or(-1))),
+            };
+            driver.register(
+                SharedSink(Arc::clone(&blink.values)),
+                sequence,
+                n,
+                &blink.generation,
+                &blink.running,
+                &blink.remaining_blinks,
+            );
+            blink
+        }
+
+        fn values(&self) -> Vec<f64> {
+            self.values.lock().unwrap().clone()
+        }
+    }
+
+    fn after(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn a_counted_blink_stops_after_its_last_repetition() {
+        let start = Instant::now();
+        let mut driver = TickDriver::new();
+        let blink = Blink::register(&mut driver, vec![(1.0, 0.1), (0.0, 0.1)], Some(2));
+
+        driver.tick(start);
+        driver.tick(after(start, 100));
+        assert_eq!(blink.values(), vec![1.0, 0.0]);
+        assert_eq!(blink.remaining_blinks.load(Ordering::SeqCst), 2);
+        driver.tick(after(start, 200));
+        assert_eq!(blink.remaining_blinks.load(Ordering::SeqCst), 1);
+        assert!(!driver.is_idle());
+
+        driver.tick(after(start, 400));
+        assert_eq!(blink.values(), vec![1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(blink.remaining_blinks.load(Ordering::SeqCst), 0);
+        assert!(!blink.running.load(Ordering::SeqCst));
+        assert!(driver.is_idle());
+    }
+
+    #[test]
+    fn a_late_tick_writes_every_step_it_passed() {
+        let start = Instant::now();
+        let mut driver = TickDriver::new();
+        let blink = Blink::register(&mut driver, vec![(1.0, 0.1), (0.0, 0.1)], None);
+        driver.tick(start);
+        driver.tick(after(start, 350));
+        assert_eq!(blink.values(), vec![1.0, 0.0, 1.0, 0.0]);
+        assert!(!driver.is_idle());
+    }
+
+    #[test]
+    fn a_sequence_taking_no_time_is_played_once() {
+        let start = Instant::now();
+        let mut driver = TickDriver::new();
+        let blink = Blink::register(&mut driver, vec![(0.5, 0.0)], None);
+        driver.tick(start);
+        assert_eq!(blink.values(), vec![0.5]);
+        assert!(driver.is_idle());
+    }
+
+    #[test]
+    fn a_blink_is_dropped_when_its_device_stops_it() {
+        let start = Instant::now();
+        let mut driver = TickDriver::new();
+        let blink = Blink::register(&mut driver, vec![(1.0, 0.1), (0.0, 0.1)], None);
+        driver.tick(start);
+        blink.generation.fetch_add(1, Ordering::SeqCst);
+        driver.tick(after(start, 100));
+        assert_eq!(blink.values(), vec![1.0]);
+        assert!(driver.is_idle());
+    }
+}
@@ -0,0 +1,272 @@
+//! The timing loop shared by blinking and fading devices.
+//!
+//! Sleeping and writing to the pin go through the `Clock` and `Sink` traits, so the
+//! loop can be driven by a `VirtualClock` and recorded with a `RecordingSink` to check
+//! the exact sequence of writes without real GPIO or real sleeps.
+//!
+//! # Example
+//!
+//! ```
+//! use rust_gpiozero::timing::{play, RecordingSink, VirtualClock};
+//! use std::sync::atomic::{AtomicBool, AtomicI32};
+//! use std::time::Duration;
+//!
+//! let mut clock = VirtualClock::new();
+//! let mut sink = RecordingSink::new();
+//! let running = AtomicBool::new(true);
+//! let remaining_blinks = AtomicI32::new(3);
+//!
+//! // blink(0.1, 0.2) three times
+//! let sequence = [(1.0, 0.1), (0.0, 0.2)];
+//! assert!(play(&mut clock, &mut sink, &sequence, Some(3), &running, &remaining_blinks));
+//!
+//! assert_eq!(sink.values, vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+//! assert_eq!(clock.elapsed(), Duration::from_millis(900));
+//! ```
+use crate::devices::sleep_micros;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Something that can wait for a period of time
+pub trait Clock {
+    /// Wait for `duration`
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Something that values can be written to, such as a device's pin
+pub trait Sink {
+    /// Write a value between 0.0 (off) and 1.0 (fully on)
+    fn write(&mut self, value: f64);
+}
+
+/// A `Clock` which really sleeps, with microsecond precision
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&mut self, duration: Duration) {
+        sleep_micros(duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros()));
+    }
+}
+
+/// How a device waits between the steps of a blink or fade.
+///
+/// * `Sleep` yields the CPU for the whole wait, but the OS may wake the thread tens of
+///   microseconds or more late, which makes intervals of a few milliseconds jittery. It is the
+///   default, since it leaves the CPU free however many devices are blinking.
+/// * `Spin` busy waits for the whole wait. It is as accurate as the system clock, but keeps a
+///   CPU core fully busy for as long as the device is blinking.
+/// * `Hybrid` sleeps for most of the wait and busy waits for the last 200μs. This is as accurate
+///   as `Spin` for most intervals while only spinning briefly.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimingStrategy {
+    /// Sleep for the whole wait
+    #[default]
+    Sleep,
+    /// Busy wait for the whole wait
+    Spin,
+    /// Sleep, then busy wait for the end of the wait
+    Hybrid,
+}
+
+impl TimingStrategy {
+    /// Returns the shortest step of a blink that the strategy can time reliably. These are
+    /// conservative figures for a Raspberry Pi rather than measurements of the current system:
+    /// `Sleep` can wake up a few hundred microseconds late when the system is busy, so it is good
+    /// for steps of 1ms and up, while `Spin` and `Hybrid` are limited by the time taken to
+    /// write to the pin between steps, about 50μs on the slower models.
+    pub fn min_interval(self) -> Duration {
+        match self {
+            TimingStrategy::Sleep => Duration::from_millis(1),
+            TimingStrategy::Spin | TimingStrategy::Hybrid => Duration::from_micros(50),
+        }
+    }
+}
+
+impl Clock for TimingStrategy {
+    fn sleep(&mut self, duration: Duration) {
+        match self {
+            TimingStrategy::Sleep => thread::sleep(duration),
+            TimingStrategy::Spin => {
+                let deadline = Instant::now() + duration;
+                while Instant::now() < deadline {
+                    std::hint::spin_loop();
+                }
+            }
+            TimingStrategy::Hybrid => SystemClock.sleep(duration),
+        }
+    }
+}
+
+/// A `Clock` which stops a timing loop once a total amount of time has been slept for.
+/// A sleep that would run past the limit is cut short, and `running` is cleared when the limit is reached.
+#[derive(Debug)]
+pub struct LimitedClock<'a, C: Clock> {
+    clock: C,
+    remaining: Duration,
+    running: &'a AtomicBool,
+}
+
+impl<'a, C: Clock> LimitedClock<'a, C> {
+    /// Returns a LimitedClock which sleeps with `clock` for at most `limit` in total
+    pub fn new(clock: C, limit: Duration, running: &'a AtomicBool) -> LimitedClock<'a, C> {
+        LimitedClock {
+            clock,
+            remaining: limit,
+            running,
+        }
+    }
+}
+
+impl<'a, C: Clock> Clock for LimitedClock<'a, C> {
+    fn sleep(&mut self, duration: Duration) {
+        let duration = duration.min(self.remaining);
+        self.clock.sleep(duration);
+        self.remaining -= duration;
+        if self.remaining == Duration::from_secs(0) {
+            self.running.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// How long a `StoppableClock` sleeps for at a time before checking if it has been stopped
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A `Clock` which wakes up early once `running` is cleared, so a long step of a timing loop
+/// can be interrupted. It sleeps towards a deadline in slices of at most 10ms, checking `running` after each.
+#[derive(Debug)]
+pub struct StoppableClock<'a, C: Clock> {
+    clock: C,
+    running: &'a AtomicBool,
+}
+
+impl<'a, C: Clock> StoppableClock<'a, C> {
+    /// Returns a StoppableClock which sleeps with `clock` while `running` is set
+    pub fn new(clock: C, running: &'a AtomicBool) -> StoppableClock<'a, C> {
+        StoppableClock { clock, running }
+    }
+}
+
+impl<'a, C: Clock> Clock for StoppableClock<'a, C> {
+    fn sleep(&mut self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while self.running.load(Ordering::SeqCst) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                break;
+            }
+            self.clock.sleep(remaining.min(STOP_CHECK_INTERVAL));
+        }
+    }
+}
+
+/// A `Clock` which returns immediately and only keeps track of how long it has slept for
+#[derive(Debug, Default, Clone)]
+pub struct VirtualClock {
+    /// Every duration slept for, in order
+    pub sleeps: Vec<Duration>,
+}
+
+impl VirtualClock {
+    /// Returns a VirtualClock which hasn't slept yet
+    pub fn new() -> VirtualClock {
+        VirtualClock::default()
+    }
+
+    /// Returns the total time slept for
+    pub fn elapsed(&self) -> Duration {
+        self.sleeps.iter().sum()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn sleep(&mut self, duration: Duration) {
+        self.sleeps.push(duration);
+    }
+}
+
+/// A `Sink` which records every value written to it
+#[derive(Debug, Default, Clone)]
+pub struct RecordingSink {
+    /// Every value written, in order
+    pub values: Vec<f64>,
+}
+
+impl RecordingSink {
+    /// Returns a RecordingSink with nothing written to it
+    pub fn new() -> RecordingSink {
+        RecordingSink::default()
+    }
+}
+
+impl Sink for RecordingSink {
+    fn write(&mut self, value: f64) {
+        self.values.push(value);
+    }
+}
+
+/// Converts a number of seconds, as taken by the device methods, to a `Duration`
+pub(crate) fn seconds(seconds: f32) -> Duration {
+    Duration::from_micros((seconds.max(0.0) * 1_000_000.0) as u64)
+}
+
+/// Warn if a step of `sequence` is shorter than `strategy` can time reliably.
+/// Steps of zero seconds, such as the ends of fades, are instantaneous on purpose and are fine.
+pub(crate) fn check_intervals(sequence: &[(f32, f32)], strategy: TimingStrategy) {
+    let min = strategy.min_interval();
+    if let Some((_, delay)) = sequence
+        .iter()
+        .find(|(_, delay)| *delay > 0.0 && seconds(*delay) < min)
+    {
+        println!(
+            "A step of {}s is shorter than the {:?} timing strategy can reliably time ({:?})",
+            delay, strategy, min
+        );
+    }
+}
+
+/// Write each `(value, seconds)` step of `sequence` to `sink`, holding it for its duration.
+/// The sequence is played `n` times, or until `running` is cleared if `n` is `None`.
+/// `remaining_blinks` is decremented after each repetition of a bounded sequence.
+/// Returns ``True`` if the sequence ran to the end and ``False`` if it was stopped by clearing `running`.
+///
+/// A count of zero plays nothing:
+///
+/// ```
+/// use rust_gpiozero::timing::{play, RecordingSink, VirtualClock};
+/// use std::sync::atomic::{AtomicBool, AtomicI32};
+///
+/// let mut clock = VirtualClock::new();
+/// let mut sink = RecordingSink::new();
+/// let sequence = [(1.0, 0.1), (0.0, 0.2)];
+/// assert!(play(&mut clock, &mut sink, &sequence, Some(0), &AtomicBool::new(true), &AtomicI32::new(0)));
+/// assert!(sink.values.is_empty());
+/// ```
+pub fn play<C: Clock, S: Sink>(
+    clock: &mut C,
+    sink: &mut S,
+    sequence: &[(f32, f32)],
+    n: Option<i32>,
+    running: &AtomicBool,
+    remaining_blinks: &AtomicI32,
+) -> bool {
+    let mut count = 0;
+    while !matches!(n, Some(end) if count >= end) {
+        for (value, delay) in sequence {
+            if !running.load(Ordering::SeqCst) {
+                return false;
+            }
+            sink.write(f64::from(*value));
+            clock.sleep(seconds(*delay));
+        }
+        count += 1;
+        if n.is_some() {
+            remaining_blinks.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+    if n.is_some() {
+        remaining_blinks.store(0, Ordering::SeqCst);
+    }
+    true
+}
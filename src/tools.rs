@@ -0,0 +1,56 @@
+//! Value generators for driving devices with `set_source`, such as low frequency
+//! oscillators for breathing and wave effects.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rust_gpiozero::tools::sine_wave;
+//! use rust_gpiozero::*;
+//! use std::time::Duration;
+//!
+//! let mut led = PWMLED::new(17);
+//! // breathe in and out every 2 seconds
+//! led.set_source(sine_wave(Duration::from_secs(2)));
+//! led.wait();
+//! ```
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+/// Returns an endless iterator of values following `shape`, a function of the phase
+/// (0.0 to 1.0) of the current period, based on the time elapsed since it was created.
+fn wave<F>(period: Duration, shape: F) -> impl Iterator<Item = f64> + Send
+where
+    F: Fn(f64) -> f64 + Send,
+{
+    let start = Instant::now();
+    let period = period.as_secs() as f64 + f64::from(period.subsec_nanos()) / 1_000_000_000.0;
+    std::iter::repeat_with(move || {
+        if period <= 0.0 {
+            return shape(0.0);
+        }
+        let elapsed = start.elapsed();
+        let elapsed =
+            elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+        shape((elapsed / period).fract())
+    })
+}
+
+/// Returns values between 0.0 and 1.0 following a sine wave with the period given,
+/// starting at 0.0.
+/// * `period` - The time taken for a full cycle
+pub fn sine_wave(period: Duration) -> impl Iterator<Item = f64> + Send {
+    wave(period, |phase| (1.0 - (2.0 * PI * phase).cos()) / 2.0)
+}
+
+/// Returns values between 0.0 and 1.0 rising and falling linearly with the period given,
+/// starting at 0.0.
+/// * `period` - The time taken for a full cycle
+pub fn triangle_wave(period: Duration) -> impl Iterator<Item = f64> + Send {
+    wave(period, |phase| 1.0 - (2.0 * phase - 1.0).abs())
+}
+
+/// Returns values rising linearly from 0.0 to 1.0 over the period given, then starting again at 0.0.
+/// * `period` - The time taken for a full cycle
+pub fn sawtooth(period: Duration) -> impl Iterator<Item = f64> + Send {
+    wave(period, |phase| phase)
+}
@@ -0,0 +1,425 @@
+//! Behaviour of output devices, checked against pins simulated by `MockPinFactory`.
+//! Reservations are shared by every test in the binary, so each test uses its own pins.
+use rppal::gpio::{Level, Mode};
+use rust_gpiozero::mock::MockPinFactory;
+use rust_gpiozero::pins::with_pin_factory;
+use rust_gpiozero::*;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Construct a device with `f` on a new mock factory, which is returned for inspecting the pins
+fn mock<D, F: FnOnce() -> D>(f: F) -> (MockPinFactory, D) {
+    let factory = MockPinFactory::new();
+    let device = with_pin_factory(Arc::new(factory.clone()), f);
+    (factory, device)
+}
+
+// Wait for `condition` to hold, which is given a few seconds to allow for a busy system, so
+// that tests synchronise with background threads rather than sleeping for a fixed time
+fn eventually<F: FnMut() -> bool>(mut condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "condition not reached in time");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn on_during_blink_is_not_undone_by_the_blink_thread() {
+    let (factory, mut led) = mock(|| LED::new(2));
+    led.blink(0.05, 0.05);
+    eventually(|| factory.pin(2).levels().len() > 2);
+    // `on` stops the blink thread before turning the LED on, so nothing is written afterwards
+    led.on();
+    let levels = factory.pin(2).levels();
+    led.wait();
+    assert_eq!(factory.pin(2).levels(), levels);
+    assert_eq!(levels.last(), Some(&true));
+    assert!(led.value());
+}
+
+#[test]
+fn dropping_a_blinking_led_releases_its_pin_straight_away() {
+    let (factory, mut led) = mock(|| LED::new(3));
+    led.blink(60.0, 60.0);
+    eventually(|| factory.pin(3).is_high());
+    // waiting for the step to end would take a minute
+    let start = Instant::now();
+    drop(led);
+    assert!(start.elapsed() < Duration::from_secs(30));
+    assert!(!factory.pin(3).is_high());
+    assert!(with_pin_factory(Arc::new(factory), || LED::try_new(3)).is_ok());
+}
+
+#[test]
+fn status_indicator_stays_on_after_warning_clears() {
+    let (factory, mut indicator) = mock(|| StatusIndicator::new(4));
+    indicator.set_state(Status::Warning);
+    eventually(|| factory.pin(4).is_high());
+    // the warning blink is stopped before the LED is turned on, so it can't turn it off again
+    indicator.set_state(Status::Ok);
+    assert_eq!(factory.pin(4).levels().last(), Some(&true));
+    assert!(factory.pin(4).is_high());
+}
+
+#[test]
+fn a_new_tone_is_not_cut_off_by_the_previous_melody() {
+    let (factory, mut buzzer) = mock(|| TonalBuzzer::new(5));
+    buzzer.play_rtttl("long:d=1,o=5,b=4:c,c").unwrap();
+    eventually(|| factory.pin(5).pwm().is_some());
+    // the melody is stopped before the tone starts, rather than left to finish its minute-long note
+    let start = Instant::now();
+    buzzer.play(440.0);
+    assert!(start.elapsed() < Duration::from_secs(30));
+    buzzer.wait();
+    assert!(buzzer.is_playing());
+    assert_eq!(
+        factory.pin(5).pwm().map(|(frequency, _)| frequency),
+        Some(440.0)
+    );
+}
+
+#[test]
+fn rgbled_fade_ends_at_the_target_color() {
+    let (factory, mut led) = mock(|| RGBLED::new(6, 7, 8));
+    led.fade_to_color((1.0, 0.5, 0.25), Duration::from_millis(200));
+    led.wait();
+    assert_eq!(factory.pin(6).pwm().map(|(_, duty)| duty), Some(1.0));
+    assert_eq!(factory.pin(7).pwm().map(|(_, duty)| duty), Some(0.5));
+    assert_eq!(factory.pin(8).pwm().map(|(_, duty)| duty), Some(0.25));
+}
+
+#[test]
+fn dithered_pwm_errors_are_returned() {
+    let (factory, mut device) = mock(|| PWMOutputDevice::new(9));
+    device.set_dither(true);
+    factory.pin(9).fail_pwm(true);
+    assert!(device.try_set_value(0.505).is_err());
+    factory.pin(9).fail_pwm(false);
+    assert!(device.try_set_value(0.505).is_ok());
+    assert!((device.value() - 0.505).abs() < f64::EPSILON);
+}
+
+#[test]
+fn rgbled_fade_stops_on_pwm_errors() {
+    let (factory, mut led) = mock(|| RGBLED::new(10, 11, 12));
+    factory.pin(11).fail_pwm(true);
+    led.fade_to_color((1.0, 1.0, 1.0), Duration::from_secs(60));
+    led.wait();
+    assert_eq!(led.remaining_blinks(), Some(1));
+    assert_eq!(factory.pin(11).pwm(), None);
+}
+
+#[test]
+fn servo_detach_timer_is_cancelled_by_the_next_move() {
+    let (factory, mut servo) = mock(|| Servo::new(13));
+    servo.set_frame_timeout(Some(Duration::from_millis(300)));
+    servo.max();
+    thread::sleep(Duration::from_millis(200));
+    servo.min();
+    let moved = Instant::now();
+    assert!(factory.pin(13).pwm().is_some());
+    // the timer started by `max` would have detached the servo 100ms after `min`
+    eventually(|| factory.pin(13).pwm().is_none());
+    assert!(moved.elapsed() >= Duration::from_millis(300));
+    servo.mid();
+    drop(servo);
+    assert!(!factory.pin(13).in_use());
+}
+
+#[test]
+fn energenie_sockets_share_the_board() {
+    let (factory, (mut first, second)) = mock(|| (Energenie::new(1), Energenie::new(2)));
+    first.on();
+    // socket 1 is code 7, with bit 3 set to switch it on
+    let bits: Vec<bool> = [17, 22, 23, 27]
+        .iter()
+        .map(|pin| factory.pin(*pin).is_high())
+        .collect();
+    assert_eq!(bits, vec![true, true, true, true]);
+    drop(first);
+    assert!(factory.pin(25).in_use());
+    drop(second);
+    assert!(!factory.pin(25).in_use());
+}
+
+#[test]
+fn unfollow_removes_the_led_handler_from_the_button() {
+    let (factory, (mut led, mut button)) = mock(|| (LED::new(14), Button::new(15)));
+    led.follow(&mut button);
+    // handlers are called in order, so the LED has followed each edge by the time it arrives here
+    let (tx, rx) = mpsc::channel();
+    button.add_handler(move |event| tx.send(event.active).unwrap());
+    factory.pin(15).drive_low();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(true));
+    assert!(factory.pin(14).is_high());
+    led.unfollow();
+    factory.pin(15).drive_high();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(false));
+    assert!(factory.pin(14).is_high());
+    // the handler held the LED's device, so dropping the LED now releases its pin
+    drop(led);
+    assert!(!factory.pin(14).in_use());
+}
+
+#[test]
+fn removing_the_last_handler_while_events_arrive_does_not_deadlock() {
+    let (factory, mut button) = mock(|| Button::new(16));
+    let token = button.add_handler(|_| {});
+    let pin = factory.pin(16);
+    let toggling = thread::spawn(move || {
+        for i in 0..500 {
+            if i % 2 == 0 {
+                pin.drive_low();
+            } else {
+                pin.drive_high();
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    thread::sleep(Duration::from_millis(50));
+    let start = Instant::now();
+    assert!(button.remove_handler(token));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    toggling.join().unwrap();
+}
+
+#[test]
+fn fade_to_carries_on_from_the_current_fade() {
+    let (factory, mut device) = mock(|| PWMOutputDevice::new(18));
+    device.fade_to(1.0, Duration::from_secs(1));
+    eventually(|| factory.pin(18).pwm().is_some_and(|(_, duty)| duty > 0.3));
+    device.fade_to(0.0, Duration::from_millis(200));
+    // the pin isn't dropped to 0% before the new fade starts
+    let (_, duty) = factory.pin(18).pwm().unwrap();
+    assert!(duty > 0.3, "duty {} at the start of the second fade", duty);
+    device.wait();
+    assert_eq!(device.value(), 0.0);
+    assert_eq!(factory.pin(18).pwm().map(|(_, duty)| duty), Some(0.0));
+}
+
+#[test]
+fn wait_returns_straight_away_when_nothing_is_blinking() {
+    let (_factory, mut led) = mock(|| LED::new(30));
+    led.wait();
+    led.set_blink_count(1);
+    led.blink(0.05, 0.05);
+    led.wait();
+    assert!(!led.is_active());
+    led.wait();
+}
+
+#[test]
+fn servo_pulse_widths_must_fit_in_the_frame() {
+    let (_factory, mut servo) = mock(|| Servo::new(20));
+    servo.set_max_pulse_width(25_000);
+    assert_eq!(servo.get_max_pulse_width(), 2000);
+    assert!(servo.set_pulse_widths(1000, 25_000).is_err());
+    servo.set_frame_width(2);
+    assert_eq!(servo.get_frame_width(), 20);
+    servo.set_frame_width(30);
+    servo.set_max_pulse_width(25_000);
+    assert_eq!(servo.get_max_pulse_width(), 25_000);
+}
+
+#[test]
+fn stopping_an_active_low_blink_leaves_the_pin_high() {
+    let (factory, mut led) = mock(|| LED::new(21));
+    led.set_active_high(false);
+    led.blink(0.05, 0.05);
+    eventually(|| factory.pin(21).levels().len() > 2);
+    // `off` stops the blink before turning the LED off
+    led.off();
+    assert!(!led.is_active());
+    assert!(factory.pin(21).is_high());
+
+    led.set_blink_count(2);
+    led.blink(0.05, 0.05);
+    led.wait();
+    assert!(!led.is_active());
+    assert!(factory.pin(21).is_high());
+}
+
+#[test]
+fn mixed_polarity_board_turns_every_led_off() {
+    let (factory, board) = mock(|| {
+        let mut active_low = LED::new(0);
+        active_low.set_active_high(false);
+        LEDBoard::from_leds(vec![active_low, LED::new(1)])
+    });
+    board.on();
+    assert!(!factory.pin(0).is_high());
+    assert!(factory.pin(1).is_high());
+    board.off();
+    assert!(factory.pin(0).is_high());
+    assert!(!factory.pin(1).is_high());
+    assert!(!board.is_active());
+}
+
+#[test]
+fn concurrent_toggles_are_not_lost() {
+    let (factory, device) = mock(|| DigitalOutputDevice::new(26));
+    let device = Arc::new(device);
+    // an odd number of toggles in total, so the device ends up on
+    let threads: Vec<_> = (0..7)
+        .map(|_| {
+            let device = Arc::clone(&device);
+            thread::spawn(move || {
+                for _ in 0..101 {
+                    device.toggle();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert!(device.value());
+    assert!(factory.pin(26).is_high());
+}
+
+#[test]
+fn blink_with_a_count_of_zero_turns_the_led_off() {
+    let (factory, mut led) = mock(|| LED::new(28));
+    led.on();
+    led.set_blink_count(0);
+    led.blink(60.0, 60.0);
+    // there is no blink thread to wait for
+    led.wait();
+    assert!(!led.is_active());
+    assert_eq!(led.remaining_blinks(), Some(0));
+    assert_eq!(factory.pin(28).levels(), vec![false, true, false]);
+}
+
+#[test]
+fn from_label_returns_an_error_for_a_pin_in_use() {
+    let (_factory, _led) = mock(|| LED::from_label("GPIO19").unwrap());
+    // a pin in use on one mock factory is in use on every one, as they stand for the same pins
+    let (_, second) = mock(|| LED::from_label("BCM19"));
+    assert!(matches!(second, Err(GpioZeroError::PinInUse(19, _))));
+    assert!(LED::from_label("PIN19").is_err());
+}
+
+#[test]
+fn re_enabling_the_toggle_interval_keeps_applying_deferred_writes() {
+    let (factory, mut device) = mock(|| DigitalOutputDevice::new(31));
+    device.set_min_toggle_interval(Duration::from_millis(100));
+    device.set_min_toggle_interval(Duration::from_secs(0));
+    device.set_min_toggle_interval(Duration::from_millis(100));
+    device.on();
+    device.off();
+    assert!(factory.pin(31).is_high());
+    eventually(|| !factory.pin(31).is_high());
+    assert_eq!(factory.pin(31).levels(), vec![false, true, false]);
+}
+
+#[test]
+fn a_device_factory_on_a_missing_chip_returns_an_error() {
+    let mut devices = DeviceFactory::new();
+    devices.set_chip("/dev/gpiochip-missing");
+    let built = devices.build(&[(PinKind::LED, 40, "status")]);
+    assert!(matches!(built, Err(GpioZeroError::Io(_))));
+    assert!(matches!(
+        OutputDevice::try_new_on_chip("/dev/gpiochip-missing", 40),
+        Err(GpioZeroError::Io(_))
+    ));
+}
+
+// Simulated pins of another Pi, which are reserved separately from the local pins
+struct OtherPi(MockPinFactory);
+
+impl pins::PinFactory for OtherPi {
+    fn input_pin(
+        &self,
+        pin: u8,
+        pull: rppal::gpio::PullUpDown,
+    ) -> Result<Box<dyn pins::InputPin>, GpioZeroError> {
+        self.0.input_pin(pin, pull)
+    }
+
+    fn io_pin(
+        &self,
+        pin: u8,
+        mode: rppal::gpio::Mode,
+    ) -> Result<Box<dyn pins::IoPin>, GpioZeroError> {
+        self.0.io_pin(pin, mode)
+    }
+
+    fn pin_available(&self, pin: u8) -> bool {
+        self.0.pin_available(pin)
+    }
+
+    fn id(&self) -> String {
+        "10.0.0.2:8888".to_string()
+    }
+}
+
+#[test]
+fn pins_with_the_same_number_on_different_factories_can_be_used_together() {
+    let (local, mut led) = mock(|| LED::new(33));
+    let remote = MockPinFactory::new();
+    let other_pi: Arc<dyn pins::PinFactory> = Arc::new(OtherPi(remote.clone()));
+    let mut remote_led = with_pin_factory(other_pi.clone(), || LED::new(33));
+    assert!(matches!(
+        with_pin_factory(other_pi.clone(), || LED::try_new(33)),
+        Err(GpioZeroError::PinInUse(33, _))
+    ));
+
+    led.set_tag("location", "kitchen");
+    remote_led.set_tag("location", "garage");
+    remote_led.on();
+    assert!(remote.pin(33).is_high());
+    assert!(!local.pin(33).is_high());
+    assert_eq!(registry::find_by_tag("location", "kitchen"), vec![33]);
+    assert!(registry::find_by_tag("location", "garage").is_empty());
+    assert_eq!(
+        with_pin_factory(other_pi, || registry::find_by_tag("location", "garage")),
+        vec![33]
+    );
+}
+
+#[test]
+fn handlers_see_the_edge_a_wait_returns_for() {
+    let (factory, mut device) = mock(|| DigitalInputDevice::new(34));
+    let (tx, rx) = mpsc::channel();
+    device.add_handler(move |event| tx.send(event.active).unwrap());
+    let pin = factory.pin(34);
+    let driver = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        pin.drive_high();
+    });
+    device.wait_for_active(Some(5.0));
+    driver.join().unwrap();
+    assert!(device.is_active());
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+}
+
+#[test]
+fn presses_are_timed_without_waiting_for_them() {
+    let (factory, mut button) = mock(|| Button::new(35));
+    let (tx, rx) = mpsc::channel();
+    button.add_handler(move |event| tx.send(event.active).unwrap());
+    assert_eq!(button.last_press_duration(), None);
+
+    factory.pin(35).drive_low();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+    assert_eq!(button.last_press_duration(), None);
+    factory.pin(35).drive_high();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+    assert!(button.last_press_duration().is_some());
+}
+
+#[test]
+fn timed_sequences_release_an_open_drain_pin_instead_of_driving_it_high() {
+    let (factory, mut device) = mock(|| OutputDevice::new(37));
+    device.set_drive_mode(DriveMode::OpenDrain);
+    device.write_timed_sequence(&[
+        (Level::Low, Duration::from_micros(100)),
+        (Level::High, Duration::from_micros(100)),
+    ]);
+    assert_eq!(factory.pin(37).mode(), Mode::Input);
+    device.write_timed_sequence(&[(Level::Low, Duration::from_micros(100))]);
+    assert_eq!(factory.pin(37).mode(), Mode::Output);
+    assert!(!factory.pin(37).is_high());
+}